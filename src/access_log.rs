@@ -0,0 +1,111 @@
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use log::error;
+use serde_json::json;
+use crate::events::ResponseEvent;
+use crate::l10n;
+
+const MONTHS: [&str; 12] = ["Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec"];
+
+type Formatter = Arc<dyn Fn(&ResponseEvent<'_>, SystemTime) -> String + Sync + Send>;
+
+#[derive(Clone)]
+pub enum LogFormat {
+    Common,
+    Combined,
+    Json,
+    Custom(Formatter)
+}
+
+impl LogFormat {
+    pub fn custom(format: impl Fn(&ResponseEvent<'_>, SystemTime) -> String + Sync + Send + 'static) -> Self {
+        Self::Custom(Arc::new(format))
+    }
+
+    pub fn format(&self, event: &ResponseEvent<'_>, time: SystemTime) -> String {
+        let request = event.request;
+        let request_line = format!("{} {} HTTP/{:.1}", request.method().as_str(), request.target(), request.version());
+        let ip = request.socket_addr().ip();
+
+        match self {
+            Self::Common => format!("{ip} - - [{}] \"{request_line}\" {} {}", common_time(time), u16::from(event.status), event.bytes),
+            Self::Combined => format!(
+                "{ip} - - [{}] \"{request_line}\" {} {} \"{}\" \"{}\"",
+                common_time(time),
+                u16::from(event.status),
+                event.bytes,
+                request.header("referer").unwrap_or("-"),
+                request.header("user-agent").unwrap_or("-")
+            ),
+            Self::Json => json!({
+                "timestamp": iso_time(time),
+                "client_ip": ip.to_string(),
+                "method": request.method().as_str(),
+                "path": request.route(),
+                "status": u16::from(event.status),
+                "latency_ms": event.duration.as_secs_f64() * 1000.0,
+                "bytes_sent": event.bytes,
+                "error": event.error.map(|err| err.to_string())
+            }).to_string(),
+            Self::Custom(format) => format(event, time)
+        }
+    }
+}
+
+pub struct AccessLog {
+    format: LogFormat,
+    sink: Mutex<Box<dyn Write + Send>>
+}
+
+impl AccessLog {
+    pub fn writer(sink: impl Write + Send + 'static) -> Self {
+        Self {
+            format: LogFormat::Common,
+            sink: Mutex::new(Box::new(sink))
+        }
+    }
+
+    pub fn stdout() -> Self {
+        Self::writer(io::stdout())
+    }
+
+    pub fn file(path: impl AsRef<Path>) -> io::Result<Self> {
+        let file: File = OpenOptions::new().create(true).append(true).open(path)?;
+        Ok(Self::writer(file))
+    }
+
+    pub fn format(mut self, format: LogFormat) -> Self {
+        self.format = format;
+        self
+    }
+
+    pub fn record(&self, event: &ResponseEvent<'_>) {
+        let line = self.format.format(event, SystemTime::now());
+        let mut sink = self.sink.lock().unwrap();
+
+        if let Err(err) = writeln!(sink, "{line}").and_then(|_| sink.flush()) {
+            error!("Failed to write access log: {}", err);
+        }
+    }
+}
+
+fn common_time(time: SystemTime) -> String {
+    let (year, month, day) = l10n::civil_date(time);
+    let (hours, minutes, seconds) = clock(time);
+    format!("{day:02}/{}/{year}:{hours:02}:{minutes:02}:{seconds:02} +0000", MONTHS[month as usize - 1])
+}
+
+fn iso_time(time: SystemTime) -> String {
+    let (year, month, day) = l10n::civil_date(time);
+    let (hours, minutes, seconds) = clock(time);
+    let millis = time.duration_since(UNIX_EPOCH).unwrap_or_default().subsec_millis();
+    format!("{year}-{month:02}-{day:02}T{hours:02}:{minutes:02}:{seconds:02}.{millis:03}Z")
+}
+
+fn clock(time: SystemTime) -> (u64, u64, u64) {
+    let seconds = time.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs() % 86400;
+    (seconds / 3600, seconds / 60 % 60, seconds % 60)
+}
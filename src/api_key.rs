@@ -0,0 +1,215 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use crate::error::ServerError;
+use crate::message::{Request, Response};
+use crate::middleware::Next;
+
+pub const API_KEY_HEADER: &str = "x-api-key";
+pub const API_KEY_PARAM: &str = "api_key";
+
+#[derive(Debug, Clone)]
+pub struct ApiKey {
+    pub key: String,
+    pub scopes: Vec<String>,
+    pub rate_limit: Option<(u32, Duration)>
+}
+
+impl ApiKey {
+    pub fn new(key: &str) -> Self {
+        Self {
+            key: key.to_string(),
+            scopes: Vec::new(),
+            rate_limit: None
+        }
+    }
+
+    pub fn scope(mut self, scope: &str) -> Self {
+        self.scopes.push(scope.to_string());
+        self
+    }
+
+    pub fn rate_limit(mut self, requests: u32, window: Duration) -> Self {
+        self.rate_limit = Some((requests, window));
+        self
+    }
+
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scopes.iter().any(|s| s == scope)
+    }
+}
+
+pub trait ApiKeyStore: Sync + Send {
+    fn get(&self, key: &str) -> Option<ApiKey>;
+}
+
+impl ApiKeyStore for HashMap<String, ApiKey> {
+    fn get(&self, key: &str) -> Option<ApiKey> {
+        HashMap::get(self, key).cloned()
+    }
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum ApiKeyError {
+    Missing,
+    Invalid,
+    RateLimited,
+    Scope
+}
+
+impl Display for ApiKeyError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Missing => write!(f, "No API key was provided"),
+            Self::Invalid => write!(f, "The given API key is invalid"),
+            Self::RateLimited => write!(f, "The API key exceeded its rate limit"),
+            Self::Scope => write!(f, "The API key lacks the required scope")
+        }
+    }
+}
+
+impl Error for ApiKeyError {}
+
+pub struct ApiKeyAuth<S: ApiKeyStore> {
+    store: S,
+    query_param: Option<String>,
+    required_scopes: Vec<String>,
+    usage: Mutex<HashMap<String, (Instant, u32)>>
+}
+
+impl <S: ApiKeyStore> ApiKeyAuth<S> {
+    pub fn new(store: S) -> Self {
+        Self {
+            store,
+            query_param: Some(API_KEY_PARAM.to_string()),
+            required_scopes: Vec::new(),
+            usage: Mutex::new(HashMap::new())
+        }
+    }
+
+    pub fn query_param(mut self, param: Option<&str>) -> Self {
+        self.query_param = param.map(String::from);
+        self
+    }
+
+    pub fn require_scope(mut self, scope: &str) -> Self {
+        self.required_scopes.push(scope.to_string());
+        self
+    }
+
+    pub fn authenticate(&self, request: &Request) -> Result<ApiKey, ApiKeyError> {
+        let key = match request.header(API_KEY_HEADER) {
+            Some(key) => key.to_string(),
+            None => self.query_param.as_ref()
+                .and_then(|param| request.url().query_pairs().find(|(key, _)| key == param))
+                .map(|(_, value)| value.into_owned())
+                .ok_or(ApiKeyError::Missing)?
+        };
+
+        let api_key = self.store.get(&key).ok_or(ApiKeyError::Invalid)?;
+
+        if let Some((requests, window)) = api_key.rate_limit {
+            let mut usage = self.usage.lock().unwrap();
+            let (start, count) = usage.entry(key).or_insert((Instant::now(), 0));
+
+            if start.elapsed() > window {
+                *start = Instant::now();
+                *count = 0;
+            }
+
+            if *count >= requests {
+                return Err(ApiKeyError::RateLimited);
+            }
+
+            *count += 1;
+        }
+
+        Ok(api_key)
+    }
+
+    pub fn authorize(&self, request: &Request, scope: &str) -> Result<ApiKey, ApiKeyError> {
+        let api_key = self.authenticate(request)?;
+
+        if api_key.has_scope(scope) {
+            Ok(api_key)
+        } else {
+            Err(ApiKeyError::Scope)
+        }
+    }
+    pub fn check(&self, request: &Request) -> Result<ApiKey, ApiKeyError> {
+        let api_key = self.authenticate(request)?;
+
+        match self.required_scopes.iter().all(|scope| api_key.has_scope(scope)) {
+            true => Ok(api_key),
+            false => Err(ApiKeyError::Scope)
+        }
+    }
+
+    pub fn reject(err: ApiKeyError) -> Response {
+        match err {
+            ApiKeyError::Missing | ApiKeyError::Invalid => Response::text("Unauthorized", 401),
+            ApiKeyError::RateLimited => Response::text("Too many requests", 429),
+            ApiKeyError::Scope => Response::text("Forbidden", 403)
+        }
+    }
+
+    pub fn middleware<E: ServerError>(self) -> impl Fn(&mut Request, Next<'_, E>) -> Result<Response, E> + Sync + Send + 'static where S: 'static {
+        move |request, next| match self.check(request) {
+            Ok(api_key) => {
+                request.insert_ext(api_key);
+                next.run(request)
+            },
+            Err(err) => Ok(Self::reject(err))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+    use crate::error::DefaultError;
+    use crate::method::HttpMethod;
+    use crate::middleware::Middleware;
+    use crate::route::Router;
+    use super::*;
+
+    fn status(target: &str, headers: &str) -> (u16, String) {
+        let keys = HashMap::from([
+            ("reader".to_string(), ApiKey::new("reader").scope("read")),
+            ("writer".to_string(), ApiKey::new("writer").scope("read").scope("write"))
+        ]);
+
+        let auth = ApiKeyAuth::new(keys).require_scope("write");
+        let mut router = Router::new(|_: &Request| Err(DefaultError::NotFound));
+        router.add(HttpMethod::Get, "/items", |request: &Request| {
+            let api_key = request.ext::<ApiKey>().unwrap();
+            Ok(Response::text(format!("{} {}", api_key.key, api_key.scopes.join(",")), 200))
+        });
+
+        let middleware = [Middleware::new("/", auth.middleware())];
+        let bytes = format!("GET {target} HTTP/1.1\r\nHost: localhost\r\n{headers}\r\n");
+        let mut request = Request::from_bytes(SocketAddr::from(([127, 0, 0, 1], 1234)), bytes.as_bytes()).unwrap();
+        let response = Next::new(&middleware, &router).run(&mut request).unwrap();
+        (response.status().as_u16(), String::from_utf8_lossy(response.body()).into_owned())
+    }
+
+    #[test]
+    fn rejects_missing_and_unknown_keys() {
+        assert_eq!(status("/items", "").0, 401);
+        assert_eq!(status("/items", "X-Api-Key: nope\r\n").0, 401);
+        assert_eq!(status("/items?api_key=nope", "").0, 401);
+    }
+
+    #[test]
+    fn rejects_keys_without_the_required_scopes() {
+        assert_eq!(status("/items", "X-Api-Key: reader\r\n").0, 403);
+    }
+
+    #[test]
+    fn stores_the_key_in_the_request() {
+        assert_eq!(status("/items", "X-Api-Key: writer\r\n"), (200, "writer read,write".to_string()));
+        assert_eq!(status("/items?api_key=writer", ""), (200, "writer read,write".to_string()));
+    }
+}
@@ -0,0 +1,337 @@
+use std::future::Future;
+use std::io::{self, ErrorKind, Read, Write};
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::sync::Arc;
+use std::time::Duration;
+use log::{debug, error, info, warn};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::runtime::{self, Handle};
+use tokio::sync::mpsc::{self, Sender};
+use tokio::task;
+use tokio::time;
+use crate::error::{DEFAULT_HANDLER, DefaultError, ErrorAction, ServerError};
+use crate::http_server::{self, ServerConfig, BUFFER_SIZE, KEEP_ALIVE_TIMEOUT, SERVER_NAME};
+use crate::message::{Request, Response};
+use crate::method::HttpMethod;
+use crate::middleware::{self, Middleware, MiddlewareAction, MiddlewareInfo};
+use crate::panic_hook;
+use crate::parser::{BodyLimits, Frame, RequestReader};
+use crate::predicate::Predicate;
+use crate::route::{RouteAction, Router, TrailingSlash};
+
+const STREAM_CHANNEL_SIZE: usize = 8;
+
+pub trait AsyncRouteAction<'a, E: ServerError> : Sync + Send + 'static {
+    type Future: Future<Output = Result<Response, E>> + Send + 'a;
+
+    fn call(&self, request: &'a Request) -> Self::Future;
+}
+
+impl <'a, E: ServerError, F, R> AsyncRouteAction<'a, E> for F
+where
+    F: Fn(&'a Request) -> R + Sync + Send + 'static,
+    R: Future<Output = Result<Response, E>> + Send + 'a
+{
+    type Future = R;
+
+    fn call(&self, request: &'a Request) -> R {
+        self(request)
+    }
+}
+
+fn blocking<E: ServerError, A: for<'a> AsyncRouteAction<'a, E>>(action: A) -> impl RouteAction<E> {
+    move |request: &Request| Handle::current().block_on(action.call(request))
+}
+
+async fn not_found(_: &Request) -> Result<Response, DefaultError> {
+    Err(DefaultError::NotFound)
+}
+
+#[derive(Default)]
+struct Feed {
+    data: Vec<u8>,
+    closed: bool
+}
+
+impl Read for Feed {
+    fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+        if self.data.is_empty() {
+            return match self.closed {
+                true => Ok(0),
+                false => Err(ErrorKind::WouldBlock.into())
+            };
+        }
+
+        let size = buffer.len().min(self.data.len());
+        buffer[..size].copy_from_slice(&self.data[..size]);
+        self.data.drain(..size);
+        Ok(size)
+    }
+}
+
+struct ChannelWriter(Sender<Vec<u8>>);
+
+impl Write for ChannelWriter {
+    fn write(&mut self, buffer: &[u8]) -> io::Result<usize> {
+        self.0.blocking_send(buffer.to_vec()).map_err(|_| io::Error::from(ErrorKind::BrokenPipe))?;
+        Ok(buffer.len())
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+pub struct AsyncHttpServer<E: ServerError, F: ErrorAction<E>> {
+    router: Router<E>,
+    middleware: Vec<Middleware<E>>,
+    error_handler: F,
+    config: ServerConfig
+}
+
+impl Default for AsyncHttpServer<DefaultError, fn(&Request, DefaultError) -> Response> {
+    fn default() -> Self {
+        Self::new(not_found, DEFAULT_HANDLER)
+    }
+}
+
+impl <E: ServerError + 'static, F: ErrorAction<E>> AsyncHttpServer<E, F> {
+    pub fn new(not_found_action: impl for<'a> AsyncRouteAction<'a, E>, error_handler: F) -> Self {
+        Self {
+            router: Router::new(blocking(not_found_action)),
+            middleware: Vec::new(),
+            error_handler,
+            config: ServerConfig::default()
+        }
+    }
+
+    pub fn route(&mut self, method: HttpMethod, route: &str, action: impl for<'a> AsyncRouteAction<'a, E>) {
+        self.router.add(method, route, blocking(action));
+    }
+
+    pub fn route_when(&mut self, method: HttpMethod, route: &str, predicate: Predicate, action: impl for<'a> AsyncRouteAction<'a, E>) {
+        self.router.add_when(method, route, predicate, blocking(action));
+    }
+
+    pub fn get(&mut self, route: &str, action: impl for<'a> AsyncRouteAction<'a, E>) {
+        self.route(HttpMethod::Get, route, action);
+    }
+
+    pub fn post(&mut self, route: &str, action: impl for<'a> AsyncRouteAction<'a, E>) {
+        self.route(HttpMethod::Post, route, action);
+    }
+
+    pub fn put(&mut self, route: &str, action: impl for<'a> AsyncRouteAction<'a, E>) {
+        self.route(HttpMethod::Put, route, action);
+    }
+
+    pub fn patch(&mut self, route: &str, action: impl for<'a> AsyncRouteAction<'a, E>) {
+        self.route(HttpMethod::Patch, route, action);
+    }
+
+    pub fn delete(&mut self, route: &str, action: impl for<'a> AsyncRouteAction<'a, E>) {
+        self.route(HttpMethod::Delete, route, action);
+    }
+
+    pub fn head(&mut self, route: &str, action: impl for<'a> AsyncRouteAction<'a, E>) {
+        self.route(HttpMethod::Head, route, action);
+    }
+
+    pub fn options(&mut self, route: &str, action: impl for<'a> AsyncRouteAction<'a, E>) {
+        self.route(HttpMethod::Options, route, action);
+    }
+
+    pub fn trailing_slash(&mut self, policy: TrailingSlash) {
+        self.router.trailing_slash(policy);
+    }
+
+    pub fn middleware(&mut self, action: impl MiddlewareAction<E>) {
+        self.middleware_at("/", action);
+    }
+
+    pub fn middleware_at(&mut self, prefix: &str, action: impl MiddlewareAction<E>) {
+        self.add_middleware(Middleware::new(prefix, action));
+    }
+
+    pub fn add_middleware(&mut self, middleware: Middleware<E>) {
+        middleware::insert_ordered(&mut self.middleware, middleware);
+    }
+
+    pub fn middleware_chain(&self) -> Vec<MiddlewareInfo> {
+        self.middleware.iter().map(Middleware::info).collect()
+    }
+
+    pub fn edit_config(&mut self) -> &mut ServerConfig {
+        &mut self.config
+    }
+
+    pub fn state<T: Send + Sync + 'static>(&mut self, value: T) {
+        Arc::make_mut(&mut self.config.state).insert(value);
+    }
+
+    pub fn body_limits(&mut self, limits: BodyLimits) {
+        self.config.body_limits = limits;
+    }
+
+    pub fn keep_alive_timeout(&mut self, timeout: Duration) {
+        self.config.keep_alive_timeout = Some(timeout);
+    }
+
+    pub fn server_name(&mut self, name: &str) {
+        self.config.server_name = Some(name.to_string());
+    }
+
+    pub fn workers(&mut self, workers: usize) {
+        self.config.workers = Some(workers.max(1));
+    }
+
+    pub fn listen(self, port: u16) -> io::Result<()> {
+        self.listen_on(SocketAddr::from(([127, 0, 0, 1], port)))
+    }
+
+    pub fn listen_on(self, addresses: impl ToSocketAddrs) -> io::Result<()> {
+        let listener = std::net::TcpListener::bind(addresses)?;
+        listener.set_nonblocking(true)?;
+
+        let mut builder = runtime::Builder::new_multi_thread();
+
+        if let Some(workers) = self.config.workers {
+            builder.worker_threads(workers);
+        }
+
+        builder.enable_all().build()?.block_on(async move {
+            self.serve(TcpListener::from_std(listener)?).await
+        })
+    }
+
+    pub async fn serve(self, listener: TcpListener) -> io::Result<()> {
+        if let Ok(address) = listener.local_addr() {
+            info!("Listening on {}", address);
+        }
+
+        let server = Arc::new(Shared {
+            keep_alive_timeout: self.config.keep_alive_timeout.unwrap_or(KEEP_ALIVE_TIMEOUT),
+            server_name: self.config.server_name.clone().unwrap_or_else(|| SERVER_NAME.to_string()),
+            router: self.router,
+            middleware: self.middleware,
+            error_handler: self.error_handler,
+            config: self.config
+        });
+
+        loop {
+            let (stream, addr) = listener.accept().await?;
+            let server = server.clone();
+
+            tokio::spawn(async move {
+                debug!("Accepted client: {}:{}", addr.ip(), addr.port());
+
+                if let Err(err) = server.handle_client(stream, addr).await {
+                    warn!("Connection with {} failed: {}", addr, err);
+                }
+
+                debug!("Closing connection with: {}:{}", addr.ip(), addr.port());
+            });
+        }
+    }
+}
+
+struct Shared<E: ServerError, F: ErrorAction<E>> {
+    router: Router<E>,
+    middleware: Vec<Middleware<E>>,
+    error_handler: F,
+    config: ServerConfig,
+    keep_alive_timeout: Duration,
+    server_name: String
+}
+
+impl <E: ServerError + 'static, F: ErrorAction<E>> Shared<E, F> {
+    async fn handle_client(self: &Arc<Self>, mut stream: TcpStream, addr: SocketAddr) -> io::Result<()> {
+        let mut reader = RequestReader::new(Feed::default());
+        let mut buffer = vec![0_u8; BUFFER_SIZE];
+
+        loop {
+            let frame = match reader.next_request(&self.config.body_limits, &mut ()) {
+                Ok(Some(frame)) => frame,
+                Ok(None) => return Ok(()),
+                Err(err) if err.kind() == ErrorKind::WouldBlock => {
+                    match time::timeout(self.keep_alive_timeout, stream.read(&mut buffer)).await {
+                        Ok(Ok(0)) => reader.get_mut().closed = true,
+                        Ok(Ok(size)) => reader.get_mut().data.extend_from_slice(&buffer[..size]),
+                        Ok(Err(err)) => return Err(err),
+                        Err(_) => return Ok(())
+                    }
+
+                    continue;
+                },
+                Err(err) if err.kind() == ErrorKind::UnexpectedEof => return Ok(()),
+                Err(err) => {
+                    warn!("{}", err);
+                    return self.reject(&mut stream, Response::text("Malformed request", 400)).await;
+                }
+            };
+
+            let data = match frame {
+                Frame::Request(data) => data,
+                Frame::TooLarge(_) => return self.reject(&mut stream, Response::text("Payload too large", 413)).await
+            };
+
+            let request = match Request::from_frame(addr, &data, true, None) {
+                Ok(request) => request,
+                Err(err) => {
+                    warn!("{}", err);
+                    return self.reject(&mut stream, Response::text("Malformed request", 400)).await;
+                }
+            };
+
+            let server = self.clone();
+
+            let (request, mut response) = task::spawn_blocking(move || {
+                let mut request = request;
+                let _active = panic_hook::track(&request);
+                let response = http_server::dispatch(&server.router, &server.middleware, &server.error_handler, &server.config, &mut request);
+                (request, response)
+            }).await.map_err(io::Error::other)?;
+
+            let keep_alive = http_server::keeps_alive(&request, &response);
+            http_server::set_connection(&mut response, keep_alive, self.keep_alive_timeout);
+            http_server::stamp(&mut response, &self.server_name);
+
+            match response.is_stream() {
+                true => Self::write_stream(&mut stream, response).await?,
+                false => stream.write_all(&response.to_bytes()).await?
+            }
+
+            if !keep_alive {
+                return Ok(());
+            }
+        }
+    }
+
+    async fn write_stream(stream: &mut TcpStream, mut response: Response) -> io::Result<()> {
+        let (sender, mut receiver) = mpsc::channel(STREAM_CHANNEL_SIZE);
+        let producer = task::spawn_blocking(move || response.write_to(&mut ChannelWriter(sender)));
+
+        while let Some(chunk) = receiver.recv().await {
+            if let Err(err) = stream.write_all(&chunk).await {
+                receiver.close();
+                producer.await.ok();
+                return Err(err);
+            }
+        }
+
+        match producer.await.map_err(io::Error::other)? {
+            Ok(()) => Ok(()),
+            Err(err) => {
+                error!("Failed to stream response body: {}", err);
+                Err(err)
+            }
+        }
+    }
+
+    async fn reject(&self, stream: &mut TcpStream, mut response: Response) -> io::Result<()> {
+        response.header("Connection", "close");
+        http_server::stamp(&mut response, &self.server_name);
+        stream.write_all(&response.to_bytes()).await
+    }
+}
@@ -0,0 +1,163 @@
+use std::error::Error;
+use std::fmt::{self, Debug, Display, Formatter};
+use std::sync::Arc;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use crate::error::ServerError;
+use crate::message::{Request, Response};
+use crate::middleware::Next;
+
+type BasicValidator = Arc<dyn Fn(&str, &str) -> bool + Sync + Send>;
+type BearerValidator = Arc<dyn Fn(&str) -> Option<String> + Sync + Send>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthScheme {
+    Basic,
+    Bearer
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Identity {
+    pub scheme: AuthScheme,
+    pub subject: String
+}
+
+#[derive(Debug, Copy, Clone, Eq, PartialEq)]
+pub enum AuthError {
+    Missing,
+    Malformed,
+    Invalid
+}
+
+impl Display for AuthError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Missing => write!(f, "No credentials were provided"),
+            Self::Malformed => write!(f, "The Authorization header is malformed"),
+            Self::Invalid => write!(f, "The given credentials are invalid")
+        }
+    }
+}
+
+impl Error for AuthError {}
+
+#[derive(Clone)]
+pub struct BasicAuth {
+    realm: String,
+    validator: BasicValidator
+}
+
+impl BasicAuth {
+    pub fn new(realm: &str, validator: impl Fn(&str, &str) -> bool + Sync + Send + 'static) -> Self {
+        Self {
+            realm: realm.to_string(),
+            validator: Arc::new(validator)
+        }
+    }
+
+    pub fn authenticate(&self, request: &Request) -> Result<Identity, AuthError> {
+        let encoded = credentials(request, "Basic")?;
+        let decoded = STANDARD.decode(encoded).map_err(|_| AuthError::Malformed)?;
+        let decoded = String::from_utf8(decoded).map_err(|_| AuthError::Malformed)?;
+        let (username, password) = decoded.split_once(':').ok_or(AuthError::Malformed)?;
+
+        match (self.validator)(username, password) {
+            true => Ok(Identity { scheme: AuthScheme::Basic, subject: username.to_string() }),
+            false => Err(AuthError::Invalid)
+        }
+    }
+
+    pub fn challenge(&self) -> Response {
+        let mut response = Response::text("Unauthorized", 401);
+        response.header("WWW-Authenticate", &format!("Basic realm=\"{}\", charset=\"UTF-8\"", quote(&self.realm)));
+        response
+    }
+
+    pub fn middleware<E: ServerError>(self) -> impl Fn(&mut Request, Next<'_, E>) -> Result<Response, E> + Sync + Send + 'static {
+        move |request, next| match self.authenticate(request) {
+            Ok(identity) => {
+                request.insert_ext(identity);
+                next.run(request)
+            },
+            Err(_) => Ok(self.challenge())
+        }
+    }
+}
+
+impl Debug for BasicAuth {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BasicAuth").field("realm", &self.realm).finish()
+    }
+}
+
+#[derive(Clone)]
+pub struct BearerAuth {
+    realm: String,
+    validator: BearerValidator
+}
+
+impl BearerAuth {
+    pub fn new(realm: &str, validator: impl Fn(&str) -> Option<String> + Sync + Send + 'static) -> Self {
+        Self {
+            realm: realm.to_string(),
+            validator: Arc::new(validator)
+        }
+    }
+
+    pub fn authenticate(&self, request: &Request) -> Result<Identity, AuthError> {
+        let token = credentials(request, "Bearer")?;
+
+        match (self.validator)(token) {
+            Some(subject) => Ok(Identity { scheme: AuthScheme::Bearer, subject }),
+            None => Err(AuthError::Invalid)
+        }
+    }
+
+    pub fn challenge(&self, err: AuthError) -> Response {
+        bearer_challenge(&self.realm, err)
+    }
+
+    pub fn middleware<E: ServerError>(self) -> impl Fn(&mut Request, Next<'_, E>) -> Result<Response, E> + Sync + Send + 'static {
+        move |request, next| match self.authenticate(request) {
+            Ok(identity) => {
+                request.insert_ext(identity);
+                next.run(request)
+            },
+            Err(err) => Ok(self.challenge(err))
+        }
+    }
+}
+
+impl Debug for BearerAuth {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("BearerAuth").field("realm", &self.realm).finish()
+    }
+}
+
+pub(crate) fn bearer_challenge(realm: &str, err: AuthError) -> Response {
+    let realm = format!("Bearer realm=\"{}\"", quote(realm));
+
+    let (mut response, challenge) = match err {
+        AuthError::Missing => (Response::text("Unauthorized", 401), realm),
+        AuthError::Malformed => (Response::text("Bad request", 400), format!("{realm}, error=\"invalid_request\"")),
+        AuthError::Invalid => (Response::text("Unauthorized", 401), format!("{realm}, error=\"invalid_token\""))
+    };
+
+    response.header("WWW-Authenticate", &challenge);
+    response
+}
+
+pub(crate) fn credentials<'a>(request: &'a Request, scheme: &str) -> Result<&'a str, AuthError> {
+    let header = request.header("authorization").ok_or(AuthError::Missing)?;
+    let (given, credentials) = header.trim().split_once(' ').ok_or(AuthError::Malformed)?;
+
+    match given.eq_ignore_ascii_case(scheme) {
+        true if !credentials.trim().is_empty() => Ok(credentials.trim()),
+        true => Err(AuthError::Malformed),
+        false => Err(AuthError::Missing)
+    }
+}
+
+fn quote(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
@@ -0,0 +1,176 @@
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use crate::message::{Request, Response};
+use crate::method::HttpMethod;
+use crate::multipart::{self, MultipartBuilder};
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct BatchRequest {
+    pub method: String,
+    pub path: String,
+    #[serde(default)]
+    pub headers: HashMap<String, String>,
+    #[serde(default)]
+    pub body: String
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct BatchResponse {
+    pub status: u16,
+    pub headers: HashMap<String, String>,
+    pub body: String
+}
+
+#[derive(Debug, Clone)]
+pub struct Batch {
+    path: String,
+    max_requests: usize
+}
+
+impl Batch {
+    pub fn new(path: &str) -> Self {
+        Self {
+            path: format!("/{}", path.trim_matches('/')),
+            max_requests: 50
+        }
+    }
+
+    pub fn max_requests(mut self, max_requests: usize) -> Self {
+        self.max_requests = max_requests;
+        self
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    pub fn matches(&self, request: &Request) -> bool {
+        request.method() == HttpMethod::Post && request.route() == self.path
+    }
+
+    pub fn handle(&self, request: &Request, dispatch: impl Fn(&mut Request) -> Response) -> Response {
+        let content_type = request.header("content-type").unwrap_or("");
+
+        let requests = match multipart::boundary(content_type) {
+            Some(_) => self.multipart_requests(request),
+            None => self.json_requests(request)
+        };
+
+        let Some(requests) = requests.filter(|requests| requests.len() <= self.max_requests) else {
+            return Response::text("Malformed batch request", 400);
+        };
+
+        let responses = requests.into_iter().map(|sub_request| match sub_request {
+            Some(mut sub_request) if sub_request.route() != self.path && same_origin(request, &sub_request) => {
+                let mut response = dispatch(&mut sub_request);
+
+                match response.buffer() {
+                    Ok(()) => response,
+                    Err(_) => Response::text("Internal server error", 500)
+                }
+            },
+            _ => Response::text("Malformed request", 400)
+        });
+
+        match multipart::boundary(content_type) {
+            Some(_) => responses.fold(MultipartBuilder::mixed(), |builder, mut response| {
+                response.fill_from(request);
+                builder.bytes("application/http", response.to_bytes())
+            }).into_response(200),
+            None => {
+                let responses: Vec<BatchResponse> = responses.map(|response| BatchResponse {
+                    status: response.status().as_u16(),
+                    headers: response.headers().iter().fold(HashMap::new(), |mut headers, (header, value)| {
+                        headers.entry(header.to_string())
+                            .and_modify(|joined: &mut String| *joined = format!("{joined}, {value}"))
+                            .or_insert_with(|| value.to_string());
+
+                        headers
+                    }),
+                    body: String::from_utf8_lossy(response.body()).into_owned()
+                }).collect();
+
+                Response::json(responses, 200).unwrap_or_else(|_| Response::text("Internal server error", 500))
+            }
+        }
+    }
+
+    fn json_requests(&self, request: &Request) -> Option<Vec<Option<Request>>> {
+        let requests: Vec<BatchRequest> = request.json().ok()?;
+
+        Some(requests.into_iter().map(|sub_request| {
+            let method = HttpMethod::try_from(sub_request.method.to_ascii_uppercase().as_str()).ok()?;
+            let url = Some(&sub_request.path)
+                .filter(|path| is_origin_path(path))
+                .and_then(|path| request.url().join(path).ok())?;
+
+            let mut headers: HashMap<String, String> = sub_request.headers.into_iter()
+                .map(|(header, value)| (header.to_ascii_lowercase(), value))
+                .collect();
+
+            headers.insert("host".to_string(), request.host().to_string());
+            headers.insert("content-length".to_string(), sub_request.body.len().to_string());
+            Some(Request::new(request.socket_addr(), method, url, request.version(), headers, sub_request.body.into_bytes()))
+        }).collect())
+    }
+
+    fn multipart_requests(&self, request: &Request) -> Option<Vec<Option<Request>>> {
+        let parts = request.multipart().ok()?;
+
+        Some(parts.iter().map(|part| {
+            let bytes = part.bytes().ok()?;
+            Request::from_bytes(request.socket_addr(), &bytes).ok()
+        }).collect())
+    }
+}
+
+fn is_origin_path(path: &str) -> bool {
+    path.starts_with('/') && !path.starts_with("//") && !path.contains('\\')
+}
+
+fn same_origin(request: &Request, sub_request: &Request) -> bool {
+    is_origin_path(sub_request.target())
+        && sub_request.url().origin() == request.url().origin()
+        && sub_request.host().eq_ignore_ascii_case(request.host())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+    use serde_json::{json, Value};
+    use super::*;
+
+    fn batch_request(body: &str) -> Request {
+        let bytes = format!("POST /batch HTTP/1.1\r\nHost: localhost\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{body}", body.len());
+        Request::from_bytes(SocketAddr::from(([127, 0, 0, 1], 1234)), bytes.as_bytes()).unwrap()
+    }
+
+    fn statuses(paths: &[&str]) -> Vec<(u64, String)> {
+        let body: Vec<Value> = paths.iter().map(|path| json!({ "method": "GET", "path": path })).collect();
+        let request = batch_request(&Value::Array(body).to_string());
+        let mut response = Batch::new("/batch").handle(&request, |sub_request| Response::text(format!("{}{}", sub_request.host(), sub_request.route()), 200));
+        response.buffer().unwrap();
+
+        let responses: Vec<Value> = serde_json::from_slice(response.body()).unwrap();
+        responses.iter().map(|response| (response["status"].as_u64().unwrap(), response["body"].as_str().unwrap().to_string())).collect()
+    }
+
+    #[test]
+    fn dispatches_same_origin_paths() {
+        assert_eq!(statuses(&["/users/1?full=true"]), vec![(200, "localhost/users/1".to_string())]);
+    }
+
+    #[test]
+    fn rejects_paths_that_leave_the_origin() {
+        let results = statuses(&["mailto:a@b", "//evil.com/x", "http://evil.com/x", "/\\evil.com/x", "users", "/ok"]);
+        let codes: Vec<u64> = results.iter().map(|(status, _)| *status).collect();
+        assert_eq!(codes, vec![400, 400, 400, 400, 400, 200]);
+    }
+
+    #[test]
+    fn request_without_host_does_not_panic() {
+        let url = url::Url::parse("mailto:a@b").unwrap();
+        let request = Request::new(SocketAddr::from(([127, 0, 0, 1], 1234)), HttpMethod::Get, url, 1.1, HashMap::new(), Vec::new());
+        assert_eq!(request.host(), "");
+    }
+}
@@ -0,0 +1,166 @@
+use std::collections::HashMap;
+use std::fmt::{self, Debug, Formatter};
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+use crate::error::ServerError;
+use crate::message::{Request, Response};
+use crate::middleware::Next;
+use crate::route::matches_prefix;
+use crate::throttle::TokenBucket;
+
+const KNOWN_AGENTS: [&str; 14] = [
+    "bot", "crawler", "spider", "slurp", "scrapy", "python-requests", "python-urllib", "go-http-client",
+    "java/", "libwww-perl", "httpclient", "headlesschrome", "phantomjs", "facebookexternalhit"
+];
+
+type Rule = Arc<dyn Fn(&Request) -> bool + Sync + Send>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum BotReason {
+    UserAgent(String),
+    Honeypot(String),
+    Trapped,
+    Rule(String)
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BotMatch {
+    pub reason: BotReason
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BotAction {
+    #[default]
+    Tag,
+    Throttle(u64),
+    Reject
+}
+
+#[derive(Clone)]
+pub struct BotDetector {
+    agents: Vec<String>,
+    honeypots: Vec<String>,
+    rules: Vec<(String, Rule)>,
+    action: BotAction,
+    trap: Option<Duration>,
+    trapped: Arc<Mutex<HashMap<IpAddr, Instant>>>,
+    bucket: Arc<Mutex<TokenBucket>>
+}
+
+impl Default for BotDetector {
+    fn default() -> Self {
+        Self {
+            agents: KNOWN_AGENTS.iter().map(|agent| agent.to_string()).collect(),
+            honeypots: Vec::new(),
+            rules: Vec::new(),
+            action: BotAction::Tag,
+            trap: None,
+            trapped: Arc::new(Mutex::new(HashMap::new())),
+            bucket: Arc::new(Mutex::new(TokenBucket::new(0)))
+        }
+    }
+}
+
+impl BotDetector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn without_known_agents(mut self) -> Self {
+        self.agents.clear();
+        self
+    }
+
+    pub fn agent(mut self, pattern: &str) -> Self {
+        self.agents.push(pattern.to_ascii_lowercase());
+        self
+    }
+
+    pub fn honeypot(mut self, prefix: &str) -> Self {
+        self.honeypots.push(format!("/{}", prefix.trim_matches('/')));
+        self
+    }
+
+    pub fn rule(mut self, name: &str, rule: impl Fn(&Request) -> bool + Sync + Send + 'static) -> Self {
+        self.rules.push((name.to_string(), Arc::new(rule)));
+        self
+    }
+
+    pub fn action(mut self, action: BotAction) -> Self {
+        if let BotAction::Throttle(rate) = action {
+            self.bucket = Arc::new(Mutex::new(TokenBucket::new(rate)));
+        }
+
+        self.action = action;
+        self
+    }
+
+    pub fn trap(mut self, duration: Duration) -> Self {
+        self.trap = Some(duration);
+        self
+    }
+
+    pub fn detect(&self, request: &Request) -> Option<BotReason> {
+        let ip = request.socket_addr().ip();
+
+        if let Some(prefix) = self.honeypots.iter().find(|prefix| matches_prefix(request.route(), prefix)) {
+            if let Some(duration) = self.trap {
+                let mut trapped = self.trapped.lock().unwrap();
+                trapped.retain(|_, until| *until > Instant::now());
+                trapped.insert(ip, Instant::now() + duration);
+            }
+
+            return Some(BotReason::Honeypot(prefix.clone()));
+        }
+
+        if self.trap.is_some() && self.trapped.lock().unwrap().get(&ip).is_some_and(|until| *until > Instant::now()) {
+            return Some(BotReason::Trapped);
+        }
+
+        let agent = request.header("user-agent").unwrap_or_default().to_ascii_lowercase();
+
+        if let Some(pattern) = self.agents.iter().find(|pattern| agent.contains(pattern.as_str())) {
+            return Some(BotReason::UserAgent(pattern.clone()));
+        }
+
+        self.rules.iter()
+            .find(|(_, rule)| rule(request))
+            .map(|(name, _)| BotReason::Rule(name.clone()))
+    }
+
+    pub fn middleware<E: ServerError>(self) -> impl Fn(&mut Request, Next<'_, E>) -> Result<Response, E> + Sync + Send + 'static {
+        move |request, next| {
+            let Some(reason) = self.detect(request) else {
+                return next.run(request);
+            };
+
+            request.insert_ext(BotMatch { reason });
+
+            match self.action {
+                BotAction::Tag => next.run(request),
+                BotAction::Reject => Ok(Response::text("Forbidden", 403)),
+                BotAction::Throttle(_) => {
+                    let wait = self.bucket.lock().unwrap().take(1);
+                    thread::sleep(wait);
+                    next.run(request)
+                }
+            }
+        }
+    }
+}
+
+impl Debug for BotDetector {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let rules: Vec<&str> = self.rules.iter().map(|(name, _)| name.as_str()).collect();
+
+        f.debug_struct("BotDetector")
+            .field("agents", &self.agents)
+            .field("honeypots", &self.honeypots)
+            .field("rules", &rules)
+            .field("action", &self.action)
+            .field("trap", &self.trap)
+            .finish()
+    }
+}
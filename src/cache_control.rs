@@ -0,0 +1,138 @@
+use std::fmt::{self, Display, Formatter};
+use std::time::Duration;
+use crate::message::Response;
+use crate::route::matches_prefix;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Visibility {
+    Public,
+    Private
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CachePolicy {
+    visibility: Option<Visibility>,
+    max_age: Option<Duration>,
+    shared_max_age: Option<Duration>,
+    immutable: bool,
+    no_store: bool,
+    no_cache: bool,
+    must_revalidate: bool
+}
+
+impl CachePolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn public(mut self) -> Self {
+        self.visibility = Some(Visibility::Public);
+        self
+    }
+
+    pub fn private(mut self) -> Self {
+        self.visibility = Some(Visibility::Private);
+        self
+    }
+
+    pub fn max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    pub fn shared_max_age(mut self, max_age: Duration) -> Self {
+        self.shared_max_age = Some(max_age);
+        self
+    }
+
+    pub fn immutable(mut self) -> Self {
+        self.immutable = true;
+        self
+    }
+
+    pub fn no_store(mut self) -> Self {
+        self.no_store = true;
+        self
+    }
+
+    pub fn no_cache(mut self) -> Self {
+        self.no_cache = true;
+        self
+    }
+
+    pub fn must_revalidate(mut self) -> Self {
+        self.must_revalidate = true;
+        self
+    }
+
+    pub fn apply(&self, response: &mut Response) {
+        let cacheable = (200..300).contains(&response.status().as_u16()) || response.status() == 304;
+
+        if cacheable && response.get_header("Cache-Control").is_none() {
+            response.header("Cache-Control", &self.to_string());
+        }
+    }
+}
+
+impl Display for CachePolicy {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if self.no_store {
+            return write!(f, "no-store");
+        }
+
+        let mut directives = Vec::new();
+
+        match self.visibility {
+            Some(Visibility::Public) => directives.push("public".to_string()),
+            Some(Visibility::Private) => directives.push("private".to_string()),
+            None => ()
+        }
+
+        if self.no_cache {
+            directives.push("no-cache".to_string());
+        }
+
+        if let Some(max_age) = self.max_age {
+            directives.push(format!("max-age={}", max_age.as_secs()));
+        }
+
+        if let Some(max_age) = self.shared_max_age {
+            directives.push(format!("s-maxage={}", max_age.as_secs()));
+        }
+
+        if self.must_revalidate {
+            directives.push("must-revalidate".to_string());
+        }
+
+        if self.immutable {
+            directives.push("immutable".to_string());
+        }
+
+        write!(f, "{}", directives.join(", "))
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct CachePolicies {
+    scopes: Vec<(String, CachePolicy)>
+}
+
+impl CachePolicies {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn scope(mut self, prefix: &str, policy: CachePolicy) -> Self {
+        let prefix = format!("/{}", prefix.trim_matches('/'));
+        self.scopes.retain(|(scope, _)| *scope != prefix);
+        self.scopes.push((prefix, policy));
+        self
+    }
+
+    pub fn for_route(&self, route: &str) -> Option<&CachePolicy> {
+        self.scopes.iter()
+            .filter(|(prefix, _)| matches_prefix(route, prefix))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, policy)| policy)
+    }
+}
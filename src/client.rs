@@ -0,0 +1,344 @@
+use std::collections::HashMap;
+use std::io::{self, BufReader, ErrorKind, Write};
+use std::net::{SocketAddr, TcpStream};
+use std::sync::{mpsc, Arc, Mutex};
+use std::thread;
+use std::time::Duration;
+use url::Url;
+use crate::headers::HeaderMap;
+use crate::message::{Request, Response};
+use crate::method::HttpMethod;
+use crate::resolver::Resolver;
+
+pub const MAX_RESPONSE_SIZE: usize = 16 * 1024 * 1024;
+
+pub struct Client {
+    timeout: Duration,
+    max_response_size: usize,
+    attempt_delay: Duration,
+    max_idle_per_host: usize,
+    resolver: Arc<Resolver>,
+    idle: Mutex<HashMap<String, Vec<TcpStream>>>
+}
+
+impl Client {
+    pub fn new() -> Self {
+        Self {
+            timeout: Duration::from_secs(30),
+            max_response_size: MAX_RESPONSE_SIZE,
+            attempt_delay: Duration::from_millis(250),
+            max_idle_per_host: 8,
+            resolver: Arc::new(Resolver::new()),
+            idle: Mutex::new(HashMap::new())
+        }
+    }
+
+    pub fn timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    pub fn max_response_size(mut self, bytes: usize) -> Self {
+        self.max_response_size = bytes;
+        self
+    }
+
+    pub fn attempt_delay(mut self, delay: Duration) -> Self {
+        self.attempt_delay = delay;
+        self
+    }
+
+    pub fn max_idle_per_host(mut self, connections: usize) -> Self {
+        self.max_idle_per_host = connections;
+        self
+    }
+
+    pub fn resolver(mut self, resolver: Resolver) -> Self {
+        self.resolver = Arc::new(resolver);
+        self
+    }
+
+    pub fn get(&self, url: &str) -> io::Result<Response> {
+        self.request(HttpMethod::Get, url, &[], Vec::new())
+    }
+
+    pub fn post(&self, url: &str, body: impl Into<Vec<u8>>, content_type: &str) -> io::Result<Response> {
+        self.request(HttpMethod::Post, url, &[("content-type", content_type)], body.into())
+    }
+
+    pub fn request(&self, method: HttpMethod, url: &str, headers: &[(&str, &str)], body: Vec<u8>) -> io::Result<Response> {
+        self.send(&outgoing(method, url, headers, body)?)
+    }
+
+    pub fn send(&self, request: &Request) -> io::Result<Response> {
+        let url = request.url();
+
+        if url.scheme() != "http" {
+            return Err(io::Error::new(ErrorKind::Unsupported, "Only http urls are supported, the client has no TLS connector"));
+        }
+
+        let host = url.host_str().ok_or(io::Error::new(ErrorKind::InvalidInput, "Url has no host"))?;
+        let port = url.port_or_known_default().unwrap_or(80);
+        let key = format!("{host}:{port}");
+        let bytes = request.to_bytes();
+        let method = request.method();
+
+        if let Some(stream) = method.is_idempotent().then(|| self.checkout(&key)).flatten() {
+            if let Ok(response) = self.exchange(&key, stream, &bytes, &method) {
+                return Ok(response);
+            }
+        }
+
+        let stream = self.connect(host, port)?;
+        self.exchange(&key, stream, &bytes, &method)
+    }
+
+    fn connect(&self, host: &str, port: u16) -> io::Result<TcpStream> {
+        let (sender, receiver) = mpsc::channel();
+        let mut pending = 0;
+        let mut last_error = io::Error::new(ErrorKind::NotFound, format!("Could not resolve {host}"));
+
+        for address in Self::interleave(self.resolver.resolve(host, port)?) {
+            let sender = sender.clone();
+            let timeout = self.timeout;
+            thread::spawn(move || sender.send(TcpStream::connect_timeout(&address, timeout)));
+            pending += 1;
+
+            match receiver.recv_timeout(self.attempt_delay) {
+                Ok(Ok(stream)) => return self.configure(stream),
+                Ok(Err(err)) => {
+                    pending -= 1;
+                    last_error = err;
+                },
+                Err(_) => ()
+            }
+        }
+
+        while pending > 0 {
+            match receiver.recv() {
+                Ok(Ok(stream)) => return self.configure(stream),
+                Ok(Err(err)) => {
+                    pending -= 1;
+                    last_error = err;
+                },
+                Err(_) => break
+            }
+        }
+
+        Err(last_error)
+    }
+
+    fn configure(&self, stream: TcpStream) -> io::Result<TcpStream> {
+        stream.set_read_timeout(Some(self.timeout))?;
+        stream.set_write_timeout(Some(self.timeout))?;
+        stream.set_nodelay(true)?;
+        Ok(stream)
+    }
+
+    fn interleave(addresses: Vec<SocketAddr>) -> Vec<SocketAddr> {
+        let first_is_ipv6 = addresses.first().is_some_and(SocketAddr::is_ipv6);
+        let (mut first, mut second): (Vec<SocketAddr>, Vec<SocketAddr>) = addresses.into_iter()
+            .partition(|address| address.is_ipv6() == first_is_ipv6);
+
+        let mut interleaved = Vec::with_capacity(first.len() + second.len());
+        first.reverse();
+        second.reverse();
+
+        while !first.is_empty() || !second.is_empty() {
+            interleaved.extend(first.pop());
+            interleaved.extend(second.pop());
+        }
+
+        interleaved
+    }
+
+    fn exchange(&self, key: &str, mut stream: TcpStream, bytes: &[u8], method: &HttpMethod) -> io::Result<Response> {
+        stream.write_all(bytes)?;
+        let mut reader = BufReader::new(stream);
+        let has_body = *method != HttpMethod::Head;
+        let mut response = Response::read_from(&mut reader, has_body, self.max_response_size)?;
+
+        while matches!(response.status().as_u16(), 100 | 102 | 103) {
+            response = Response::read_from(&mut reader, has_body, self.max_response_size)?;
+        }
+
+        let framed = !has_body
+            || response.get_header("Content-Length").is_some()
+            || response.get_header("Transfer-Encoding").is_some_and(|encoding| encoding.eq_ignore_ascii_case("chunked"))
+            || matches!(response.status().as_u16(), 100..=199 | 204 | 304);
+
+        let close = response.get_header("Connection").is_some_and(|connection| connection.eq_ignore_ascii_case("close"))
+            || response.version() < 1.1;
+
+        if framed && !close && reader.buffer().is_empty() {
+            self.checkin(key, reader.into_inner());
+        }
+
+        Ok(response)
+    }
+
+    fn checkout(&self, key: &str) -> Option<TcpStream> {
+        self.idle.lock().unwrap().get_mut(key)?.pop()
+    }
+
+    fn checkin(&self, key: &str, stream: TcpStream) {
+        let mut idle = self.idle.lock().unwrap();
+        let connections = idle.entry(key.to_string()).or_default();
+
+        if connections.len() < self.max_idle_per_host {
+            connections.push(stream);
+        }
+    }
+}
+
+impl Default for Client {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "tokio")]
+#[derive(Clone, Default)]
+pub struct AsyncClient(Arc<Client>);
+
+#[cfg(feature = "tokio")]
+impl AsyncClient {
+    pub fn new(client: Client) -> Self {
+        Self(Arc::new(client))
+    }
+
+    pub async fn get(&self, url: &str) -> io::Result<Response> {
+        self.request(HttpMethod::Get, url, &[], Vec::new()).await
+    }
+
+    pub async fn post(&self, url: &str, body: impl Into<Vec<u8>>, content_type: &str) -> io::Result<Response> {
+        self.request(HttpMethod::Post, url, &[("content-type", content_type)], body.into()).await
+    }
+
+    pub async fn request(&self, method: HttpMethod, url: &str, headers: &[(&str, &str)], body: Vec<u8>) -> io::Result<Response> {
+        self.send(outgoing(method, url, headers, body)?).await
+    }
+
+    pub async fn send(&self, request: Request) -> io::Result<Response> {
+        let client = self.0.clone();
+        tokio::task::spawn_blocking(move || client.send(&request)).await.map_err(io::Error::other)?
+    }
+}
+
+fn outgoing(method: HttpMethod, url: &str, headers: &[(&str, &str)], body: Vec<u8>) -> io::Result<Request> {
+    let url = Url::parse(url).map_err(|err| io::Error::new(ErrorKind::InvalidInput, err))?;
+
+    if url.host_str().is_none() {
+        return Err(io::Error::new(ErrorKind::InvalidInput, "Url has no host"));
+    }
+
+    let headers: HeaderMap = headers.iter().copied().collect();
+    Ok(Request::outgoing(method, url, headers, body))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, Read};
+    use std::net::TcpListener;
+    use std::time::Instant;
+    use crate::parser::MAX_HEAD_SIZE;
+    use super::*;
+
+    fn serve(responses: Vec<&'static str>) -> (String, thread::JoinHandle<Vec<String>>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/", listener.local_addr().unwrap());
+
+        let handle = thread::spawn(move || {
+            let mut requests = Vec::new();
+
+            for response in responses {
+                let (stream, _) = listener.accept().unwrap();
+                let mut reader = BufReader::new(stream);
+                let mut head = String::new();
+
+                while reader.read_line(&mut head).unwrap() > 2 && !head.ends_with("\r\n\r\n") {}
+
+                let length = head.lines()
+                    .find_map(|line| line.to_ascii_lowercase().strip_prefix("content-length:").map(|length| length.trim().parse::<usize>().unwrap()))
+                    .unwrap_or(0);
+
+                let mut body = vec![0; length];
+                reader.read_exact(&mut body).unwrap();
+                requests.push(head.lines().next().unwrap_or("").to_string());
+                reader.into_inner().write_all(response.as_bytes()).unwrap();
+            }
+
+            requests
+        });
+
+        (url, handle)
+    }
+
+    #[test]
+    fn head_response_has_no_body() {
+        let (url, server) = serve(vec!["HTTP/1.1 200 OK\r\nContent-Length: 100\r\n\r\n"]);
+        let client = Client::new().timeout(Duration::from_secs(2));
+        let started = Instant::now();
+        let response = client.request(HttpMethod::Head, &url, &[], Vec::new()).unwrap();
+
+        assert_eq!(response.status(), 200);
+        assert!(response.body().is_empty());
+        assert!(started.elapsed() < Duration::from_secs(1));
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn skips_interim_responses() {
+        let (url, server) = serve(vec!["HTTP/1.1 100 Continue\r\n\r\nHTTP/1.1 204 No Content\r\n\r\n"]);
+        let response = Client::new().timeout(Duration::from_secs(2)).get(&url).unwrap();
+        assert_eq!(response.status(), 204);
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn post_does_not_reuse_pooled_connections() {
+        let (url, server) = serve(vec![
+            "HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok",
+            "HTTP/1.1 201 Created\r\nContent-Length: 0\r\n\r\n"
+        ]);
+
+        let client = Client::new().timeout(Duration::from_secs(2));
+        client.get(&url).unwrap();
+        let response = client.post(&url, "data", "text/plain").unwrap();
+
+        assert_eq!(response.status(), 201);
+        assert_eq!(server.join().unwrap(), vec!["GET / HTTP/1.1", "POST / HTTP/1.1"]);
+    }
+
+    #[test]
+    fn rejects_responses_over_the_size_limit() {
+        let (url, server) = serve(vec!["HTTP/1.1 200 OK\r\nContent-Length: 4096\r\n\r\n"]);
+        let err = Client::new().max_response_size(1024).get(&url).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        server.join().unwrap();
+
+        let (url, server) = serve(vec!["HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n800\r\n"]);
+        let err = Client::new().max_response_size(1024).get(&url).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+        server.join().unwrap();
+    }
+
+    #[test]
+    fn caps_the_response_head() {
+        let head = format!("HTTP/1.1 200 OK\r\nX-Filler: {}\r\n\r\n", "a".repeat(MAX_HEAD_SIZE));
+        let mut reader = BufReader::new(head.as_bytes());
+        let err = Response::read_from(&mut reader, true, MAX_RESPONSE_SIZE).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::InvalidData);
+    }
+
+    #[cfg(feature = "tokio")]
+    #[test]
+    fn async_client_sends_requests() {
+        let (url, server) = serve(vec!["HTTP/1.1 200 OK\r\nContent-Length: 2\r\n\r\nok"]);
+        let runtime = tokio::runtime::Builder::new_current_thread().enable_all().build().unwrap();
+        let response = runtime.block_on(AsyncClient::new(Client::new().timeout(Duration::from_secs(2))).get(&url)).unwrap();
+
+        assert_eq!(response.body(), b"ok");
+        server.join().unwrap();
+    }
+}
@@ -0,0 +1,69 @@
+#[derive(Debug, Clone)]
+pub struct Compat {
+    expect_continue: bool,
+    legacy_accept: bool,
+    default_host: Option<String>
+}
+
+impl Default for Compat {
+    fn default() -> Self {
+        Self::modern()
+    }
+}
+
+impl Compat {
+    pub fn strict() -> Self {
+        Self {
+            expect_continue: false,
+            legacy_accept: false,
+            default_host: None
+        }
+    }
+
+    pub fn modern() -> Self {
+        Self::strict().expect_continue(true)
+    }
+
+    pub fn legacy(default_host: &str) -> Self {
+        Self::modern().legacy_accept(true).default_host(default_host)
+    }
+
+    pub fn expect_continue(mut self, enabled: bool) -> Self {
+        self.expect_continue = enabled;
+        self
+    }
+
+    pub fn legacy_accept(mut self, enabled: bool) -> Self {
+        self.legacy_accept = enabled;
+        self
+    }
+
+    pub fn default_host(mut self, host: &str) -> Self {
+        self.default_host = Some(host.to_string());
+        self
+    }
+
+    pub fn sends_continue(&self) -> bool {
+        self.expect_continue
+    }
+
+    pub fn fallback_host(&self) -> Option<&str> {
+        self.default_host.as_deref()
+    }
+
+    pub fn accept(&self, accept: &str) -> Option<String> {
+        if !self.legacy_accept {
+            return None;
+        }
+
+        let types: Vec<&str> = accept.split(',')
+            .map(|entry| entry.split(';').next().unwrap_or("").trim())
+            .collect();
+
+        let legacy = types.contains(&"*/*")
+            && types.iter().any(|media_type| media_type.starts_with("image/"))
+            && !types.iter().any(|media_type| media_type.starts_with("text/") || media_type.ends_with("/json") || media_type.ends_with("+xml"));
+
+        legacy.then(|| "*/*".to_string())
+    }
+}
@@ -0,0 +1,63 @@
+use std::fs::Metadata;
+use std::time::UNIX_EPOCH;
+use crate::message::{Request, Response};
+use crate::method::HttpMethod;
+use crate::route::matches_prefix;
+
+#[derive(Debug, Clone, Default)]
+pub struct Conditional {
+    exempt: Vec<String>
+}
+
+impl Conditional {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn exempt(mut self, prefix: &str) -> Self {
+        self.exempt.push(format!("/{}", prefix.trim_matches('/')));
+        self
+    }
+
+    pub fn applies(&self, route: &str) -> bool {
+        !self.exempt.iter().any(|prefix| matches_prefix(route, prefix))
+    }
+}
+
+pub fn etag(metadata: &Metadata) -> String {
+    let modified = metadata.modified().ok()
+        .and_then(|modified| modified.duration_since(UNIX_EPOCH).ok())
+        .unwrap_or_default();
+
+    format!("\"{:x}-{:x}\"", modified.as_secs(), metadata.len())
+}
+
+pub fn last_modified(metadata: &Metadata) -> Option<String> {
+    metadata.modified().ok().map(httpdate::fmt_http_date)
+}
+
+pub fn is_fresh(request: &Request, response: &Response) -> bool {
+    if !matches!(request.method(), HttpMethod::Get | HttpMethod::Head) || !(response.status() == 200 || response.status() == 206) {
+        return false;
+    }
+
+    if let Some(tags) = request.header("if-none-match") {
+        let Some(etag) = response.get_header("ETag") else {
+            return false;
+        };
+
+        return tags.split(',').map(str::trim).any(|tag| tag == "*" || weak(tag) == weak(etag));
+    }
+
+    let since = request.header("if-modified-since").and_then(|since| httpdate::parse_http_date(since.trim()).ok());
+    let modified = response.get_header("Last-Modified").and_then(|modified| httpdate::parse_http_date(modified).ok());
+
+    match (since, modified) {
+        (Some(since), Some(modified)) => modified <= since,
+        _ => false
+    }
+}
+
+fn weak(tag: &str) -> &str {
+    tag.strip_prefix("W/").unwrap_or(tag)
+}
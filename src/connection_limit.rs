@@ -0,0 +1,62 @@
+use std::sync::{Arc, Condvar, Mutex};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Overflow {
+    #[default]
+    Reject,
+    Delay
+}
+
+#[derive(Debug)]
+pub struct ConnectionLimit {
+    max: usize,
+    overflow: Overflow,
+    active: Mutex<usize>,
+    released: Condvar
+}
+
+impl ConnectionLimit {
+    pub fn new(max: usize) -> Self {
+        Self {
+            max: max.max(1),
+            overflow: Overflow::default(),
+            active: Mutex::new(0),
+            released: Condvar::new()
+        }
+    }
+
+    pub fn overflow(mut self, overflow: Overflow) -> Self {
+        self.overflow = overflow;
+        self
+    }
+
+    pub fn max(&self) -> usize {
+        self.max
+    }
+
+    pub fn active(&self) -> usize {
+        *self.active.lock().unwrap()
+    }
+
+    pub(crate) fn acquire(self: &Arc<Self>) -> Option<ConnectionPermit> {
+        let active = self.active.lock().unwrap();
+
+        let mut active = match self.overflow {
+            Overflow::Reject if *active >= self.max => return None,
+            Overflow::Reject => active,
+            Overflow::Delay => self.released.wait_while(active, |active| *active >= self.max).unwrap()
+        };
+
+        *active += 1;
+        Some(ConnectionPermit(self.clone()))
+    }
+}
+
+pub struct ConnectionPermit(Arc<ConnectionLimit>);
+
+impl Drop for ConnectionPermit {
+    fn drop(&mut self) {
+        *self.0.active.lock().unwrap() -= 1;
+        self.0.released.notify_one();
+    }
+}
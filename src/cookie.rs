@@ -0,0 +1,291 @@
+use std::fmt::{self, Display, Formatter};
+use std::time::{Duration, SystemTime};
+use percent_encoding::{percent_decode_str, utf8_percent_encode, AsciiSet, CONTROLS, NON_ALPHANUMERIC};
+use crate::error::RequestParseError;
+
+const MAX_AGE_LIMIT: Duration = Duration::from_secs(400 * 24 * 60 * 60);
+const COOKIE_VALUE: &AsciiSet = &CONTROLS.add(b' ').add(b'"').add(b',').add(b';').add(b'\\').add(b'%');
+const COOKIE_NAME: &AsciiSet = &NON_ALPHANUMERIC.remove(b'!').remove(b'#').remove(b'$').remove(b'&').remove(b'\'').remove(b'*')
+    .remove(b'+').remove(b'-').remove(b'.').remove(b'^').remove(b'_').remove(b'`').remove(b'|').remove(b'~');
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SameSite {
+    Strict,
+    Lax,
+    None
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cookie {
+    name: String,
+    value: String,
+    max_age: Option<Duration>,
+    expires: Option<SystemTime>,
+    path: Option<String>,
+    domain: Option<String>,
+    secure: bool,
+    http_only: bool,
+    partitioned: bool,
+    same_site: Option<SameSite>
+}
+
+impl Cookie {
+    pub fn new(name: &str, value: &str) -> Self {
+        Self {
+            name: encode_name(name),
+            value: encode_value(value),
+            max_age: None,
+            expires: None,
+            path: None,
+            domain: None,
+            secure: false,
+            http_only: false,
+            partitioned: false,
+            same_site: None
+        }
+    }
+
+    pub fn percent_encoded(name: &str, value: &str) -> Self {
+        Self::new(name, &utf8_percent_encode(value, COOKIE_VALUE).to_string())
+    }
+
+    pub fn removal(name: &str) -> Self {
+        Self::new(name, "").path("/").max_age(Duration::ZERO)
+    }
+
+    pub fn max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    pub fn expires(mut self, expires: SystemTime) -> Self {
+        self.expires = Some(expires);
+        self
+    }
+
+    pub fn path(mut self, path: &str) -> Self {
+        self.path = Some(attribute(path));
+        self
+    }
+
+    pub fn domain(mut self, domain: &str) -> Self {
+        self.domain = Some(attribute(domain));
+        self
+    }
+
+    pub fn secure(mut self, secure: bool) -> Self {
+        self.secure = secure;
+        self
+    }
+
+    pub fn http_only(mut self, http_only: bool) -> Self {
+        self.http_only = http_only;
+        self
+    }
+
+    pub fn partitioned(mut self, partitioned: bool) -> Self {
+        self.partitioned = partitioned;
+        self
+    }
+
+    pub fn with_value(mut self, value: &str) -> Self {
+        self.value = encode_value(value);
+        self
+    }
+
+    pub fn same_site(mut self, same_site: SameSite) -> Self {
+        self.same_site = Some(same_site);
+        self
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+}
+
+impl Display for Cookie {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let host_only = self.name.starts_with("__Host-");
+        let secure = self.secure || self.partitioned || host_only || self.name.starts_with("__Secure-") || self.same_site == Some(SameSite::None);
+        write!(f, "{}={}", self.name, self.value)?;
+
+        if let Some(max_age) = self.max_age {
+            write!(f, "; Max-Age={}", max_age.min(MAX_AGE_LIMIT).as_secs())?;
+        }
+
+        if let Some(expires) = self.expires {
+            write!(f, "; Expires={}", httpdate::fmt_http_date(expires))?;
+        }
+
+        match host_only {
+            true => write!(f, "; Path=/")?,
+            false => {
+                if let Some(path) = &self.path {
+                    write!(f, "; Path={path}")?;
+                }
+
+                if let Some(domain) = &self.domain {
+                    write!(f, "; Domain={domain}")?;
+                }
+            }
+        }
+
+        if secure {
+            write!(f, "; Secure")?;
+        }
+
+        if self.http_only {
+            write!(f, "; HttpOnly")?;
+        }
+
+        if self.partitioned {
+            write!(f, "; Partitioned")?;
+        }
+
+        match self.same_site {
+            Some(SameSite::Strict) => write!(f, "; SameSite=Strict"),
+            Some(SameSite::Lax) => write!(f, "; SameSite=Lax"),
+            Some(SameSite::None) => write!(f, "; SameSite=None"),
+            None => Ok(())
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CookieParser {
+    strict: bool,
+    percent_decode: bool
+}
+
+impl CookieParser {
+    pub fn lenient() -> Self {
+        Self::default()
+    }
+
+    pub fn strict() -> Self {
+        Self {
+            strict: true,
+            percent_decode: false
+        }
+    }
+
+    pub fn percent_decode(mut self, enabled: bool) -> Self {
+        self.percent_decode = enabled;
+        self
+    }
+
+    pub fn parse(&self, header: &str) -> Result<Vec<(String, String)>, RequestParseError> {
+        let mut cookies = Vec::new();
+
+        for pair in header.split(';').map(str::trim).filter(|pair| !pair.is_empty()) {
+            let parsed = pair.split_once('=')
+                .map(|(name, value)| (name.trim(), value.trim()))
+                .filter(|(name, value)| !self.strict || (is_token(name) && is_cookie_value(value)));
+
+            let Some((name, value)) = parsed.filter(|(name, _)| !name.is_empty()) else {
+                match self.strict {
+                    true => return Err(RequestParseError::Cookie),
+                    false => continue
+                }
+            };
+
+            let value = value.strip_prefix('"').and_then(|value| value.strip_suffix('"')).unwrap_or(value);
+
+            let value = match self.percent_decode {
+                true => percent_decode_str(value).decode_utf8().map_err(|_| RequestParseError::Cookie),
+                false => Ok(value.into())
+            };
+
+            match value {
+                Ok(value) => cookies.push((name.to_string(), value.into_owned())),
+                Err(err) if self.strict => return Err(err),
+                Err(_) => ()
+            }
+        }
+
+        Ok(cookies)
+    }
+}
+
+fn encode_name(name: &str) -> String {
+    match is_token(name) {
+        true => name.to_string(),
+        false => utf8_percent_encode(name, COOKIE_NAME).to_string()
+    }
+}
+
+fn encode_value(value: &str) -> String {
+    match is_cookie_value(value) {
+        true => value.to_string(),
+        false => utf8_percent_encode(value, COOKIE_VALUE).to_string()
+    }
+}
+
+fn attribute(value: &str) -> String {
+    value.chars().filter(|char| *char != ';' && !char.is_control()).collect()
+}
+
+fn is_token(name: &str) -> bool {
+    name.bytes().all(|byte| byte.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&byte))
+}
+
+fn is_cookie_value(value: &str) -> bool {
+    let value = value.strip_prefix('"').and_then(|value| value.strip_suffix('"')).unwrap_or(value);
+    value.bytes().all(|byte| matches!(byte, 0x21 | 0x23..=0x2B | 0x2D..=0x3A | 0x3C..=0x5B | 0x5D..=0x7E))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_valid_names_and_values() {
+        assert_eq!(Cookie::new("session", "abc-123_.~").to_string(), "session=abc-123_.~");
+        assert_eq!(Cookie::new("quoted", "\"abc\"").to_string(), "quoted=\"abc\"");
+    }
+
+    #[test]
+    fn encodes_delimiters_and_line_breaks() {
+        let cookie = Cookie::new("name; Path=/", "a; Domain=evil.test,\r\nSet-Cookie: x=y").path("/;\r\nx").domain("a.test; Secure");
+        let header = cookie.to_string();
+
+        assert_eq!(cookie.name(), "name%3B%20Path%3D%2F");
+        assert_eq!(cookie.value(), "a%3B%20Domain=evil.test%2C%0D%0ASet-Cookie:%20x=y");
+        assert_eq!(header, "name%3B%20Path%3D%2F=a%3B%20Domain=evil.test%2C%0D%0ASet-Cookie:%20x=y; Path=/x; Domain=a.test Secure");
+        assert!(!header.contains('\r') && !header.contains('\n'));
+    }
+
+    #[test]
+    fn lenient_parser_skips_malformed_pairs() {
+        let cookies = CookieParser::lenient().parse("a=1; broken; =empty; b=\"two words\"; c=%41").unwrap();
+
+        assert_eq!(cookies, vec![
+            ("a".to_string(), "1".to_string()),
+            ("b".to_string(), "two words".to_string()),
+            ("c".to_string(), "%41".to_string())
+        ]);
+    }
+
+    #[test]
+    fn strict_parser_rejects_invalid_names_and_values() {
+        let parser = CookieParser::strict();
+
+        assert_eq!(parser.parse("a=1; b=\"2\"").unwrap(), vec![("a".to_string(), "1".to_string()), ("b".to_string(), "2".to_string())]);
+
+        for header in ["a=1; broken", "a b=1", "a=1,2", "a=two words", "=1", "a=\\"] {
+            assert!(matches!(parser.parse(header), Err(RequestParseError::Cookie)), "{header} was accepted");
+        }
+    }
+
+    #[test]
+    fn percent_decodes_values_when_enabled() {
+        let cookies = CookieParser::lenient().percent_decode(true).parse("a=%41%20b; bad=%FF; c=3").unwrap();
+        assert_eq!(cookies, vec![("a".to_string(), "A b".to_string()), ("c".to_string(), "3".to_string())]);
+
+        assert!(CookieParser::strict().percent_decode(true).parse("bad=%FF").is_err());
+    }
+}
@@ -0,0 +1,116 @@
+use aes_gcm::{Aes256Gcm, Key, KeyInit, Nonce};
+use aes_gcm::aead::{Aead, Payload};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use sha2::{Digest, Sha256};
+use crate::cookie::Cookie;
+use crate::keyring::{self, Keyring, KeyringError};
+use crate::message::Request;
+
+const NONCE_SIZE: usize = 12;
+
+#[derive(Debug, Clone)]
+pub struct CookieJar {
+    keys: Vec<Vec<u8>>
+}
+
+impl CookieJar {
+    pub fn new(key: impl AsRef<[u8]>) -> Self {
+        Self {
+            keys: vec![key.as_ref().to_vec()]
+        }
+    }
+
+    pub fn from_keyring(keyring: &Keyring) -> Result<Self, KeyringError> {
+        let keys: Vec<Vec<u8>> = keyring.derive(keyring::COOKIES).secrets().map(|secret| secret.to_vec()).collect();
+
+        match keys.is_empty() {
+            true => Err(KeyringError::Empty),
+            false => Ok(Self { keys })
+        }
+    }
+
+    pub fn rotate(mut self, key: impl AsRef<[u8]>) -> Self {
+        self.keys.insert(0, key.as_ref().to_vec());
+        self
+    }
+
+    pub fn fallback(mut self, key: impl AsRef<[u8]>) -> Self {
+        self.keys.push(key.as_ref().to_vec());
+        self
+    }
+
+    pub fn sign(&self, cookie: Cookie) -> Cookie {
+        let signature = URL_SAFE_NO_PAD.encode(Self::mac(&self.keys[0], cookie.name(), cookie.value()).finalize().into_bytes());
+        let value = format!("{}.{signature}", cookie.value());
+        cookie.with_value(&value)
+    }
+
+    pub fn verify(&self, name: &str, value: &str) -> Option<String> {
+        let (value, signature) = value.rsplit_once('.')?;
+        let signature = URL_SAFE_NO_PAD.decode(signature).ok()?;
+
+        self.keys.iter()
+            .any(|key| Self::mac(key, name, value).verify_slice(&signature).is_ok())
+            .then(|| value.to_string())
+    }
+
+    pub fn signed(&self, request: &Request, name: &str) -> Option<String> {
+        self.verify(name, request.cookie(name)?)
+    }
+
+    pub fn encrypt(&self, cookie: Cookie) -> Cookie {
+        let mut nonce = [0_u8; NONCE_SIZE];
+        rand::thread_rng().fill_bytes(&mut nonce);
+
+        let payload = Payload {
+            msg: cookie.value().as_bytes(),
+            aad: cookie.name().as_bytes()
+        };
+
+        let Ok(ciphertext) = Self::cipher(&self.keys[0]).encrypt(Nonce::from_slice(&nonce), payload) else {
+            return cookie.with_value("");
+        };
+
+        let value = URL_SAFE_NO_PAD.encode([nonce.as_slice(), &ciphertext].concat());
+        cookie.with_value(&value)
+    }
+
+    pub fn decrypt(&self, name: &str, value: &str) -> Option<String> {
+        let sealed = URL_SAFE_NO_PAD.decode(value).ok()?;
+
+        if sealed.len() < NONCE_SIZE {
+            return None;
+        }
+
+        let (nonce, ciphertext) = sealed.split_at(NONCE_SIZE);
+
+        let plaintext = self.keys.iter().find_map(|key| {
+            let payload = Payload {
+                msg: ciphertext,
+                aad: name.as_bytes()
+            };
+
+            Self::cipher(key).decrypt(Nonce::from_slice(nonce), payload).ok()
+        })?;
+
+        String::from_utf8(plaintext).ok()
+    }
+
+    pub fn private(&self, request: &Request, name: &str) -> Option<String> {
+        self.decrypt(name, request.cookie(name)?)
+    }
+
+    fn mac(key: &[u8], name: &str, value: &str) -> Hmac<Sha256> {
+        let mut mac = <Hmac<Sha256> as Mac>::new_from_slice(key).expect("HMAC accepts keys of any length");
+        mac.update(format!("{name}={value}").as_bytes());
+        mac
+    }
+
+    fn cipher(key: &[u8]) -> Aes256Gcm {
+        let key = Sha256::new().chain_update(b"cookie-encryption\n").chain_update(key).finalize();
+        Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key))
+    }
+}
@@ -0,0 +1,255 @@
+use std::time::Duration;
+use crate::error::ServerError;
+use crate::message::{Request, Response};
+use crate::method::HttpMethod;
+use crate::middleware::Next;
+
+#[derive(Debug, Clone)]
+pub enum AllowedOrigins {
+    Any,
+    List(Vec<String>)
+}
+
+#[derive(Debug, Clone)]
+pub struct Cors {
+    origins: AllowedOrigins,
+    methods: Vec<HttpMethod>,
+    headers: Option<Vec<String>>,
+    exposed_headers: Vec<String>,
+    credentials: bool,
+    max_age: Option<Duration>
+}
+
+impl Default for Cors {
+    fn default() -> Self {
+        Self {
+            origins: AllowedOrigins::List(Vec::new()),
+            methods: vec![HttpMethod::Get, HttpMethod::Head, HttpMethod::Post, HttpMethod::Put, HttpMethod::Patch, HttpMethod::Delete],
+            headers: Some(Vec::new()),
+            exposed_headers: Vec::new(),
+            credentials: false,
+            max_age: None
+        }
+    }
+}
+
+impl Cors {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn permissive() -> Self {
+        Self::new().any_origin().any_header()
+    }
+
+    pub fn origin(mut self, origin: &str) -> Self {
+        let origin = origin.trim_end_matches('/').to_ascii_lowercase();
+
+        match &mut self.origins {
+            AllowedOrigins::List(origins) => origins.push(origin),
+            AllowedOrigins::Any => self.origins = AllowedOrigins::List(vec![origin])
+        }
+
+        self
+    }
+
+    pub fn any_origin(mut self) -> Self {
+        self.origins = AllowedOrigins::Any;
+        self
+    }
+
+    pub fn methods(mut self, methods: &[HttpMethod]) -> Self {
+        self.methods = methods.to_vec();
+        self
+    }
+
+    pub fn header(mut self, header: &str) -> Self {
+        self.headers.get_or_insert_with(Vec::new).push(header.to_ascii_lowercase());
+        self
+    }
+
+    pub fn any_header(mut self) -> Self {
+        self.headers = None;
+        self
+    }
+
+    pub fn expose_header(mut self, header: &str) -> Self {
+        self.exposed_headers.push(header.to_string());
+        self
+    }
+
+    pub fn credentials(mut self, enabled: bool) -> Self {
+        self.credentials = enabled;
+        self
+    }
+
+    pub fn max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    pub fn allows_origin(&self, origin: &str) -> bool {
+        match &self.origins {
+            AllowedOrigins::Any => true,
+            AllowedOrigins::List(origins) => origins.iter().any(|allowed| allowed.eq_ignore_ascii_case(origin))
+        }
+    }
+
+    pub fn preflight(&self, request: &Request) -> Option<Response> {
+        let origin = request.header("origin").filter(|origin| self.allows_origin(origin))?;
+        let method = request.header("access-control-request-method")?;
+        HttpMethod::try_from(method.trim()).ok().filter(|method| self.methods.contains(method))?;
+
+        let requested: Vec<String> = request.header("access-control-request-headers")
+            .map(|headers| headers.split(',').map(|header| header.trim().to_ascii_lowercase()).filter(|header| !header.is_empty()).collect())
+            .unwrap_or_default();
+
+        if let Some(allowed) = &self.headers {
+            if !requested.iter().all(|header| allowed.contains(header)) {
+                return None;
+            }
+        }
+
+        let mut response = Response::no_content();
+        self.apply_origin(origin, &mut response);
+
+        let methods: Vec<&str> = self.methods.iter().map(|allowed| allowed.as_str()).collect();
+        response.header("Access-Control-Allow-Methods", &methods.join(", "));
+
+        let headers = match &self.headers {
+            Some(allowed) => allowed.join(", "),
+            None => requested.join(", ")
+        };
+
+        if !headers.is_empty() {
+            response.header("Access-Control-Allow-Headers", &headers);
+        }
+
+        if let Some(max_age) = self.max_age {
+            response.header("Access-Control-Max-Age", &max_age.as_secs().to_string());
+        }
+
+        Some(response)
+    }
+
+    pub fn apply(&self, request: &Request, response: &mut Response) {
+        let Some(origin) = request.header("origin").filter(|origin| self.allows_origin(origin)) else {
+            if matches!(self.origins, AllowedOrigins::List(_)) {
+                vary(response, "Origin");
+            }
+
+            return;
+        };
+
+        self.apply_origin(origin, response);
+
+        if !self.exposed_headers.is_empty() {
+            response.header("Access-Control-Expose-Headers", &self.exposed_headers.join(", "));
+        }
+    }
+
+    pub fn middleware<E: ServerError>(self) -> impl Fn(&mut Request, Next<'_, E>) -> Result<Response, E> + Sync + Send + 'static {
+        move |request, next| {
+            if request.method() == HttpMethod::Options && request.header("access-control-request-method").is_some() {
+                if let Some(response) = self.preflight(request) {
+                    return Ok(response);
+                }
+            }
+
+            let mut response = next.run(request)?;
+            self.apply(request, &mut response);
+            Ok(response)
+        }
+    }
+
+    fn apply_origin(&self, origin: &str, response: &mut Response) {
+        match (&self.origins, self.credentials) {
+            (AllowedOrigins::Any, false) => response.header("Access-Control-Allow-Origin", "*"),
+            _ => {
+                response.header("Access-Control-Allow-Origin", origin);
+                vary(response, "Origin");
+            }
+        }
+
+        if self.credentials {
+            response.header("Access-Control-Allow-Credentials", "true");
+        }
+    }
+}
+
+fn vary(response: &mut Response, header: &str) {
+    let value = match response.get_header("Vary") {
+        Some(vary) if vary.split(',').any(|value| value.trim().eq_ignore_ascii_case(header)) => return,
+        Some(vary) => format!("{vary}, {header}"),
+        None => header.to_string()
+    };
+
+    response.header("Vary", &value);
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+    use crate::error::DefaultError;
+    use crate::middleware::Middleware;
+    use crate::route::Router;
+    use super::*;
+
+    fn run(cors: Cors, method: &str, headers: &str) -> Response {
+        let bytes = format!("{method} /items HTTP/1.1\r\nHost: api.test\r\n{headers}\r\n");
+        let mut request = Request::from_bytes(SocketAddr::from(([127, 0, 0, 1], 1234)), bytes.as_bytes()).unwrap();
+        let router = Router::new(|_: &Request| Ok::<_, DefaultError>(Response::text("fallback", 200)));
+        let middleware = [Middleware::new("/", cors.middleware())];
+
+        Next::new(&middleware, &router).run(&mut request).unwrap()
+    }
+
+    fn preflight(origin: &str, method: &str, headers: &str) -> String {
+        format!("Origin: {origin}\r\nAccess-Control-Request-Method: {method}\r\nAccess-Control-Request-Headers: {headers}\r\n")
+    }
+
+    #[test]
+    fn answers_allowed_preflights() {
+        let cors = Cors::new().origin("https://app.test/").header("X-Token").max_age(Duration::from_secs(600));
+        let response = run(cors, "OPTIONS", &preflight("https://APP.test", "PUT", "x-token"));
+
+        assert_eq!(response.status(), 204);
+        assert_eq!(response.get_header("Access-Control-Allow-Origin"), Some("https://APP.test"));
+        assert_eq!(response.get_header("Access-Control-Allow-Methods"), Some("GET, HEAD, POST, PUT, PATCH, DELETE"));
+        assert_eq!(response.get_header("Access-Control-Allow-Headers"), Some("x-token"));
+        assert_eq!(response.get_header("Access-Control-Max-Age"), Some("600"));
+        assert_eq!(response.get_header("Vary"), Some("Origin"));
+    }
+
+    #[test]
+    fn passes_denied_preflights_through_without_cors_headers() {
+        let cors = || Cors::new().origin("https://app.test").methods(&[HttpMethod::Get]);
+
+        for headers in [preflight("https://evil.test", "GET", ""), preflight("https://app.test", "DELETE", ""), preflight("https://app.test", "GET", "x-token")] {
+            let response = run(cors(), "OPTIONS", &headers);
+
+            assert_eq!(response.status(), 200, "{headers}");
+            assert_eq!(response.get_header("Access-Control-Allow-Methods"), None);
+        }
+
+        let response = run(cors(), "OPTIONS", &preflight("https://evil.test", "GET", ""));
+        assert_eq!(response.get_header("Access-Control-Allow-Origin"), None);
+        assert_eq!(response.get_header("Vary"), Some("Origin"));
+    }
+
+    #[test]
+    fn varies_on_origin_unless_any_origin_is_allowed() {
+        let response = run(Cors::permissive(), "GET", "Origin: https://app.test\r\n");
+        assert_eq!(response.get_header("Access-Control-Allow-Origin"), Some("*"));
+        assert_eq!(response.get_header("Vary"), None);
+
+        let response = run(Cors::permissive().credentials(true), "GET", "Origin: https://app.test\r\n");
+        assert_eq!(response.get_header("Access-Control-Allow-Origin"), Some("https://app.test"));
+        assert_eq!(response.get_header("Access-Control-Allow-Credentials"), Some("true"));
+        assert_eq!(response.get_header("Vary"), Some("Origin"));
+
+        let response = run(Cors::new().origin("https://app.test").expose_header("X-Total"), "GET", "");
+        assert_eq!(response.get_header("Access-Control-Expose-Headers"), None);
+        assert_eq!(response.get_header("Vary"), Some("Origin"));
+    }
+}
@@ -0,0 +1,76 @@
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use md5::Md5;
+use sha2::{Digest, Sha256, Sha512};
+use crate::error::RequestParseError;
+use crate::message::Request;
+
+pub fn content_md5(body: &[u8]) -> String {
+    STANDARD.encode(Md5::digest(body))
+}
+
+pub fn sha256(body: &[u8]) -> String {
+    sha256_value(&Sha256::digest(body))
+}
+
+pub fn sha256_value(hash: &[u8]) -> String {
+    format!("sha-256={}", STANDARD.encode(hash))
+}
+
+pub fn verify(request: &Request) -> Result<(), RequestParseError> {
+    let body = request.raw();
+
+    if let Some(md5) = request.header("content-md5") {
+        if md5.trim() != content_md5(body) {
+            return Err(RequestParseError::Digest);
+        }
+    }
+
+    if let Some(digest) = request.header("digest") {
+        for entry in digest.split(',') {
+            let (algorithm, value) = entry.split_once('=').ok_or(RequestParseError::Header("digest".to_string()))?;
+
+            let expected = match algorithm.trim().to_ascii_lowercase().as_str() {
+                "md5" => STANDARD.encode(Md5::digest(body)),
+                "sha-256" => STANDARD.encode(Sha256::digest(body)),
+                "sha-512" => STANDARD.encode(Sha512::digest(body)),
+                _ => continue
+            };
+
+            if value.trim() != expected {
+                return Err(RequestParseError::Digest);
+            }
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+    use super::*;
+
+    fn request(headers: &str) -> Request {
+        let bytes = format!("POST /a HTTP/1.1\r\nHost: localhost\r\nContent-Length: 5\r\n{headers}\r\nhello");
+        Request::from_bytes(SocketAddr::from(([127, 0, 0, 1], 1234)), bytes.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn accepts_matching_digests() {
+        let headers = format!("Content-MD5: {}\r\nDigest: {}, unknown=abc\r\n", content_md5(b"hello"), sha256(b"hello"));
+        assert!(verify(&request(&headers)).is_ok());
+        assert!(verify(&request("")).is_ok());
+    }
+
+    #[test]
+    fn rejects_mismatched_digests() {
+        let headers = format!("Digest: {}\r\n", sha256(b"other"));
+        assert!(matches!(verify(&request(&headers)), Err(RequestParseError::Digest)));
+
+        let headers = format!("Content-MD5: {}\r\n", content_md5(b"other"));
+        assert!(matches!(verify(&request(&headers)), Err(RequestParseError::Digest)));
+
+        assert!(matches!(verify(&request("Digest: sha-256\r\n")), Err(RequestParseError::Header(_))));
+    }
+}
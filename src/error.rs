@@ -1,23 +1,32 @@
-use std::any::{Any, TypeId};
-use std::collections::HashMap;
 use std::error::Error;
-use std::fmt::{Display, Formatter, self, Debug};
+use std::fmt::{Display, Formatter, self};
 use std::io::{self, ErrorKind};
-use std::marker::PhantomData;
-use std::string::ParseError;
+use log::warn;
+use crate::api_key::ApiKeyError;
+use crate::auth::AuthError;
 use crate::message::{Response, Request};
 
-pub const DEFAULT_HANDLER: fn(&Request, err: DefaultError) -> Response = |req, err| {
-    eprintln!("Error: {}", err);
+pub const DEFAULT_HANDLER: fn(&Request, err: DefaultError) -> Response = |_req, err| {
+    warn!("{}", err);
 
     match err {
         DefaultError::NotFound => Response::text("Not found", 404),
-        DefaultError::RequestParse(_) => Response::text("Malformed request", 500),
+        DefaultError::Unauthorized => Response::text("Unauthorized", 401),
+        DefaultError::Forbidden => Response::text("Forbidden", 403),
+        DefaultError::TooManyRequests => Response::text("Too many requests", 429),
+        DefaultError::LengthRequired => Response::text("Length required", 411),
+        DefaultError::PayloadTooLarge => Response::text("Payload too large", 413),
+        DefaultError::UnsupportedMediaType => {
+            let mut response = Response::text("Unsupported media type", 415);
+            response.header("Accept-Encoding", "gzip");
+            response
+        },
+        DefaultError::RequestParse(_) => Response::text("Malformed request", 400),
         DefaultError::Other(_) => Response::text("Internal server error", 500)
     }
 };
 
-pub trait ServerError: Error + Sync + Send {}
+pub trait ServerError: Error + Sync + Send + From<RequestParseError> {}
 
 #[derive(Debug, Copy, Clone)]
 pub struct InvalidMethodError;
@@ -30,6 +39,17 @@ impl Display for InvalidMethodError {
 
 impl Error for InvalidMethodError {}
 
+#[derive(Debug, Copy, Clone)]
+pub struct InvalidSignatureError;
+
+impl Display for InvalidSignatureError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "The given signature is invalid or has expired")
+    }
+}
+
+impl Error for InvalidSignatureError {}
+
 #[derive(Debug)]
 pub enum RequestParseError {
     MalformedRequest,
@@ -38,12 +58,25 @@ pub enum RequestParseError {
     Protocol,
     Host,
     Body,
-    Header(String)
+    Query,
+    Multipart,
+    Cookie,
+    Header(String),
+    Digest,
+    LengthRequired,
+    PayloadTooLarge,
+    UnsupportedEncoding
 }
 
 impl Display for RequestParseError {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
-        write!(f, "Failed to parse {}", format!("{:?}", self).to_lowercase())
+        match self {
+            Self::Digest => write!(f, "Request body does not match its digest"),
+            Self::LengthRequired => write!(f, "Request body has no Content-Length or chunked framing"),
+            Self::PayloadTooLarge => write!(f, "Request body exceeds the maximum allowed size"),
+            Self::UnsupportedEncoding => write!(f, "Request body has an unsupported Content-Encoding"),
+            _ => write!(f, "Failed to parse {}", format!("{:?}", self).to_lowercase())
+        }
     }
 }
 
@@ -58,6 +91,12 @@ impl From<InvalidMethodError> for RequestParseError {
 #[derive(Debug)]
 pub enum DefaultError {
     NotFound,
+    Unauthorized,
+    Forbidden,
+    TooManyRequests,
+    LengthRequired,
+    PayloadTooLarge,
+    UnsupportedMediaType,
     RequestParse(RequestParseError),
     Other(Box<dyn Error + Send + Sync>)
 }
@@ -66,8 +105,14 @@ impl Display for DefaultError {
     fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
         match self {
             Self::NotFound => write!(f, "Could not find the requested resource"),
-            Self::RequestParse(err) => write!(f, "Failed to parse request"),
-            Self::Other(err) => write!(f, "Internal server error. {}", err.to_string())
+            Self::Unauthorized => write!(f, "Authentication is required to access the requested resource"),
+            Self::Forbidden => write!(f, "Access to the requested resource is forbidden"),
+            Self::TooManyRequests => write!(f, "Too many requests"),
+            Self::LengthRequired => write!(f, "A Content-Length is required"),
+            Self::PayloadTooLarge => write!(f, "Request body exceeds the maximum allowed size"),
+            Self::UnsupportedMediaType => write!(f, "The request body is in an unsupported format"),
+            Self::RequestParse(err) => write!(f, "Failed to parse request. {}", err),
+            Self::Other(err) => write!(f, "Internal server error. {}", err)
         }
     }
 }
@@ -90,9 +135,50 @@ impl From<serde_json::Error> for DefaultError {
     }
 }
 
+impl From<InvalidSignatureError> for DefaultError {
+    fn from(_: InvalidSignatureError) -> DefaultError {
+        Self::Forbidden
+    }
+}
+
+impl From<ApiKeyError> for DefaultError {
+    fn from(err: ApiKeyError) -> DefaultError {
+        match err {
+            ApiKeyError::Missing | ApiKeyError::Invalid => Self::Unauthorized,
+            ApiKeyError::RateLimited => Self::TooManyRequests,
+            ApiKeyError::Scope => Self::Forbidden
+        }
+    }
+}
+
+impl From<AuthError> for DefaultError {
+    fn from(_: AuthError) -> DefaultError {
+        Self::Unauthorized
+    }
+}
+
+#[cfg(feature = "jwt")]
+impl From<crate::jwt::JwtError> for DefaultError {
+    fn from(_: crate::jwt::JwtError) -> DefaultError {
+        Self::Unauthorized
+    }
+}
+
+#[cfg(feature = "oauth")]
+impl From<crate::oauth::OAuthError> for DefaultError {
+    fn from(_: crate::oauth::OAuthError) -> DefaultError {
+        Self::Unauthorized
+    }
+}
+
 impl From<RequestParseError> for DefaultError {
     fn from(err: RequestParseError) -> DefaultError {
-        Self::Other(Box::new(err))
+        match err {
+            RequestParseError::LengthRequired => Self::LengthRequired,
+            RequestParseError::PayloadTooLarge => Self::PayloadTooLarge,
+            RequestParseError::UnsupportedEncoding => Self::UnsupportedMediaType,
+            err => Self::RequestParse(err)
+        }
     }
 }
 
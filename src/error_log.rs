@@ -0,0 +1,93 @@
+use std::collections::VecDeque;
+use std::error::Error;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+use serde::Serialize;
+use crate::error::ServerError;
+use crate::message::{Request, Response};
+use crate::route::RouteAction;
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ErrorRecord {
+    pub timestamp: u64,
+    pub client: String,
+    pub method: String,
+    pub target: String,
+    pub user_agent: Option<String>,
+    pub status: u16,
+    pub message: String,
+    pub sources: Vec<String>
+}
+
+#[derive(Debug)]
+pub struct ErrorLog {
+    capacity: usize,
+    min_status: u16,
+    records: Mutex<VecDeque<ErrorRecord>>
+}
+
+impl ErrorLog {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            min_status: 0,
+            records: Mutex::new(VecDeque::new())
+        }
+    }
+
+    pub fn min_status(mut self, status: u16) -> Self {
+        self.min_status = status;
+        self
+    }
+
+    pub fn record(&self, request: &Request, message: String, sources: Vec<String>, response: &Response) {
+        let status = response.status().as_u16();
+
+        if status < self.min_status {
+            return;
+        }
+
+        let record = ErrorRecord {
+            timestamp: SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64,
+            client: request.socket_addr().to_string(),
+            method: request.method().as_str().to_string(),
+            target: request.target().to_string(),
+            user_agent: request.header("user-agent").map(String::from),
+            status,
+            message,
+            sources
+        };
+
+        let mut records = self.records.lock().unwrap();
+
+        if records.len() == self.capacity {
+            records.pop_front();
+        }
+
+        records.push_back(record);
+    }
+
+    pub fn snapshot(&self) -> Vec<ErrorRecord> {
+        self.records.lock().unwrap().iter().cloned().collect()
+    }
+
+    pub fn clear(&self) {
+        self.records.lock().unwrap().clear();
+    }
+
+    pub fn endpoint<E: ServerError>(self: Arc<Self>) -> impl RouteAction<E> {
+        move |_| Ok(Response::json(self.snapshot(), 200).unwrap_or_else(|_| Response::text("Internal server error", 500)))
+    }
+}
+
+pub fn sources(err: &dyn Error) -> Vec<String> {
+    let mut sources = Vec::new();
+    let mut source = err.source();
+
+    while let Some(err) = source {
+        sources.push(err.to_string());
+        source = err.source();
+    }
+
+    sources
+}
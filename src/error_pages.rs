@@ -0,0 +1,111 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use log::error;
+use crate::message::{Request, Response};
+use crate::panic_hook;
+use crate::route::matches_prefix;
+use crate::status::StatusCode;
+
+#[derive(Debug, Clone, Default)]
+pub struct ErrorPages {
+    scopes: Vec<(String, HashMap<StatusCode, PathBuf>)>
+}
+
+impl ErrorPages {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn page(self, status: impl Into<StatusCode>, file: impl Into<PathBuf>) -> Self {
+        self.scope("/", status, file)
+    }
+
+    pub fn scope(mut self, prefix: &str, status: impl Into<StatusCode>, file: impl Into<PathBuf>) -> Self {
+        let status = status.into();
+        let prefix = format!("/{}", prefix.trim_matches('/'));
+
+        match self.scopes.iter_mut().find(|(scope, _)| *scope == prefix) {
+            Some((_, pages)) => {
+                pages.insert(status, file.into());
+            },
+            None => {
+                self.scopes.push((prefix, HashMap::from([(status, file.into())])));
+                self.scopes.sort_by_key(|(prefix, _)| std::cmp::Reverse(prefix.len()));
+            }
+        }
+
+        self
+    }
+
+    pub fn apply(&self, request: &Request, response: &mut Response) {
+        let status = response.status();
+        let route = request.route();
+
+        let file = self.scopes.iter()
+            .filter(|(prefix, _)| matches_prefix(route, prefix))
+            .find_map(|(_, pages)| pages.get(&status));
+
+        let Some(file) = file else {
+            return;
+        };
+
+        match fs::read_to_string(file) {
+            Ok(template) => {
+                let page = template
+                    .replace("{{status}}", &status.as_u16().to_string())
+                    .replace("{{path}}", &escape_html(route))
+                    .replace("{{method}}", request.method().as_str())
+                    .replace("{{request_id}}", &panic_hook::active_request_id().map(|id| id.to_string()).unwrap_or_default());
+
+                if let Err(err) = response.set_body(page.as_bytes(), "text/html; charset=utf-8") {
+                    error!("Failed to render error page {}: {}", file.display(), err);
+                }
+            },
+            Err(err) => error!("Failed to read error page {}: {}", file.display(), err)
+        }
+    }
+
+    pub fn check(&self) -> Result<(), String> {
+        let unreadable: Vec<String> = self.scopes.iter()
+            .flat_map(|(_, pages)| pages.values())
+            .filter_map(|file| fs::read_to_string(file).err().map(|err| format!("{} ({})", file.display(), err)))
+            .collect();
+
+        match unreadable.is_empty() {
+            true => Ok(()),
+            false => Err(format!("Could not load error page templates: {}", unreadable.join(", ")))
+        }
+    }
+}
+
+pub fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+        .replace('\'', "&#39;")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+    use super::*;
+
+    #[test]
+    fn renders_the_active_request_id() {
+        let file = std::env::temp_dir().join(format!("http-server-error-page-{}.html", std::process::id()));
+        fs::write(&file, "{{status}} {{method}} {{path}} {{request_id}}").unwrap();
+
+        let pages = ErrorPages::new().page(404, &file);
+        let request = Request::from_bytes(SocketAddr::from(([127, 0, 0, 1], 1234)), b"GET /<missing> HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+        let mut response = Response::text("Not found", 404);
+
+        let _active = panic_hook::track(&request);
+        let id = panic_hook::active_request_id().unwrap();
+        pages.apply(&request, &mut response);
+        fs::remove_file(&file).ok();
+
+        assert_eq!(response.body(), format!("404 GET {} {id}", escape_html(request.route())).as_bytes());
+    }
+}
@@ -0,0 +1,50 @@
+use std::fmt::{self, Debug, Formatter};
+use std::io;
+use std::sync::Arc;
+use std::time::Duration;
+use crate::message::Request;
+use crate::status::StatusCode;
+
+#[derive(Debug)]
+pub struct ResponseEvent<'a> {
+    pub request: &'a Request,
+    pub status: StatusCode,
+    pub bytes: u64,
+    pub duration: Duration,
+    pub error: Option<&'a io::Error>
+}
+
+impl ResponseEvent<'_> {
+    pub fn delivered(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+type ResponseListener = Arc<dyn Fn(&ResponseEvent<'_>) + Sync + Send>;
+
+#[derive(Clone, Default)]
+pub struct ResponseHooks {
+    listeners: Vec<ResponseListener>
+}
+
+impl ResponseHooks {
+    pub fn add(&mut self, listener: impl Fn(&ResponseEvent<'_>) + Sync + Send + 'static) {
+        self.listeners.push(Arc::new(listener));
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.listeners.is_empty()
+    }
+
+    pub fn fire(&self, event: &ResponseEvent<'_>) {
+        for listener in &self.listeners {
+            listener(event);
+        }
+    }
+}
+
+impl Debug for ResponseHooks {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("ResponseHooks").field("listeners", &self.listeners.len()).finish()
+    }
+}
@@ -0,0 +1,143 @@
+use std::collections::HashMap;
+use std::time::Duration;
+use rand::Rng;
+use crate::cookie::{Cookie, SameSite};
+use crate::error::ServerError;
+use crate::message::{Request, Response};
+use crate::middleware::Next;
+
+#[derive(Debug, Clone)]
+pub struct Experiment {
+    name: String,
+    cookie: String,
+    buckets: Vec<(String, u32)>,
+    routes: HashMap<String, String>
+}
+
+impl Experiment {
+    pub fn new(name: &str) -> Self {
+        Self {
+            name: name.to_string(),
+            cookie: format!("experiment_{name}"),
+            buckets: Vec::new(),
+            routes: HashMap::new()
+        }
+    }
+
+    pub fn bucket(mut self, bucket: &str, weight: u32) -> Self {
+        self.buckets.push((bucket.to_string(), weight));
+        self
+    }
+
+    pub fn route(mut self, bucket: &str, prefix: &str) -> Self {
+        self.routes.insert(bucket.to_string(), format!("/{}", prefix.trim_matches('/')));
+        self
+    }
+
+    pub fn cookie(mut self, cookie: &str) -> Self {
+        self.cookie = cookie.to_string();
+        self
+    }
+
+    pub fn check(&self) -> Result<(), String> {
+        match self.total_weight() {
+            0 => Err(format!("Experiment {} has no bucket with a positive weight", self.name)),
+            _ => Ok(())
+        }
+    }
+
+    pub fn assign(&self, request: &Request) -> Option<(String, bool)> {
+        let existing = request.cookie(&self.cookie)
+            .filter(|value| self.buckets.iter().any(|(bucket, _)| bucket == value));
+
+        if let Some(bucket) = existing {
+            return Some((bucket.to_string(), false));
+        }
+
+        let total = self.total_weight();
+
+        if total == 0 {
+            return None;
+        }
+
+        let mut pick = rand::thread_rng().gen_range(0..total);
+
+        for (bucket, weight) in &self.buckets {
+            let weight = u64::from(*weight);
+
+            if pick < weight {
+                return Some((bucket.clone(), true));
+            }
+
+            pick -= weight;
+        }
+
+        None
+    }
+
+    fn total_weight(&self) -> u64 {
+        self.buckets.iter().map(|(_, weight)| u64::from(*weight)).sum()
+    }
+
+    pub fn middleware<E: ServerError>(self) -> impl Fn(&mut Request, Next<'_, E>) -> Result<Response, E> + Sync + Send + 'static {
+        if let Err(err) = self.check() {
+            panic!("{err}");
+        }
+
+        move |request, next| {
+            let Some((bucket, assigned)) = self.assign(request) else {
+                return next.run(request);
+            };
+
+            request.set_experiment(&self.name, &bucket);
+
+            if let Some(prefix) = self.routes.get(&bucket) {
+                let route = format!("{prefix}{}", request.raw_route());
+                request.set_route(&route);
+            }
+
+            let mut response = next.run(request)?;
+            response.header(&format!("X-Experiment-{}", self.name), &bucket);
+
+            if assigned {
+                let cookie = Cookie::new(&self.cookie, &bucket)
+                    .path("/")
+                    .max_age(Duration::from_secs(2592000))
+                    .same_site(SameSite::Lax);
+
+                response.set_cookie(cookie);
+            }
+
+            Ok(response)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+    use crate::error::DefaultError;
+    use super::*;
+
+    fn request() -> Request {
+        Request::from_bytes(SocketAddr::from(([127, 0, 0, 1], 1234)), b"GET / HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap()
+    }
+
+    #[test]
+    fn sums_weights_without_overflowing() {
+        let experiment = Experiment::new("checkout").bucket("a", u32::MAX).bucket("b", u32::MAX).bucket("c", 1);
+        let (bucket, assigned) = experiment.assign(&request()).unwrap();
+
+        assert!(assigned);
+        assert!(["a", "b", "c"].contains(&bucket.as_str()));
+    }
+
+    #[test]
+    fn rejects_experiments_without_weight() {
+        let experiment = Experiment::new("checkout").bucket("a", 0).bucket("b", 0);
+
+        assert!(experiment.check().is_err());
+        assert!(experiment.assign(&request()).is_none());
+        assert!(std::panic::catch_unwind(|| experiment.middleware::<DefaultError>()).is_err());
+    }
+}
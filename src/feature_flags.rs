@@ -0,0 +1,24 @@
+use std::collections::{HashMap, HashSet};
+use crate::message::Request;
+
+pub trait FeatureFlags: Sync + Send {
+    fn enabled(&self, flag: &str, request: &Request) -> bool;
+}
+
+impl FeatureFlags for HashMap<String, bool> {
+    fn enabled(&self, flag: &str, _: &Request) -> bool {
+        self.get(flag).copied().unwrap_or(false)
+    }
+}
+
+impl FeatureFlags for HashSet<String> {
+    fn enabled(&self, flag: &str, _: &Request) -> bool {
+        self.contains(flag)
+    }
+}
+
+impl <F: Fn(&str, &Request) -> bool + Sync + Send> FeatureFlags for F {
+    fn enabled(&self, flag: &str, request: &Request) -> bool {
+        self(flag, request)
+    }
+}
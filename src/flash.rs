@@ -0,0 +1,171 @@
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use log::warn;
+use serde::{Deserialize, Serialize};
+use crate::cookie::{Cookie, SameSite};
+use crate::cookie_jar::CookieJar;
+use crate::form::Form;
+use crate::message::{Request, Response};
+
+pub const FLASH_COOKIE: &str = "flash";
+pub const MAX_COOKIE_SIZE: usize = 4096;
+const SENSITIVE_FIELDS: [&str; 6] = ["password", "passwd", "secret", "token", "card", "cvv"];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FlashMessage {
+    pub level: String,
+    pub message: String
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FlashData {
+    pub messages: Vec<FlashMessage>,
+    pub form: Option<Form>
+}
+
+impl FlashData {
+    pub fn message(mut self, level: &str, message: &str) -> Self {
+        self.messages.push(FlashMessage {
+            level: level.to_string(),
+            message: message.to_string()
+        });
+
+        self
+    }
+
+    pub fn form(mut self, form: Form) -> Self {
+        self.form = Some(form);
+        self
+    }
+}
+
+pub struct Flash {
+    jar: CookieJar,
+    sensitive: Vec<String>
+}
+
+impl Flash {
+    pub fn new(key: impl AsRef<[u8]>) -> Self {
+        Self::with_jar(CookieJar::new(key))
+    }
+
+    pub fn with_jar(jar: CookieJar) -> Self {
+        Self {
+            jar,
+            sensitive: SENSITIVE_FIELDS.iter().map(|field| field.to_string()).collect()
+        }
+    }
+
+    pub fn sensitive(mut self, field: &str) -> Self {
+        self.sensitive.push(field.to_ascii_lowercase());
+        self
+    }
+
+    pub fn set(&self, response: &mut Response, data: &FlashData) {
+        let mut data = data.clone();
+
+        if let Some(form) = &mut data.form {
+            let sensitive: Vec<String> = form.values().keys()
+                .filter(|field| self.is_sensitive(field))
+                .cloned()
+                .collect();
+
+            for field in sensitive {
+                form.remove(&field);
+            }
+        }
+
+        loop {
+            let cookie = self.cookie(&data);
+
+            if cookie.to_string().len() <= MAX_COOKIE_SIZE {
+                response.set_cookie(cookie);
+                return;
+            }
+
+            if data.form.take().is_none() && data.messages.pop().is_none() {
+                break;
+            }
+
+            warn!("Flash data does not fit in a cookie, dropping the form or the last message");
+        }
+    }
+
+    pub fn take(&self, request: &Request, response: &mut Response) -> FlashData {
+        if request.cookie(FLASH_COOKIE).is_none() {
+            return FlashData::default();
+        }
+
+        response.set_cookie(Cookie::removal(FLASH_COOKIE));
+
+        self.jar.private(request, FLASH_COOKIE)
+            .and_then(|payload| serde_json::from_slice(&URL_SAFE_NO_PAD.decode(payload).ok()?).ok())
+            .unwrap_or_default()
+    }
+
+    fn cookie(&self, data: &FlashData) -> Cookie {
+        let payload = URL_SAFE_NO_PAD.encode(serde_json::to_vec(data).unwrap_or_default());
+        let cookie = Cookie::new(FLASH_COOKIE, &payload)
+            .path("/")
+            .http_only(true)
+            .same_site(SameSite::Lax);
+
+        self.jar.encrypt(cookie)
+    }
+
+    fn is_sensitive(&self, field: &str) -> bool {
+        let field = field.to_ascii_lowercase();
+        self.sensitive.iter().any(|sensitive| field.contains(sensitive.as_str()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::net::SocketAddr;
+    use super::*;
+
+    fn round_trip(flash: &Flash, data: &FlashData) -> (String, FlashData) {
+        let mut response = Response::new(303);
+        flash.set(&mut response, data);
+
+        let value = response.cookies().iter().find(|cookie| cookie.name() == FLASH_COOKIE).unwrap().value().to_string();
+        let bytes = format!("GET / HTTP/1.1\r\nHost: localhost\r\nCookie: {FLASH_COOKIE}={value}\r\n\r\n");
+        let request = Request::from_bytes(SocketAddr::from(([127, 0, 0, 1], 1234)), bytes.as_bytes()).unwrap();
+        (value, flash.take(&request, &mut Response::new(200)))
+    }
+
+    #[test]
+    fn encrypts_messages_and_drops_sensitive_fields() {
+        let form = Form::new(HashMap::from([
+            ("email".to_string(), "ada@example.com".to_string()),
+            ("password".to_string(), "hunter2".to_string()),
+            ("Password_Confirmation".to_string(), "hunter2".to_string()),
+            ("pin".to_string(), "1234".to_string())
+        ]));
+
+        let flash = Flash::new("key").sensitive("pin");
+        let (value, data) = round_trip(&flash, &FlashData::default().message("error", "Invalid login").form(form));
+        let form = data.form.unwrap();
+
+        assert!(!String::from_utf8_lossy(&URL_SAFE_NO_PAD.decode(&value).unwrap()).contains("Invalid login"));
+        assert_eq!(data.messages[0].message, "Invalid login");
+        assert_eq!(form.value("email"), Some("ada@example.com"));
+        assert_eq!(form.values().len(), 1);
+    }
+
+    #[test]
+    fn trims_data_that_does_not_fit_in_a_cookie() {
+        let form = Form::new(HashMap::from([("bio".to_string(), "a".repeat(MAX_COOKIE_SIZE))]));
+        let data = FlashData::default().message("info", "Saved").message("info", &"b".repeat(MAX_COOKIE_SIZE)).form(form);
+
+        let mut response = Response::new(303);
+        Flash::new("key").set(&mut response, &data);
+        assert!(response.cookies().iter().all(|cookie| cookie.to_string().len() <= MAX_COOKIE_SIZE));
+
+        let (_, data) = round_trip(&Flash::new("key"), &data);
+        assert!(data.form.is_none());
+        assert_eq!(data.messages.len(), 1);
+        assert_eq!(data.messages[0].message, "Saved");
+    }
+}
@@ -0,0 +1,70 @@
+use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
+use url::form_urlencoded;
+use crate::error::RequestParseError;
+use crate::message::Request;
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Form {
+    values: HashMap<String, String>,
+    errors: HashMap<String, Vec<String>>
+}
+
+impl Form {
+    pub fn new(values: HashMap<String, String>) -> Self {
+        Self {
+            values,
+            errors: HashMap::new()
+        }
+    }
+
+    pub fn from_request(request: &Request) -> Result<Self, RequestParseError> {
+        Ok(Self::new(request.form()?))
+    }
+
+    pub fn parse(body: &[u8]) -> HashMap<String, String> {
+        form_urlencoded::parse(body).into_owned().collect()
+    }
+
+    pub fn value(&self, field: &str) -> Option<&str> {
+        self.values.get(field).map(|value| value.as_str())
+    }
+
+    pub fn values(&self) -> &HashMap<String, String> {
+        &self.values
+    }
+
+    pub fn remove(&mut self, field: &str) -> Option<String> {
+        self.values.remove(field)
+    }
+
+    pub fn required(&mut self, field: &str, message: &str) -> &mut Self {
+        self.validate(field, |value| !value.trim().is_empty(), message)
+    }
+
+    pub fn validate(&mut self, field: &str, predicate: impl Fn(&str) -> bool, message: &str) -> &mut Self {
+        let value = self.value(field).unwrap_or("");
+
+        if !predicate(value) {
+            self.error(field, message);
+        }
+
+        self
+    }
+
+    pub fn error(&mut self, field: &str, message: &str) {
+        self.errors.entry(field.to_string()).or_default().push(message.to_string());
+    }
+
+    pub fn errors(&self, field: &str) -> &[String] {
+        self.errors.get(field).map(|errors| errors.as_slice()).unwrap_or(&[])
+    }
+
+    pub fn error_map(&self) -> &HashMap<String, Vec<String>> {
+        &self.errors
+    }
+
+    pub fn is_valid(&self) -> bool {
+        self.errors.is_empty()
+    }
+}
@@ -0,0 +1,54 @@
+use std::collections::BTreeMap;
+use std::net::IpAddr;
+use std::path::Path;
+use std::sync::Arc;
+use maxminddb::{geoip2, MaxMindDBError, Reader};
+use crate::error::ServerError;
+use crate::message::{Request, Response};
+use crate::middleware::Next;
+
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct GeoInfo {
+    pub country_code: Option<String>,
+    pub country: Option<String>,
+    pub city: Option<String>,
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>
+}
+
+#[derive(Clone)]
+pub struct GeoIp {
+    reader: Arc<Reader<Vec<u8>>>
+}
+
+impl GeoIp {
+    pub fn open(database: impl AsRef<Path>) -> Result<Self, MaxMindDBError> {
+        Ok(Self {
+            reader: Arc::new(Reader::open_readfile(database)?)
+        })
+    }
+
+    pub fn lookup(&self, ip: IpAddr) -> Option<GeoInfo> {
+        let city: geoip2::City = self.reader.lookup(ip).ok()?;
+        let english = |names: Option<&BTreeMap<&str, &str>>| names.and_then(|names| names.get("en")).map(|name| name.to_string());
+        let location = city.location.as_ref();
+
+        Some(GeoInfo {
+            country_code: city.country.as_ref().and_then(|country| country.iso_code).map(str::to_string),
+            country: english(city.country.as_ref().and_then(|country| country.names.as_ref())),
+            city: english(city.city.as_ref().and_then(|city| city.names.as_ref())),
+            latitude: location.and_then(|location| location.latitude),
+            longitude: location.and_then(|location| location.longitude)
+        })
+    }
+
+    pub fn middleware<E: ServerError>(self) -> impl Fn(&mut Request, Next<'_, E>) -> Result<Response, E> + Sync + Send + 'static {
+        move |request, next| {
+            if let Some(info) = self.lookup(request.socket_addr().ip()) {
+                request.insert_ext(info);
+            }
+
+            next.run(request)
+        }
+    }
+}
@@ -0,0 +1,101 @@
+use std::collections::BTreeMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use serde::Deserialize;
+use crate::message::Response;
+
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+pub struct HeaderRule {
+    route: String,
+    #[serde(default)]
+    set: BTreeMap<String, String>,
+    #[serde(default)]
+    add: BTreeMap<String, String>,
+    #[serde(default)]
+    remove: Vec<String>
+}
+
+impl HeaderRule {
+    pub fn new(route: &str) -> Self {
+        Self {
+            route: format!("/{}", route.trim_matches('/')),
+            ..Self::default()
+        }
+    }
+
+    pub fn matches(&self, route: &str) -> bool {
+        let mut segments = route.trim_matches('/').split('/');
+
+        self.route.trim_matches('/')
+            .split('/')
+            .filter(|pattern| !pattern.is_empty())
+            .all(|pattern| segments.next().is_some_and(|segment| !segment.is_empty() && (pattern == "*" || pattern.starts_with(':') || pattern == segment)))
+    }
+
+    pub fn apply(&self, response: &mut Response) {
+        for name in &self.remove {
+            response.remove_header(name);
+        }
+
+        for (name, value) in &self.set {
+            response.header(name, value);
+        }
+
+        for (name, value) in &self.add {
+            response.append_header(name, value);
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct HeaderPolicy {
+    rules: Vec<HeaderRule>
+}
+
+impl HeaderPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let rules: Vec<HeaderRule> = serde_json::from_str(&fs::read_to_string(path)?)?;
+
+        Ok(Self {
+            rules: rules.into_iter().map(|rule| HeaderRule { route: format!("/{}", rule.route.trim_matches('/')), ..rule }).collect()
+        })
+    }
+
+    pub fn set(mut self, route: &str, header: &str, value: &str) -> Self {
+        self.rule(route).set.insert(header.to_string(), value.to_string());
+        self
+    }
+
+    pub fn add(mut self, route: &str, header: &str, value: &str) -> Self {
+        self.rule(route).add.insert(header.to_string(), value.to_string());
+        self
+    }
+
+    pub fn remove(mut self, route: &str, header: &str) -> Self {
+        self.rule(route).remove.push(header.to_string());
+        self
+    }
+
+    pub fn apply(&self, route: &str, response: &mut Response) {
+        for rule in self.rules.iter().filter(|rule| rule.matches(route)) {
+            rule.apply(response);
+        }
+    }
+
+    fn rule(&mut self, route: &str) -> &mut HeaderRule {
+        let rule = HeaderRule::new(route);
+
+        match self.rules.iter().position(|existing| existing.route == rule.route) {
+            Some(index) => &mut self.rules[index],
+            None => {
+                self.rules.push(rule);
+                self.rules.last_mut().unwrap()
+            }
+        }
+    }
+}
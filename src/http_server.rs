@@ -1,148 +1,1272 @@
-use std::any::{Any, TypeId};
-use std::collections::HashMap;
-use std::error::Error;
-use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::net::{SocketAddr, TcpListener, TcpStream, ToSocketAddrs};
+use std::path::Path;
 use std::thread;
 use std::io;
-use std::io::{BufReader, Read, Write};
-use std::sync::{Arc, RwLock, RwLockWriteGuard};
-use std::time::{Duration, Instant};
-use crate::error::{DEFAULT_HANDLER, DefaultError, ErrorAction, ServerError};
+use std::io::{ErrorKind, Write};
+use std::sync::{Arc, Mutex, RwLock, RwLockWriteGuard};
+use std::sync::mpsc::{self, RecvTimeoutError};
+use std::time::{Duration, Instant, SystemTime};
+use log::{Level, LevelFilter, debug, error, info, log_enabled, trace, warn};
+use crate::access_log::AccessLog;
+use crate::batch::Batch;
+use crate::cache_control::{CachePolicies, CachePolicy};
+use crate::header_policy::HeaderPolicy;
+use crate::compat::Compat;
+use crate::connection_limit::ConnectionLimit;
+use crate::conditional::{self, Conditional};
+use crate::cookie::CookieParser;
+use crate::cors::Cors;
+use crate::digest;
+use crate::error_pages::ErrorPages;
+use crate::feature_flags::FeatureFlags;
+use crate::i18n::I18n;
+use crate::events::{ResponseEvent, ResponseHooks};
+use crate::error::{DEFAULT_HANDLER, DefaultError, ErrorAction, RequestParseError, ServerError};
 use crate::message::{Request, Response};
+use crate::middleware::{self, Middleware, MiddlewareAction, MiddlewareInfo, Next};
 use crate::method::HttpMethod;
-use crate::route::{NOT_FOUND_ACTION, RouteAction, Router};
-use super::route::RoutingTreeNode;
+use crate::parser::{BodyLimits, Frame, RequestReader};
+use crate::panic_hook;
+use crate::pool::{PoolStats, ThreadPool};
+use crate::prefork;
+use crate::priority::{Priorities, Priority};
+use crate::predicate::Predicate;
+use crate::preflight::Preflight;
+use crate::preload::PreloadManifest;
+use crate::rate_limit::RateLimiter;
+use crate::restart::{self, SoftRestart};
+use crate::route::{NOT_FOUND_ACTION, RouteAction, RouteGroup, Router, TrailingSlash};
+use crate::state::SharedState;
+use crate::static_files::StaticFiles;
+use crate::stats::{ServerStats, StatsWriter};
+use crate::throttle::{ThrottledWriter, TokenBucket};
+use crate::transfer_stats::TransferStats;
+use crate::sampling::RequestSampler;
+use crate::error_log::{self, ErrorLog};
+use crate::uri::{self, DotSegments};
 
 pub const BUFFER_SIZE: usize = 2048;
+const MAX_FORWARDS: usize = 10;
+pub(crate) const KEEP_ALIVE_TIMEOUT: Duration = Duration::from_secs(5);
+const MAX_DECOMPRESSED_SIZE: usize = 10 * 1024 * 1024;
+const DRAIN_LIMIT: usize = 64 * 1024;
+const ERROR_BUDGET: usize = 3;
+const HANDLER_WORKERS: usize = 16;
+pub(crate) const SERVER_NAME: &str = concat!("http_server/", env!("CARGO_PKG_VERSION"));
+const THREAD_NAME: &str = "http";
+const HEADER_TIMEOUT: Duration = Duration::from_secs(10);
+const BODY_TIMEOUT: Duration = Duration::from_secs(60);
+const DRAIN_TIMEOUT: Duration = Duration::from_secs(30);
+const DRAIN_POLL_INTERVAL: Duration = Duration::from_millis(50);
 const EDIT_AFTER_INIT_MESSAGE: &str = "Error: Attempt to edit server configuration after initialization. All configuration must be done before calling HttpServer::listen()";
 
-pub struct HttpServer<E: ServerError, R: RouteAction<E>, F: ErrorAction<E>> {
-    router: Arc<RwLock<Router<E, R>>>,
+#[derive(Debug, Clone, Default)]
+pub struct ServerConfig {
+    pub digests: bool,
+    pub i18n: Option<Arc<I18n>>,
+    pub connection_bandwidth: Option<u64>,
+    pub global_bandwidth: Option<Arc<Mutex<TokenBucket>>>,
+    pub transfer_stats: Option<Arc<TransferStats>>,
+    pub sampler: Option<Arc<RequestSampler>>,
+    pub error_log: Option<Arc<ErrorLog>>,
+    pub sniff_mime: bool,
+    pub preload: Option<Arc<PreloadManifest>>,
+    pub error_pages: Option<Arc<ErrorPages>>,
+    pub workers: Option<usize>,
+    pub memory_limit: Option<usize>,
+    pub keep_alive_timeout: Option<Duration>,
+    pub header_timeout: Option<Duration>,
+    pub body_timeout: Option<Duration>,
+    pub handler_timeout: Option<Duration>,
+    pub method_override: bool,
+    pub cookie_parser: CookieParser,
+    pub body_limits: BodyLimits,
+    pub max_decompressed_size: Option<usize>,
+    pub drain_limit: Option<usize>,
+    pub allow_missing_length: bool,
+    pub error_budget: Option<usize>,
+    pub log_level: Option<LevelFilter>,
+    pub thread_name: Option<String>,
+    pub panic_hook: bool,
+    pub restart: Option<Arc<SoftRestart>>,
+    pub prefork: Option<usize>,
+    pub connection_limit: Option<Arc<ConnectionLimit>>,
+    pub preflight: Preflight,
+    pub conditional: Conditional,
+    pub cache_policies: CachePolicies,
+    pub header_policy: Option<Arc<HeaderPolicy>>,
+    pub server_name: Option<String>,
+    pub priorities: Priorities,
+    pub dot_segments: DotSegments,
+    pub state: Arc<SharedState>,
+    pub compat: Compat,
+    pub response_hooks: ResponseHooks,
+    pub batch: Option<Arc<Batch>>
+}
+
+pub(crate) fn stamp(response: &mut Response, server: &str) {
+    if response.get_header("Date").is_none() {
+        response.header("Date", &httpdate::fmt_http_date(SystemTime::now()));
+    }
+
+    if !server.is_empty() && response.get_header("Server").is_none() {
+        response.header("Server", server);
+    }
+}
+
+fn reject(writer: &mut impl Write, stats: &ServerStats, server: &str, mut response: Response) {
+    response.header("Connection", "close");
+    stamp(&mut response, server);
+    stats.response(&response);
+    response.write_to(writer).ok();
+}
+
+fn deliver<W: Write>(writer: &mut ThrottledWriter<StatsWriter<W>>, hooks: &ResponseHooks, server: &str, request: &Request, response: &mut Response, started: Instant) -> io::Result<()> {
+    stamp(response, server);
+    let before = writer.get_ref().written();
+    let result = response.write_to(writer);
+
+    if !hooks.is_empty() {
+        hooks.fire(&ResponseEvent {
+            request,
+            status: response.status(),
+            bytes: writer.get_ref().written() - before,
+            duration: started.elapsed(),
+            error: result.as_ref().err()
+        });
+    }
+
+    result
+}
+
+pub(crate) fn keeps_alive(request: &Request, response: &Response) -> bool {
+    let has_token = |connection: Option<&str>, token: &str| connection
+        .is_some_and(|connection| connection.split(',').any(|value| value.trim().eq_ignore_ascii_case(token)));
+
+    if has_token(response.get_header("Connection"), "close") {
+        return false;
+    }
+
+    match request.version() >= 1.1 {
+        true => !has_token(request.header("connection"), "close"),
+        false => has_token(request.header("connection"), "keep-alive") && (!response.is_stream() || response.get_header("Content-Length").is_some())
+    }
+}
+
+fn missing_length(request: &Request) -> bool {
+    matches!(request.method(), HttpMethod::Post | HttpMethod::Put | HttpMethod::Patch)
+        && request.header("content-length").is_none()
+        && request.header("transfer-encoding").is_none()
+}
+
+pub(crate) fn set_connection(response: &mut Response, keep_alive: bool, timeout: Duration) {
+    if keep_alive {
+        response.header("Connection", "keep-alive");
+        response.header("Keep-Alive", &format!("timeout={}", timeout.as_secs()));
+    } else {
+        response.header("Connection", "close");
+    }
+}
+
+fn respond_within<E: ServerError + 'static, F: ErrorAction<E>>(
+    timeout: Duration,
+    handlers: &ThreadPool,
+    router: &Arc<RwLock<Router<E>>>,
+    middleware: &Arc<RwLock<Vec<Middleware<E>>>>,
+    error_handler: &Arc<RwLock<F>>,
+    config: &Arc<RwLock<ServerConfig>>,
+    mut request: Request
+) -> Result<(Request, Response), RecvTimeoutError> {
+    let (router, middleware, error_handler, config) = (router.clone(), middleware.clone(), error_handler.clone(), config.clone());
+    let (sender, receiver) = mpsc::channel();
+    let active = panic_hook::active_request();
+    let deadline = Instant::now() + timeout;
+
+    handlers.execute(move || {
+        if Instant::now() >= deadline {
+            return;
+        }
+
+        let _active = active.map(panic_hook::resume);
+        let router = router.read().unwrap();
+        let middleware = middleware.read().unwrap();
+        let error_handler = error_handler.read().unwrap();
+        let config = config.read().unwrap();
+
+        let response = dispatch(&router, &middleware, &*error_handler, &config, &mut request);
+        sender.send((request, response)).ok();
+    });
+
+    receiver.recv_timeout(timeout)
+}
+
+pub(crate) fn dispatch<E: ServerError, F: ErrorAction<E>>(router: &Router<E>, middleware: &[Middleware<E>], error_handler: &F, config: &ServerConfig, request: &mut Request) -> Response {
+    Pipeline { middleware, router, error_handler, config }.respond(request)
+}
+
+fn peek_priority(client: &TcpStream, priorities: &Priorities) -> Priority {
+    let mut head = [0_u8; 512];
+    client.set_nonblocking(true).ok();
+    let peeked = client.peek(&mut head).unwrap_or(0);
+    client.set_nonblocking(false).ok();
+    priorities.for_head(&head[..peeked])
+}
+
+fn overloaded() -> Response {
+    let mut response = Response::text("Service unavailable", 503);
+    response.header("Retry-After", "1");
+    response
+}
+
+struct Pipeline<'a, E: ServerError, F: ErrorAction<E>> {
+    middleware: &'a [Middleware<E>],
+    router: &'a Router<E>,
+    error_handler: &'a F,
+    config: &'a ServerConfig
+}
+
+impl <E: ServerError, F: ErrorAction<E>> Pipeline<'_, E, F> {
+    fn respond(&self, request: &mut Request) -> Response {
+        request.set_state(self.config.state.clone());
+
+        if let Some(accept) = request.header("accept").and_then(|accept| self.config.compat.accept(accept)) {
+            request.set_header("accept", &accept);
+        }
+
+        if let Some(i18n) = &self.config.i18n {
+            request.localize(i18n.clone());
+        }
+
+        if let Some(method) = request.method_override().filter(|_| self.config.method_override) {
+            request.set_method(method);
+        }
+
+        let mut response = match &self.config.batch {
+            Some(batch) if batch.matches(request) => batch.handle(request, |sub_request| self.respond(sub_request)),
+            _ => {
+                let decompressed_limit = self.config.body_limits.for_route(request.route())
+                    .unwrap_or(usize::MAX)
+                    .min(self.config.max_decompressed_size.unwrap_or(MAX_DECOMPRESSED_SIZE));
+
+                let canonical = match self.config.dot_segments {
+                    DotSegments::Reject if uri::has_dot_segments(request.target()) => Err(RequestParseError::Route),
+                    _ => Ok(())
+                };
+
+                let parsed = canonical
+                    .and_then(|_| request.parse_cookies(&self.config.cookie_parser))
+                    .and_then(|_| digest::verify(request))
+                    .and_then(|_| request.decompress(decompressed_limit));
+
+                let mut result = match parsed {
+                    Ok(()) => Next::new(self.middleware, self.router).run(request),
+                    Err(err) => Err(E::from(err))
+                };
+
+                let mut forwards = 0;
+
+                while let Ok(Some(route)) = result.as_mut().map(Response::take_forward) {
+                    forwards += 1;
+
+                    if forwards > MAX_FORWARDS {
+                        error!("Request to {} exceeded {} forwards", request.route(), MAX_FORWARDS);
+                        result = Ok(Response::text("Internal server error", 500));
+                        break;
+                    }
+
+                    request.set_route(&route);
+                    result = Next::new(&[], self.router).run(request);
+                }
+
+                match result {
+                    Ok(res) => res,
+                    Err(err) => self.handle_error(request, err)
+                }
+            }
+        };
+
+        response.fill_from(request);
+
+        if let Some(pages) = self.config.error_pages.as_ref().filter(|_| response.status() >= 400) {
+            pages.apply(request, &mut response);
+        }
+
+        if self.config.sniff_mime {
+            response.sniff_content_type();
+        }
+
+        if self.config.digests {
+            response.add_digests();
+        }
+
+        if let Some(preload) = &self.config.preload {
+            let is_html = response.get_header("Content-Type").is_some_and(|content_type| content_type.starts_with("text/html"));
+
+            if let Some(link) = preload.link_header(request.route()).filter(|_| is_html) {
+                response.header("Link", &link);
+            }
+        }
+
+        if let Some(policy) = self.config.cache_policies.for_route(request.route()) {
+            policy.apply(&mut response);
+        }
+
+        if self.config.conditional.applies(request.route()) && conditional::is_fresh(request, &response) {
+            response.not_modified();
+        }
+
+        if let Some(policy) = &self.config.header_policy {
+            policy.apply(request.route(), &mut response);
+        }
+
+        if request.method() == HttpMethod::Head {
+            response.strip_body();
+        }
+
+        response
+    }
+
+    fn handle_error(&self, request: &Request, err: E) -> Response {
+        let Some(errors) = &self.config.error_log else {
+            return (self.error_handler)(request, err);
+        };
+
+        let message = err.to_string();
+        let sources = error_log::sources(&err);
+        let response = (self.error_handler)(request, err);
+        errors.record(request, message, sources, &response);
+        response
+    }
+
+    fn reject(&self, request: &Request, err: RequestParseError) -> Response {
+        let mut response = self.handle_error(request, E::from(err));
+        response.fill_from(request);
+
+        if let Some(pages) = self.config.error_pages.as_ref().filter(|_| response.status() >= 400) {
+            pages.apply(request, &mut response);
+        }
+
+        if request.method() == HttpMethod::Head {
+            response.strip_body();
+        }
+
+        response
+    }
+}
+
+pub struct HttpServer<E: ServerError, F: ErrorAction<E>> {
+    router: Arc<RwLock<Router<E>>>,
+    middleware: Arc<RwLock<Vec<Middleware<E>>>>,
     error_handler: Arc<RwLock<F>>,
+    config: Arc<RwLock<ServerConfig>>,
+    stats: Arc<ServerStats>,
+    pool: Option<ThreadPool>,
+    handlers: Option<Arc<ThreadPool>>,
     active: bool
 }
 
-impl HttpServer<DefaultError, fn(&Request) -> Result<Response, DefaultError>, fn(&Request, DefaultError) -> Response> {
-    pub fn default() -> Self {
+impl Default for HttpServer<DefaultError, fn(&Request, DefaultError) -> Response> {
+    fn default() -> Self {
         Self::new(NOT_FOUND_ACTION, DEFAULT_HANDLER)
     }
 }
 
-impl <E: ServerError + 'static, R: RouteAction<E>, F: ErrorAction<E>> HttpServer<E, R, F> {
-    pub fn new(not_found_action: R, error_handler: F) -> Self {
+impl <E: ServerError + 'static, F: ErrorAction<E>> HttpServer<E, F> {
+    pub fn new(not_found_action: impl RouteAction<E>, error_handler: F) -> Self {
         Self {
             active: false,
             error_handler: Arc::new(RwLock::new(error_handler)),
+            config: Arc::new(RwLock::new(ServerConfig::default())),
+            stats: Arc::new(ServerStats::new()),
+            pool: None,
+            handlers: None,
+            middleware: Arc::new(RwLock::new(Vec::new())),
             router: Arc::new(RwLock::new(Router::new(not_found_action)))
         }
     }
 
-    pub fn listen(mut self, port: u16) -> io::Result<()> {
-        let address = SocketAddr::from(([127, 0, 0, 1], port));
-        let listener = TcpListener::bind(address)?;
+    pub fn listen(self, port: u16) -> io::Result<()> {
+        self.listen_on(SocketAddr::from(([127, 0, 0, 1], port)))
+    }
+
+    pub fn listen_on(self, addresses: impl ToSocketAddrs) -> io::Result<()> {
+        let inherits = self.config.read().unwrap().restart.is_some() || prefork::is_worker();
+        let inherited = inherits.then(restart::inherited_listeners).flatten();
+
+        if let Some(listeners) = inherited {
+            info!("Reusing {} inherited listener(s)", listeners.len());
+            return self.listen_with(listeners);
+        }
+
+        let listeners = addresses.to_socket_addrs()?
+            .map(TcpListener::bind)
+            .collect::<io::Result<Vec<TcpListener>>>()?;
+
+        self.listen_with(listeners)
+    }
+
+    pub fn listen_with(mut self, listeners: impl IntoIterator<Item = TcpListener>) -> io::Result<()> {
+        let mut listeners: Vec<TcpListener> = listeners.into_iter().collect();
+        let last = listeners.pop().ok_or(io::Error::new(ErrorKind::InvalidInput, "No address to listen on"))?;
+        self.config.read().unwrap().preflight.run().map_err(io::Error::other)?;
+
+        if let Some(workers) = self.config.read().unwrap().prefork.filter(|_| !prefork::is_worker()) {
+            listeners.push(last);
+            return prefork::supervise(workers, &listeners);
+        }
+
+        if let Some(restart) = &self.config.read().unwrap().restart {
+            restart.bind(&listeners.iter().chain([&last]).collect::<Vec<&TcpListener>>())?;
+        }
+
         self.active = true;
-        println!("Server active");
+        self.stats.start();
+        let name = self.thread_prefix();
+        self.pool = self.config.read().unwrap().workers.map(|workers| ThreadPool::new(workers, self.stats.pool(), &name));
+        self.handlers = self.config.read().unwrap().handler_timeout.map(|_| {
+            let workers = self.config.read().unwrap().workers.unwrap_or(HANDLER_WORKERS);
+            Arc::new(ThreadPool::new(workers, Arc::new(PoolStats::default()), &format!("{name}-handler")))
+        });
 
-        for client in listener.incoming() {
-            self.handle_client(client?)?
+        if let Some(level) = self.config.read().unwrap().log_level {
+            log::set_max_level(level);
+        }
+
+        if self.config.read().unwrap().panic_hook {
+            panic_hook::install();
         }
 
+        info!("Server active");
+
+        let server = Arc::new(self);
+
+        for listener in listeners {
+            let server = server.clone();
+            let address = listener.local_addr().map(|address| address.to_string()).unwrap_or_default();
+
+            thread::Builder::new().name(format!("{name}-listener-{address}")).spawn(move || {
+                if let Err(err) = server.accept(listener) {
+                    error!("Listener stopped: {}", err);
+                }
+            })?;
+        }
+
+        server.accept(last)?;
+        server.drain();
         Ok(())
     }
 
+    pub fn dispatch(&self, mut request: Request) -> Response {
+        let router = self.router.read().unwrap();
+        let middleware = self.middleware.read().unwrap();
+        let error_handler = self.error_handler.read().unwrap();
+        let config = self.config.read().unwrap();
+
+        dispatch(&router, &middleware, &*error_handler, &config, &mut request)
+    }
+
+    fn accept(&self, listener: TcpListener) -> io::Result<()> {
+        if let Ok(address) = listener.local_addr() {
+            info!("Listening on {}", address);
+        }
+
+        let restart = self.config.read().unwrap().restart.clone();
+
+        if let Some(restart) = &restart {
+            restart.started_accepting();
+        }
+
+        let mut result = Ok(());
+
+        for client in listener.incoming() {
+            result = client.and_then(|client| self.handle_client(client));
+
+            if result.is_err() || restart.as_ref().is_some_and(|restart| restart.is_draining()) {
+                break;
+            }
+        }
+
+        if let Some(restart) = &restart {
+            restart.stopped_accepting();
+        }
+
+        result
+    }
+
+    fn drain(&self) {
+        let Some(restart) = self.config.read().unwrap().restart.clone() else {
+            return;
+        };
+
+        let deadline = Instant::now() + restart.drain_deadline().unwrap_or(DRAIN_TIMEOUT);
+        let busy = || self.stats.snapshot().open_connections > 0 || self.pool.as_ref().is_some_and(|pool| pool.queue_depth() > 0);
+
+        while busy() && Instant::now() < deadline {
+            thread::sleep(DRAIN_POLL_INTERVAL);
+        }
+
+        match busy() {
+            true => warn!("Drain timed out with {} open connection(s)", self.stats.snapshot().open_connections),
+            false => info!("Drained all connections")
+        }
+    }
+
     fn handle_client(&self, mut client: TcpStream) -> io::Result<()> {
+        let limit = self.config.read().unwrap().connection_limit.clone();
+
+        let permit = match limit.map(|limit| limit.acquire()) {
+            Some(None) => {
+                self.stats.shed();
+                let config = self.config.read().unwrap();
+                reject(&mut client, &self.stats, config.server_name.as_deref().unwrap_or(SERVER_NAME), overloaded());
+                return Ok(());
+            },
+            permit => permit.flatten()
+        };
+
+        let priority = match &self.pool {
+            Some(pool) if pool.is_busy() => {
+                let config = self.config.read().unwrap();
+                let priority = match config.priorities.is_empty() {
+                    true => Priority::Normal,
+                    false => peek_priority(&client, &config.priorities)
+                };
+
+                if config.priorities.sheds(priority, pool.queue_depth()) {
+                    self.stats.shed();
+                    reject(&mut client, &self.stats, config.server_name.as_deref().unwrap_or(SERVER_NAME), overloaded());
+                    return Ok(());
+                }
+
+                priority
+            },
+            _ => Priority::Normal
+        };
+
         let router = self.router.clone();
+        let middleware = self.middleware.clone();
         let error_handler = self.error_handler.clone();
+        let config = self.config.clone();
+        let stats = self.stats.clone();
+        let handlers = self.handlers.clone();
 
-        thread::spawn(move || {
-            if let Ok(addr) = client.peer_addr() {
-                println!("Accepted client: {}:{}", addr.ip(), addr.port());
-                let mut buffer = [0_u8; BUFFER_SIZE];
-                let mut last_request = Instant::now();
-                let router_lock = router.read().unwrap();
-                let err_hand_lock = error_handler.read().unwrap();
+        let client_addr = client.peer_addr().ok();
 
-                loop {
-                    let mut data = Vec::new();
+        let task = move || {
+            let _permit = permit;
+
+            if let (Ok(addr), Ok(stream)) = (client.peer_addr(), client.try_clone()) {
+                debug!("Accepted client: {}:{}", addr.ip(), addr.port());
+                let _connection = stats.connection();
+
+                let (mut writer, keep_alive_timeout, body_limits, memory_limit, drain_limit, error_budget, compat, server, timeouts) = {
+                    let config = config.read().unwrap();
+                    let writer = ThrottledWriter::new(stats.writer(stream), config.connection_bandwidth, config.global_bandwidth.clone());
+                    let server = config.server_name.clone().unwrap_or_else(|| SERVER_NAME.to_string());
+                    let timeouts = (config.header_timeout.unwrap_or(HEADER_TIMEOUT), config.body_timeout.unwrap_or(BODY_TIMEOUT));
+                    (writer, config.keep_alive_timeout.unwrap_or(KEEP_ALIVE_TIMEOUT), config.body_limits.clone(), config.memory_limit, config.drain_limit.unwrap_or(DRAIN_LIMIT), config.error_budget.unwrap_or(ERROR_BUDGET), config.compat.clone(), server, timeouts)
+                };
+
+                client.set_read_timeout(Some(keep_alive_timeout)).ok();
+                let interim = client.try_clone().ok().filter(|_| compat.sends_continue());
+                let mut reader = RequestReader::new(client);
+                reader.timeouts(Some(timeouts.0), Some(timeouts.1));
+
+                if let Some(interim) = interim {
+                    reader.send_continue(interim);
+                }
+
+                let mut errors = 0;
 
-                    while let Ok(size) = client.read(&mut buffer) {
-                        data.extend_from_slice(&buffer[..size]);
+                loop {
+                    let mut request_buffer = stats.reservation(memory_limit);
 
-                        if size < BUFFER_SIZE {
+                    let frame = match reader.next_request(&body_limits, &mut request_buffer) {
+                        Ok(Some(frame)) => frame,
+                        Ok(None) => break,
+                        Err(err) if err.kind() == ErrorKind::OutOfMemory => {
+                            debug!("Shedding request from {}:{}: {}", addr.ip(), addr.port(), err);
+                            reject(&mut writer, &stats, &server, overloaded());
                             break;
+                        },
+                        Err(err) if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) && reader.in_progress() => {
+                            debug!("Timed out reading request from: {}:{}", addr.ip(), addr.port());
+                            reject(&mut writer, &stats, &server, Response::text("Request timeout", 408));
+                            break;
+                        },
+                        Err(err) if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut | ErrorKind::UnexpectedEof) => break,
+                        Err(err) => {
+                            warn!("{}", err);
+                            reject(&mut writer, &stats, &server, Response::text("Malformed request", 400));
+                            break;
+                        }
+                    };
+
+                    let started = Instant::now();
+                    let router_lock = router.read().unwrap();
+                    let middleware_lock = middleware.read().unwrap();
+                    let err_hand_lock = error_handler.read().unwrap();
+                    let config_lock = config.read().unwrap();
+
+                    let pipeline = Pipeline {
+                        middleware: &middleware_lock,
+                        router: &router_lock,
+                        error_handler: &*err_hand_lock,
+                        config: &config_lock
+                    };
+
+                    let data = match frame {
+                        Frame::Request(data) => {
+                            stats.request(data.len());
+                            data
+                        },
+                        Frame::TooLarge(head) => {
+                            stats.request(head.len());
+
+                            let Ok(request) = Request::from_frame(addr, &head, false, compat.fallback_host()) else {
+                                reject(&mut writer, &stats, &server, Response::text("Malformed request", 400));
+                                break;
+                            };
+
+                            let mut response = pipeline.reject(&request, RequestParseError::PayloadTooLarge);
+                            let drainable = reader.pending_body().is_some_and(|remaining| remaining <= drain_limit);
+
+                            let keep_alive = drainable && keeps_alive(&request, &response);
+                            stats.response(&response);
+                            set_connection(&mut response, keep_alive, keep_alive_timeout);
+                            let delivered = deliver(&mut writer, &config_lock.response_hooks, &server, &request, &mut response, started).is_ok();
+
+                            if !keep_alive || !delivered || !reader.drain(drain_limit).unwrap_or(false) {
+                                break;
+                            }
+
+                            continue;
+                        }
+                    };
+
+                    let mut request = match Request::from_frame(addr, &data, true, compat.fallback_host()) {
+                        Ok(request) => request,
+                        Err(err) => {
+                            warn!("{}", err);
+                            errors += 1;
+
+                            if errors >= error_budget {
+                                reject(&mut writer, &stats, &server, Response::text("Malformed request", 400));
+                                break;
+                            }
+
+                            let mut response = Response::text("Malformed request", 400);
+                            set_connection(&mut response, true, keep_alive_timeout);
+                            stamp(&mut response, &server);
+                            stats.response(&response);
+
+                            match response.write_to(&mut writer) {
+                                Ok(()) => continue,
+                                Err(_) => break
+                            }
                         }
+                    };
+
+                    trace!("Request:\n{:?}", String::from_utf8_lossy(&data));
+                    let _active = panic_hook::track(&request);
+
+                    if config_lock.priorities.sheds(config_lock.priorities.for_route(request.route()), stats.queue_depth()) {
+                        stats.shed();
+                        reject(&mut writer, &stats, &server, overloaded());
+                        break;
+                    }
+
+                    if !config_lock.allow_missing_length && missing_length(&request) {
+                        let mut response = pipeline.reject(&request, RequestParseError::LengthRequired);
+                        stats.response(&response);
+                        set_connection(&mut response, false, keep_alive_timeout);
+                        deliver(&mut writer, &config_lock.response_hooks, &server, &request, &mut response, started).ok();
+                        break;
                     }
 
-                    if data.len() > 0 {
-                        let request = Request::from_bytes(addr, &data).unwrap();
+                    if let Some(preload) = config_lock.preload.as_ref().filter(|preload| preload.sends_early_hints() && request.version() >= 1.1) {
+                        if let Some(hints) = preload.early_hints_bytes(request.route()) {
+                            writer.write_all(&hints).ok();
+                        }
+                    }
 
-                        println!("Request:\n{:?}", String::from_utf8_lossy(&data));
+                    let sampled = config_lock.sampler.as_ref().filter(|sampler| sampler.should_sample(&request));
+                    let mut response = match config_lock.handler_timeout.zip(handlers.as_deref()) {
+                        Some((timeout, handlers)) => match respond_within(timeout, handlers, &router, &middleware, &error_handler, &config, request) {
+                            Ok((responded, response)) => {
+                                request = responded;
+                                response
+                            },
+                            Err(RecvTimeoutError::Timeout) => {
+                                warn!("Handler did not finish within {:?}", timeout);
+                                reject(&mut writer, &stats, &server, Response::text("Service unavailable", 503));
+                                break;
+                            },
+                            Err(RecvTimeoutError::Disconnected) => {
+                                error!("Handler stopped without producing a response");
+                                reject(&mut writer, &stats, &server, Response::text("Internal server error", 500));
+                                break;
+                            }
+                        },
+                        None => pipeline.respond(&mut request)
+                    };
 
-                        let mut action = router_lock.get(request.method(), request.route());
+                    if let Some(sampler) = sampled {
+                        sampler.record(&request, &response, started.elapsed());
+                    }
 
-                        let mut response = match action(&request) {
-                            Ok(res) => res,
-                            Err(err) => err_hand_lock(&request, err)
-                        };
+                    let response_buffer = stats.reserve(response.body_len(), config_lock.memory_limit);
 
+                    if response_buffer.is_none() {
+                        response = overloaded();
                         response.fill_from(&request);
-                        let bytes = response.to_bytes();
-                        println!("\nConnection HEADER: {:?}", request.header("Connection"));
-                        println!("Response:\n{:?}", String::from_utf8_lossy(&bytes));
-                        client.write(&bytes).unwrap();
+                    }
 
-                        if request.version() == 1.0 || Some("close") == request.header("Connection") {
-                            break;
-                        }
+                    stats.response(&response);
+                    let draining = config_lock.restart.as_ref().is_some_and(|restart| restart.is_draining());
+                    let keep_alive = !draining && keeps_alive(&request, &response);
+                    set_connection(&mut response, keep_alive, keep_alive_timeout);
+
+                    if log_enabled!(Level::Trace) {
+                        trace!("Response:\n{:?}", String::from_utf8_lossy(&response.to_bytes()));
+                    }
 
-                        last_request = Instant::now();
-                    } else if last_request.elapsed().as_secs() > 4 {
+                    let before = writer.get_ref().written();
+
+                    if let Err(err) = deliver(&mut writer, &config_lock.response_hooks, &server, &request, &mut response, started) {
+                        warn!("Failed to write response: {}", err);
                         break;
                     }
 
-                    thread::sleep(Duration::from_millis(50))
+                    if let Some(transfers) = &config_lock.transfer_stats {
+                        transfers.record(&request, &response, writer.get_ref().written() - before);
+                    }
+
+                    if !keep_alive {
+                        break;
+                    }
                 }
 
-                println!("Closing connection with: {}:{}", addr.ip(), addr.port());
+                debug!("Closing connection with: {}:{}", addr.ip(), addr.port());
             }
-        });
+        };
+
+        match &self.pool {
+            Some(pool) => pool.execute_with(priority, task),
+            None => {
+                let peer = client_addr.map(|address| address.to_string()).unwrap_or_default();
+
+                if let Err(err) = thread::Builder::new().name(format!("{}-conn-{peer}", self.thread_prefix())).spawn(task) {
+                    error!("Failed to spawn connection thread: {}", err);
+                }
+            }
+        }
 
         Ok(())
     }
 
-    pub fn route(&mut self, method: HttpMethod, route: &str, action: R) {
+    fn thread_prefix(&self) -> String {
+        self.config.read().unwrap().thread_name.clone().unwrap_or_else(|| THREAD_NAME.to_string())
+    }
+
+    pub fn route(&mut self, method: HttpMethod, route: &str, action: impl RouteAction<E>) {
         let mut router = self.edit_router();
         router.add(method, route, action);
     }
 
-    pub fn get(&mut self, route: &str, action: R) {
+    pub fn route_flagged(&mut self, method: HttpMethod, route: &str, flag: &str, action: impl RouteAction<E>) {
+        let mut router = self.edit_router();
+        router.add_flagged(method, route, flag, action);
+    }
+
+    pub fn route_when(&mut self, method: HttpMethod, route: &str, predicate: Predicate, action: impl RouteAction<E>) {
+        let mut router = self.edit_router();
+        router.add_when(method, route, predicate, action);
+    }
+
+    pub fn scope(&mut self, prefix: &str, build: impl FnOnce(&mut RouteGroup<E>)) {
+        let mut group = RouteGroup::new();
+        build(&mut group);
+        self.mount(prefix, &group);
+    }
+
+    pub fn mount(&mut self, prefix: &str, group: &RouteGroup<E>) {
+        self.edit_router().mount(prefix, group);
+    }
+
+    pub fn feature_flags(&mut self, provider: impl FeatureFlags + 'static) {
+        self.edit_router().feature_flags(provider);
+    }
+
+    pub fn trailing_slash(&mut self, policy: TrailingSlash) {
+        self.edit_router().trailing_slash(policy);
+    }
+
+    pub fn get(&mut self, route: &str, action: impl RouteAction<E>) {
         self.route(HttpMethod::Get, route, action);
     }
 
-    pub fn post(&mut self, route: &str, action: R) {
+    pub fn post(&mut self, route: &str, action: impl RouteAction<E>) {
         self.route(HttpMethod::Post, route, action);
     }
 
-    pub fn put(&mut self, route: &str, action: R) {
+    pub fn put(&mut self, route: &str, action: impl RouteAction<E>) {
         self.route(HttpMethod::Put, route, action);
     }
 
-    pub fn patch(&mut self, route: &str, action: R) {
+    pub fn patch(&mut self, route: &str, action: impl RouteAction<E>) {
         self.route(HttpMethod::Patch, route, action);
     }
 
-    pub fn delete(&mut self, route: &str, action: R) {
+    pub fn delete(&mut self, route: &str, action: impl RouteAction<E>) {
         self.route(HttpMethod::Delete, route, action);
     }
 
+    pub fn head(&mut self, route: &str, action: impl RouteAction<E>) {
+        self.route(HttpMethod::Head, route, action);
+    }
+
+    pub fn options(&mut self, route: &str, action: impl RouteAction<E>) {
+        self.route(HttpMethod::Options, route, action);
+    }
+
+    pub fn trace(&mut self, route: &str, action: impl RouteAction<E>) {
+        self.route(HttpMethod::Trace, route, action);
+    }
+
+    pub fn connect(&mut self, route: &str, action: impl RouteAction<E>) {
+        self.route(HttpMethod::Connect, route, action);
+    }
+
+    pub fn middleware(&mut self, action: impl MiddlewareAction<E>) {
+        self.middleware_at("/", action);
+    }
+
+    pub fn middleware_at(&mut self, prefix: &str, action: impl MiddlewareAction<E>) {
+        self.add_middleware(Middleware::new(prefix, action));
+    }
+
+    pub fn add_middleware(&mut self, middleware: Middleware<E>) {
+        self.panic_if_active();
+        middleware::insert_ordered(&mut self.middleware.write().expect(EDIT_AFTER_INIT_MESSAGE), middleware);
+    }
+
+    pub fn middleware_chain(&self) -> Vec<MiddlewareInfo> {
+        self.middleware.read().unwrap().iter().map(Middleware::info).collect()
+    }
+
+    pub fn cors(&mut self, cors: Cors) {
+        self.add_middleware(Middleware::new("/", cors.middleware()).named("cors"));
+    }
+
+    pub fn rate_limit(&mut self, prefix: &str, limiter: RateLimiter) {
+        self.add_middleware(Middleware::new(prefix, limiter.middleware()).named("rate-limit"));
+    }
+
+    pub fn static_files(&mut self, prefix: &str, directory: impl AsRef<Path>) {
+        let files = StaticFiles::new(prefix, directory);
+        let check = files.clone();
+        self.add_check(&format!("static files at {}", files.prefix()), move || check.check());
+        self.add_middleware(Middleware::new(prefix, files.middleware()).named("static-files"));
+    }
+
+    pub fn workers(&mut self, workers: usize) {
+        self.edit_config().workers = Some(workers);
+    }
+
+    pub fn cookie_parser(&mut self, parser: CookieParser) {
+        self.edit_config().cookie_parser = parser;
+    }
+
+    pub fn method_override(&mut self, enabled: bool) {
+        self.edit_config().method_override = enabled;
+    }
+
+    pub fn max_body_size(&mut self, bytes: usize) {
+        let mut config = self.edit_config();
+        config.body_limits = std::mem::take(&mut config.body_limits).limit(bytes);
+    }
+
+    pub fn max_body_size_at(&mut self, prefix: &str, bytes: usize) {
+        let mut config = self.edit_config();
+        config.body_limits = std::mem::take(&mut config.body_limits).scope(prefix, bytes);
+    }
+
+    pub fn max_decompressed_size(&mut self, bytes: usize) {
+        self.edit_config().max_decompressed_size = Some(bytes);
+    }
+
+    pub fn on_response_sent(&mut self, listener: impl Fn(&ResponseEvent<'_>) + Sync + Send + 'static) {
+        self.edit_config().response_hooks.add(listener);
+    }
+
+    pub fn access_log(&mut self, log: AccessLog) {
+        let log = Arc::new(log);
+        self.on_response_sent(move |event| log.record(event));
+    }
+
+    pub fn priority(&mut self, prefix: &str, priority: Priority) {
+        let mut config = self.edit_config();
+        config.priorities = std::mem::take(&mut config.priorities).scope(prefix, priority);
+    }
+
+    pub fn shed_low_priority(&mut self, queue_depth: usize) {
+        let mut config = self.edit_config();
+        config.priorities = std::mem::take(&mut config.priorities).shed_low(queue_depth);
+    }
+
+    pub fn log_level(&mut self, level: LevelFilter) {
+        self.edit_config().log_level = Some(level);
+    }
+
+    pub fn quiet(&mut self) {
+        self.log_level(LevelFilter::Off);
+    }
+
+    pub fn thread_name(&mut self, prefix: &str) {
+        self.edit_config().thread_name = Some(prefix.to_string());
+    }
+
+    pub fn panic_hook(&mut self) {
+        self.edit_config().panic_hook = true;
+    }
+
+    pub fn connection_limit(&mut self, limit: ConnectionLimit) -> Arc<ConnectionLimit> {
+        let limit = Arc::new(limit);
+        self.edit_config().connection_limit = Some(limit.clone());
+        limit
+    }
+
+    pub fn prefork(&mut self, processes: usize) {
+        self.edit_config().prefork = Some(processes.max(1));
+    }
+
+    pub fn header_read_timeout(&mut self, timeout: Duration) {
+        self.edit_config().header_timeout = Some(timeout);
+    }
+
+    pub fn body_read_timeout(&mut self, timeout: Duration) {
+        self.edit_config().body_timeout = Some(timeout);
+    }
+
+    pub fn handler_timeout(&mut self, timeout: Duration) {
+        self.edit_config().handler_timeout = Some(timeout);
+    }
+
+    pub fn soft_restart(&mut self, restart: SoftRestart) -> Arc<SoftRestart> {
+        let restart = Arc::new(restart);
+        self.edit_config().restart = Some(restart.clone());
+        restart
+    }
+
+    pub fn error_budget(&mut self, errors: usize) {
+        self.edit_config().error_budget = Some(errors.max(1));
+    }
+
+    pub fn compat(&mut self, compat: Compat) {
+        self.edit_config().compat = compat;
+    }
+
+    pub fn state<T: Send + Sync + 'static>(&mut self, value: T) {
+        Arc::make_mut(&mut self.edit_config().state).insert(value);
+    }
+
+    pub fn dot_segments(&mut self, policy: DotSegments) {
+        self.edit_config().dot_segments = policy;
+    }
+
+    pub fn allow_missing_length(&mut self, allowed: bool) {
+        self.edit_config().allow_missing_length = allowed;
+    }
+
+    pub fn drain_limit(&mut self, bytes: usize) {
+        self.edit_config().drain_limit = Some(bytes);
+    }
+
+    pub fn keep_alive_timeout(&mut self, timeout: Duration) {
+        self.edit_config().keep_alive_timeout = Some(timeout);
+    }
+
+    pub fn memory_limit(&mut self, bytes: usize) {
+        self.edit_config().memory_limit = Some(bytes);
+    }
+
+    pub fn batch(&mut self, batch: Batch) {
+        self.edit_config().batch = Some(Arc::new(batch));
+    }
+
+    pub fn digests(&mut self, enabled: bool) {
+        self.edit_config().digests = enabled;
+    }
+
+    pub fn i18n(&mut self, i18n: I18n) {
+        self.edit_config().i18n = Some(Arc::new(i18n));
+    }
+
+    pub fn connection_bandwidth(&mut self, bytes_per_second: u64) {
+        self.edit_config().connection_bandwidth = Some(bytes_per_second);
+    }
+
+    pub fn global_bandwidth(&mut self, bytes_per_second: u64) {
+        self.edit_config().global_bandwidth = Some(Arc::new(Mutex::new(TokenBucket::new(bytes_per_second))));
+    }
+
+    pub fn stats(&self) -> Arc<ServerStats> {
+        self.stats.clone()
+    }
+
+    pub fn transfer_stats(&mut self) -> Arc<TransferStats> {
+        self.edit_config().transfer_stats.get_or_insert_with(|| Arc::new(TransferStats::new())).clone()
+    }
+
+    pub fn sampling(&mut self, sampler: RequestSampler) -> Arc<RequestSampler> {
+        let sampler = Arc::new(sampler);
+        self.edit_config().sampler = Some(sampler.clone());
+        sampler
+    }
+
+    pub fn error_log(&mut self, errors: ErrorLog) -> Arc<ErrorLog> {
+        let errors = Arc::new(errors);
+        self.edit_config().error_log = Some(errors.clone());
+        errors
+    }
+
+    pub fn sniff_mime(&mut self, enabled: bool) {
+        self.edit_config().sniff_mime = enabled;
+    }
+
+    pub fn preload(&mut self, manifest: PreloadManifest) {
+        self.edit_config().preload = Some(Arc::new(manifest));
+    }
+
+    pub fn error_pages(&mut self, pages: ErrorPages) {
+        let pages = Arc::new(pages);
+        let check = pages.clone();
+        self.add_check("error pages", move || check.check());
+        self.edit_config().error_pages = Some(pages);
+    }
+
+    pub fn server_name(&mut self, name: &str) {
+        self.edit_config().server_name = Some(name.to_string());
+    }
+
+    pub fn cache_control(&mut self, prefix: &str, policy: CachePolicy) {
+        let mut config = self.edit_config();
+        config.cache_policies = std::mem::take(&mut config.cache_policies).scope(prefix, policy);
+    }
+
+    pub fn header_policy(&mut self, policy: HeaderPolicy) {
+        self.edit_config().header_policy = Some(Arc::new(policy));
+    }
+
+    pub fn disable_conditional_at(&mut self, prefix: &str) {
+        let mut config = self.edit_config();
+        config.conditional = std::mem::take(&mut config.conditional).exempt(prefix);
+    }
+
+    pub fn preflight(&mut self) {
+        let mut config = self.edit_config();
+        config.preflight = std::mem::take(&mut config.preflight).enabled(true);
+    }
+
+    pub fn self_check(&mut self, name: &str, check: impl Fn() -> Result<(), String> + Sync + Send + 'static) {
+        self.add_check(name, check);
+        self.preflight();
+    }
+
+    fn add_check(&mut self, name: &str, check: impl Fn() -> Result<(), String> + Sync + Send + 'static) {
+        let mut config = self.edit_config();
+        config.preflight = std::mem::take(&mut config.preflight).check(name, check);
+    }
+
     pub fn panic_if_active(&self) {
         if self.active {
             panic!("{}", EDIT_AFTER_INIT_MESSAGE);
         }
     }
 
-    pub fn edit_router(&mut self) -> RwLockWriteGuard<Router<E, R>> {
+    pub fn edit_router(&mut self) -> RwLockWriteGuard<'_, Router<E>> {
         self.panic_if_active();
         self.router.write().expect(EDIT_AFTER_INIT_MESSAGE)
     }
-}
\ No newline at end of file
+
+    pub fn edit_config(&mut self) -> RwLockWriteGuard<'_, ServerConfig> {
+        self.panic_if_active();
+        self.config.write().expect(EDIT_AFTER_INIT_MESSAGE)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Read;
+    use std::net::TcpStream;
+    use crate::error::DefaultError;
+    use crate::parser::DEFAULT_BODY_LIMIT;
+    use super::*;
+
+    fn start(configure: impl FnOnce(&mut HttpServer<DefaultError, fn(&Request, DefaultError) -> Response>)) -> SocketAddr {
+        let mut server = HttpServer::default();
+        server.quiet();
+        server.post("/echo", |request: &Request| Ok(Response::text(String::from_utf8_lossy(request.raw()), 200)));
+        server.get("/hello", |_: &Request| Ok(Response::text("hello", 200)));
+        configure(&mut server);
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap();
+        thread::spawn(move || server.listen_with([listener]));
+        address
+    }
+
+    fn exchange(address: SocketAddr, request: &[u8]) -> String {
+        let mut stream = TcpStream::connect(address).unwrap();
+        stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        stream.write_all(request).unwrap();
+
+        let mut response = Vec::new();
+        stream.read_to_end(&mut response).unwrap();
+        String::from_utf8_lossy(&response).into_owned()
+    }
+
+    #[test]
+    fn rejects_oversized_bodies_by_default_and_closes() {
+        let address = start(|_| ());
+        let request = format!("POST /echo HTTP/1.1\r\nHost: localhost\r\nContent-Length: {}\r\n\r\npartial", DEFAULT_BODY_LIMIT + DRAIN_LIMIT + 1);
+        let response = exchange(address, request.as_bytes());
+
+        assert!(response.starts_with("HTTP/1.1 413 "), "{response}");
+        assert!(response.contains("Connection: close\r\n"));
+    }
+
+    #[test]
+    fn sheds_requests_over_the_memory_limit_before_reading_the_body() {
+        let address = start(|server| server.memory_limit(64 * 1024));
+        let response = exchange(address, b"POST /echo HTTP/1.1\r\nHost: localhost\r\nContent-Length: 1048576\r\n\r\n");
+
+        assert!(response.starts_with("HTTP/1.1 503 "), "{response}");
+        assert!(response.contains("Retry-After: 1\r\n"));
+        assert!(response.contains("Connection: close\r\n"));
+    }
+
+    #[test]
+    fn requires_a_length_for_unframed_bodies() {
+        let address = start(|_| ());
+        let response = exchange(address, b"POST /echo HTTP/1.1\r\nHost: localhost\r\n\r\nbody");
+
+        assert!(response.starts_with("HTTP/1.1 411 "), "{response}");
+        assert!(response.contains("Connection: close\r\n"));
+
+        for request in [
+            &b"POST /echo HTTP/1.1\r\nHost: localhost\r\nContent-Length: 0\r\nConnection: close\r\n\r\n"[..],
+            b"POST /echo HTTP/1.1\r\nHost: localhost\r\nTransfer-Encoding: chunked\r\nConnection: close\r\n\r\n4\r\nbody\r\n0\r\n\r\n",
+            b"GET /hello HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n"
+        ] {
+            let response = exchange(address, request);
+            assert!(response.starts_with("HTTP/1.1 200 "), "{response}");
+        }
+
+        let address = start(|server| server.allow_missing_length(true));
+        let response = exchange(address, b"POST /echo HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n");
+        assert!(response.starts_with("HTTP/1.1 200 "), "{response}");
+    }
+
+    #[test]
+    fn closes_after_exhausting_the_error_budget() {
+        let malformed = "GET /hello HTTP/1.1\r\nX-Missing: host\r\n\r\n";
+
+        let address = start(|_| ());
+        let response = exchange(address, format!("{malformed}GET /hello HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n").as_bytes());
+        let (rejected, served) = response.split_once("HTTP/1.1 200 ").expect(&response);
+
+        assert!(rejected.starts_with("HTTP/1.1 400 ") && rejected.contains("Connection: keep-alive\r\n"), "{response}");
+        assert!(served.ends_with("hello"));
+
+        let address = start(|server| server.error_budget(2));
+        let response = exchange(address, malformed.repeat(3).as_bytes());
+        let responses: Vec<&str> = response.split("HTTP/1.1 ").skip(1).collect();
+
+        assert_eq!(responses.len(), 2, "{response}");
+        assert!(responses[0].starts_with("400 ") && responses[0].contains("Connection: keep-alive\r\n"));
+        assert!(responses[1].starts_with("400 ") && responses[1].contains("Connection: close\r\n"));
+    }
+
+    fn trickle(address: SocketAddr, head: &[u8], trickled: &[u8]) -> String {
+        let mut stream = TcpStream::connect(address).unwrap();
+        stream.set_read_timeout(Some(Duration::from_millis(150))).unwrap();
+        stream.write_all(head).unwrap();
+
+        let mut response = Vec::new();
+
+        for byte in trickled {
+            if stream.write_all(&[*byte]).is_err() {
+                break;
+            }
+
+            let mut buffer = [0_u8; 256];
+
+            if let Ok(size) = stream.read(&mut buffer) {
+                response.extend_from_slice(&buffer[..size]);
+                break;
+            }
+        }
+
+        stream.set_read_timeout(Some(Duration::from_secs(5))).unwrap();
+        stream.read_to_end(&mut response).ok();
+        String::from_utf8_lossy(&response).into_owned()
+    }
+
+    #[test]
+    fn times_out_slow_request_heads() {
+        let address = start(|server| server.header_read_timeout(Duration::from_millis(300)));
+        let response = trickle(address, b"GET /hello HTTP/1.1\r\n", b"Host: localhost\r\nX-Slow: aaaaaaaaaaaaaaaaaaaaaaaaaaaaaaaa\r\n\r\n");
+
+        assert!(response.starts_with("HTTP/1.1 408 "), "{response}");
+        assert!(response.contains("Connection: close\r\n"));
+    }
+
+    #[test]
+    fn times_out_slow_request_bodies() {
+        let address = start(|server| server.body_read_timeout(Duration::from_millis(300)));
+        let response = trickle(address, b"POST /echo HTTP/1.1\r\nHost: localhost\r\nContent-Length: 32\r\n\r\n", &[b'a'; 32]);
+
+        assert!(response.starts_with("HTTP/1.1 408 "), "{response}");
+        assert!(response.contains("Connection: close\r\n"));
+    }
+
+    #[test]
+    fn runs_timed_handlers_on_named_workers_with_the_request_id() {
+        let seen = Arc::new(Mutex::new(None));
+        let recorded = seen.clone();
+
+        let address = start(move |server| {
+            server.handler_timeout(Duration::from_millis(300));
+            server.get("/slow", move |request: &Request| {
+                *recorded.lock().unwrap() = Some((thread::current().name().map(str::to_string), panic_hook::active_request().map(|active| active.target)));
+
+                if request.query("sleep").is_some() {
+                    thread::sleep(Duration::from_secs(1));
+                }
+
+                Ok(Response::text("done", 200))
+            });
+        });
+
+        let response = exchange(address, b"GET /slow HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n");
+        assert!(response.starts_with("HTTP/1.1 200 "), "{response}");
+
+        let (name, target) = seen.lock().unwrap().take().unwrap();
+        assert!(name.is_some_and(|name| name.starts_with("http-handler-worker-")));
+        assert_eq!(target.as_deref(), Some("/slow"));
+
+        let response = exchange(address, b"GET /slow?sleep=1 HTTP/1.1\r\nHost: localhost\r\n\r\n");
+        assert!(response.starts_with("HTTP/1.1 503 "), "{response}");
+    }
+
+    #[test]
+    fn rejects_unsupported_content_encodings() {
+        let address = start(|_| ());
+        let response = exchange(address, b"POST /echo HTTP/1.1\r\nHost: localhost\r\nContent-Encoding: br\r\nContent-Length: 4\r\nConnection: close\r\n\r\ndata");
+
+        assert!(response.starts_with("HTTP/1.1 415 "), "{response}");
+        assert!(response.contains("Accept-Encoding: gzip\r\n"));
+    }
+}
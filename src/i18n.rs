@@ -0,0 +1,156 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+use crate::message::Request;
+
+pub const LOCALE_COOKIE: &str = "lang";
+
+#[derive(Debug, Clone)]
+pub struct I18n {
+    default_locale: String,
+    cookie: String,
+    catalogs: HashMap<String, HashMap<String, String>>
+}
+
+impl I18n {
+    pub fn new(default_locale: &str) -> Self {
+        Self {
+            default_locale: default_locale.to_string(),
+            cookie: LOCALE_COOKIE.to_string(),
+            catalogs: HashMap::new()
+        }
+    }
+
+    pub fn cookie(mut self, cookie: &str) -> Self {
+        self.cookie = cookie.to_string();
+        self
+    }
+
+    pub fn add(&mut self, locale: &str, key: &str, message: &str) {
+        self.catalogs.entry(locale.to_string()).or_default().insert(key.to_string(), message.to_string());
+    }
+
+    pub fn load_fluent(&mut self, locale: &str, path: impl AsRef<Path>) -> io::Result<()> {
+        let source = fs::read_to_string(path)?;
+        let mut current: Option<(String, String)> = None;
+
+        for line in source.lines() {
+            if line.trim_start().starts_with('#') || line.trim().is_empty() {
+                continue;
+            }
+
+            if line.starts_with(char::is_whitespace) {
+                if let Some((_, message)) = current.as_mut() {
+                    message.push('\n');
+                    message.push_str(line.trim());
+                }
+
+                continue;
+            }
+
+            if let Some((key, message)) = current.take() {
+                self.add(locale, &key, &message);
+            }
+
+            if let Some((key, message)) = line.split_once('=') {
+                current = Some((key.trim().to_string(), message.trim().to_string()));
+            }
+        }
+
+        if let Some((key, message)) = current {
+            self.add(locale, &key, &message);
+        }
+
+        Ok(())
+    }
+
+    pub fn load_gettext(&mut self, locale: &str, path: impl AsRef<Path>) -> io::Result<()> {
+        let source = fs::read_to_string(path)?;
+        let mut msgid: Option<String> = None;
+        let mut msgstr: Option<String> = None;
+
+        for line in source.lines().map(str::trim) {
+            if let Some(rest) = line.strip_prefix("msgid ") {
+                if let (Some(key), Some(message)) = (msgid.take(), msgstr.take()) {
+                    self.add(locale, &key, &message);
+                }
+
+                msgid = Some(unquote(rest));
+            } else if let Some(rest) = line.strip_prefix("msgstr ") {
+                msgstr = Some(unquote(rest));
+            } else if line.starts_with('"') {
+                if let Some(current) = msgstr.as_mut().or(msgid.as_mut()) {
+                    current.push_str(&unquote(line));
+                }
+            }
+        }
+
+        if let (Some(key), Some(message)) = (msgid, msgstr) {
+            self.add(locale, &key, &message);
+        }
+
+        Ok(())
+    }
+
+    pub fn negotiate(&self, request: &Request) -> String {
+        if let Some(locale) = request.cookie(&self.cookie).and_then(|locale| self.supported(locale)) {
+            return locale;
+        }
+
+        let mut accepted: Vec<(&str, f32)> = request.header("accept-language")
+            .unwrap_or("")
+            .split(',')
+            .filter_map(|entry| {
+                let mut parts = entry.trim().split(';');
+                let locale = parts.next()?.trim();
+                let quality = parts.find_map(|param| param.trim().strip_prefix("q=")?.parse().ok()).unwrap_or(1.0);
+                (!locale.is_empty()).then_some((locale, quality))
+            })
+            .collect();
+
+        accepted.sort_by(|a, b| b.1.total_cmp(&a.1));
+
+        accepted.into_iter()
+            .find_map(|(locale, _)| self.supported(locale))
+            .unwrap_or_else(|| self.default_locale.clone())
+    }
+
+    pub fn translate(&self, locale: &str, key: &str, args: &[(&str, &str)]) -> String {
+        let message = [locale, locale.split('-').next().unwrap_or(locale), &self.default_locale]
+            .iter()
+            .find_map(|locale| self.catalogs.get(*locale)?.get(key));
+
+        let mut message = match message {
+            Some(message) => message.clone(),
+            None => return key.to_string()
+        };
+
+        for (name, value) in args {
+            message = message
+                .replace(&format!("{{ ${name} }}"), value)
+                .replace(&format!("{{${name}}}"), value)
+                .replace(&format!("{{{name}}}"), value);
+        }
+
+        message
+    }
+
+    fn supported(&self, locale: &str) -> Option<String> {
+        let locale = locale.trim();
+
+        if self.catalogs.contains_key(locale) {
+            return Some(locale.to_string());
+        }
+
+        let primary = locale.split('-').next()?;
+        self.catalogs.contains_key(primary).then(|| primary.to_string())
+    }
+}
+
+fn unquote(value: &str) -> String {
+    value.trim()
+        .trim_matches('"')
+        .replace("\\n", "\n")
+        .replace("\\\"", "\"")
+}
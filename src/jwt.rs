@@ -0,0 +1,381 @@
+use std::error::Error;
+use std::fmt::{self, Debug, Display, Formatter};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use hmac::{Hmac, Mac};
+use rsa::{RsaPrivateKey, RsaPublicKey};
+use rsa::pkcs1::{DecodeRsaPrivateKey, DecodeRsaPublicKey};
+use rsa::pkcs1v15::{Signature, SigningKey, VerifyingKey};
+use rsa::pkcs8::{DecodePrivateKey, DecodePublicKey};
+use rsa::signature::{SignatureEncoding, Signer, Verifier};
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::{json, Map, Value};
+use sha2::Sha256;
+use crate::auth::{self, AuthError};
+use crate::error::ServerError;
+use crate::keyring::{self, Keyring, KeyringError};
+use crate::message::{Request, Response};
+use crate::middleware::Next;
+
+const DEFAULT_TTL: Duration = Duration::from_secs(3600);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Algorithm {
+    HS256,
+    RS256
+}
+
+impl Algorithm {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::HS256 => "HS256",
+            Self::RS256 => "RS256"
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum JwtError {
+    Malformed,
+    Algorithm(String),
+    Signature,
+    Expired,
+    MissingExpiry,
+    NotYetValid,
+    Issuer,
+    Audience,
+    Key(String),
+    SigningKey,
+    Claims(String)
+}
+
+impl Display for JwtError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Malformed => write!(f, "The token is not a well formed JWT"),
+            Self::Algorithm(alg) => write!(f, "The token algorithm \"{}\" is not accepted", alg),
+            Self::Signature => write!(f, "The token signature is invalid"),
+            Self::Expired => write!(f, "The token has expired"),
+            Self::MissingExpiry => write!(f, "The token has no expiration time"),
+            Self::NotYetValid => write!(f, "The token is not valid yet"),
+            Self::Issuer => write!(f, "The token was issued by an untrusted issuer"),
+            Self::Audience => write!(f, "The token is not intended for this audience"),
+            Self::Key(reason) => write!(f, "Invalid RSA key: {}", reason),
+            Self::SigningKey => write!(f, "No private key is configured to sign tokens"),
+            Self::Claims(reason) => write!(f, "Invalid token claims: {}", reason)
+        }
+    }
+}
+
+impl Error for JwtError {}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Claims(Map<String, Value>);
+
+impl Claims {
+    pub fn get(&self, name: &str) -> Option<&Value> {
+        self.0.get(name)
+    }
+
+    pub fn subject(&self) -> Option<&str> {
+        self.get("sub").and_then(Value::as_str)
+    }
+
+    pub fn expires_at(&self) -> Option<u64> {
+        self.get("exp").and_then(Value::as_u64)
+    }
+
+    pub fn deserialize<T: DeserializeOwned>(&self) -> Result<T, JwtError> {
+        serde_json::from_value(Value::Object(self.0.clone())).map_err(|err| JwtError::Claims(err.to_string()))
+    }
+
+    pub fn into_map(self) -> Map<String, Value> {
+        self.0
+    }
+}
+
+#[derive(Clone)]
+enum Key {
+    Hmac(Vec<Vec<u8>>),
+    Rsa(Box<VerifyingKey<Sha256>>, Option<Box<SigningKey<Sha256>>>)
+}
+
+#[derive(Clone)]
+pub struct Jwt {
+    key: Key,
+    issuer: Option<String>,
+    audience: Option<String>,
+    leeway: Duration,
+    ttl: Duration,
+    require_exp: bool,
+    realm: String
+}
+
+impl Jwt {
+    pub fn hs256(secret: impl AsRef<[u8]>) -> Self {
+        Self::with_key(Key::Hmac(vec![secret.as_ref().to_vec()]))
+    }
+
+    pub fn from_keyring(keyring: &Keyring) -> Result<Self, KeyringError> {
+        let keys: Vec<Vec<u8>> = keyring.derive(keyring::JWT).secrets().map(|secret| secret.to_vec()).collect();
+
+        match keys.is_empty() {
+            true => Err(KeyringError::Empty),
+            false => Ok(Self::with_key(Key::Hmac(keys)))
+        }
+    }
+
+    pub fn rs256_public_pem(pem: &str) -> Result<Self, JwtError> {
+        let public = RsaPublicKey::from_public_key_pem(pem)
+            .or_else(|_| RsaPublicKey::from_pkcs1_pem(pem))
+            .map_err(|err| JwtError::Key(err.to_string()))?;
+
+        Ok(Self::with_key(Key::Rsa(Box::new(VerifyingKey::new(public)), None)))
+    }
+
+    pub fn rs256_private_pem(pem: &str) -> Result<Self, JwtError> {
+        let private = RsaPrivateKey::from_pkcs8_pem(pem)
+            .or_else(|_| RsaPrivateKey::from_pkcs1_pem(pem))
+            .map_err(|err| JwtError::Key(err.to_string()))?;
+
+        let verifying = Box::new(VerifyingKey::new(private.to_public_key()));
+        Ok(Self::with_key(Key::Rsa(verifying, Some(Box::new(SigningKey::new(private))))))
+    }
+
+    fn with_key(key: Key) -> Self {
+        Self {
+            key,
+            issuer: None,
+            audience: None,
+            leeway: Duration::from_secs(30),
+            ttl: DEFAULT_TTL,
+            require_exp: true,
+            realm: "api".to_string()
+        }
+    }
+
+    pub fn issuer(mut self, issuer: &str) -> Self {
+        self.issuer = Some(issuer.to_string());
+        self
+    }
+
+    pub fn audience(mut self, audience: &str) -> Self {
+        self.audience = Some(audience.to_string());
+        self
+    }
+
+    pub fn leeway(mut self, leeway: Duration) -> Self {
+        self.leeway = leeway;
+        self
+    }
+
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    pub fn require_exp(mut self, required: bool) -> Self {
+        self.require_exp = required;
+        self
+    }
+
+    pub fn realm(mut self, realm: &str) -> Self {
+        self.realm = realm.to_string();
+        self
+    }
+
+    pub fn algorithm(&self) -> Algorithm {
+        match self.key {
+            Key::Hmac(_) => Algorithm::HS256,
+            Key::Rsa(..) => Algorithm::RS256
+        }
+    }
+
+    pub fn issue(&self, claims: &impl Serialize) -> Result<String, JwtError> {
+        let Value::Object(mut claims) = serde_json::to_value(claims).map_err(|err| JwtError::Claims(err.to_string()))? else {
+            return Err(JwtError::Claims("claims must serialize to a JSON object".to_string()));
+        };
+
+        let now = now();
+        claims.entry("iat").or_insert(json!(now));
+        claims.entry("exp").or_insert(json!(now + self.ttl.as_secs()));
+
+        if let Some(issuer) = &self.issuer {
+            claims.entry("iss").or_insert(json!(issuer));
+        }
+
+        if let Some(audience) = &self.audience {
+            claims.entry("aud").or_insert(json!(audience));
+        }
+
+        let header = json!({ "alg": self.algorithm().as_str(), "typ": "JWT" });
+        let signing_input = format!("{}.{}", URL_SAFE_NO_PAD.encode(header.to_string()), URL_SAFE_NO_PAD.encode(Value::Object(claims).to_string()));
+        let signature = self.sign(signing_input.as_bytes())?;
+
+        Ok(format!("{signing_input}.{}", URL_SAFE_NO_PAD.encode(signature)))
+    }
+
+    pub fn issue_for(&self, subject: &str) -> Result<String, JwtError> {
+        self.issue(&json!({ "sub": subject }))
+    }
+
+    pub fn validate(&self, token: &str) -> Result<Claims, JwtError> {
+        let mut parts = token.split('.');
+
+        let (Some(header), Some(payload), Some(signature), None) = (parts.next(), parts.next(), parts.next(), parts.next()) else {
+            return Err(JwtError::Malformed);
+        };
+
+        let header: Value = decode(header)?;
+        let alg = header.get("alg").and_then(Value::as_str).ok_or(JwtError::Malformed)?;
+
+        if alg != self.algorithm().as_str() {
+            return Err(JwtError::Algorithm(alg.to_string()));
+        }
+
+        let signature = URL_SAFE_NO_PAD.decode(signature).map_err(|_| JwtError::Malformed)?;
+        self.verify(signing_input(token).as_bytes(), &signature)?;
+
+        let Value::Object(claims) = decode(payload)? else {
+            return Err(JwtError::Malformed);
+        };
+
+        self.check(&claims)?;
+        Ok(Claims(claims))
+    }
+
+    pub fn middleware<E: ServerError>(self) -> impl Fn(&mut Request, Next<'_, E>) -> Result<Response, E> + Sync + Send + 'static {
+        move |request, next| {
+            let claims = auth::credentials(request, "Bearer").and_then(|token| self.validate(token).map_err(|_| AuthError::Invalid));
+
+            match claims {
+                Ok(claims) => {
+                    request.insert_ext(claims);
+                    next.run(request)
+                },
+                Err(err) => Ok(auth::bearer_challenge(&self.realm, err))
+            }
+        }
+    }
+
+    fn sign(&self, message: &[u8]) -> Result<Vec<u8>, JwtError> {
+        match &self.key {
+            Key::Hmac(keys) => Ok(hmac(&keys[0], message)),
+            Key::Rsa(_, Some(signing)) => Ok(signing.sign(message).to_vec()),
+            Key::Rsa(_, None) => Err(JwtError::SigningKey)
+        }
+    }
+
+    fn verify(&self, message: &[u8], signature: &[u8]) -> Result<(), JwtError> {
+        let valid = match &self.key {
+            Key::Hmac(keys) => keys.iter().any(|key| {
+                let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+                mac.update(message);
+                mac.verify_slice(signature).is_ok()
+            }),
+            Key::Rsa(verifying, _) => Signature::try_from(signature).is_ok_and(|signature| verifying.verify(message, &signature).is_ok())
+        };
+
+        match valid {
+            true => Ok(()),
+            false => Err(JwtError::Signature)
+        }
+    }
+
+    fn check(&self, claims: &Map<String, Value>) -> Result<(), JwtError> {
+        let now = now();
+        let leeway = self.leeway.as_secs();
+        let timestamp = |name: &str| claims.get(name).map(|value| value.as_u64().ok_or(JwtError::Malformed)).transpose();
+
+        match timestamp("exp")? {
+            Some(exp) if now >= exp.saturating_add(leeway) => return Err(JwtError::Expired),
+            None if self.require_exp => return Err(JwtError::MissingExpiry),
+            _ => ()
+        }
+
+        if timestamp("nbf")?.is_some_and(|nbf| now.saturating_add(leeway) < nbf) {
+            return Err(JwtError::NotYetValid);
+        }
+
+        if let Some(issuer) = &self.issuer {
+            if claims.get("iss").and_then(Value::as_str) != Some(issuer) {
+                return Err(JwtError::Issuer);
+            }
+        }
+
+        if let Some(audience) = &self.audience {
+            let accepted = match claims.get("aud") {
+                Some(Value::String(aud)) => aud == audience,
+                Some(Value::Array(auds)) => auds.iter().any(|aud| aud.as_str() == Some(audience)),
+                _ => false
+            };
+
+            if !accepted {
+                return Err(JwtError::Audience);
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Debug for Jwt {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Jwt")
+            .field("algorithm", &self.algorithm())
+            .field("issuer", &self.issuer)
+            .field("audience", &self.audience)
+            .finish()
+    }
+}
+
+fn decode<T: DeserializeOwned>(part: &str) -> Result<T, JwtError> {
+    let bytes = URL_SAFE_NO_PAD.decode(part).map_err(|_| JwtError::Malformed)?;
+    serde_json::from_slice(&bytes).map_err(|_| JwtError::Malformed)
+}
+
+fn signing_input(token: &str) -> &str {
+    token.rsplit_once('.').map_or(token, |(input, _)| input)
+}
+
+fn hmac(key: &[u8], message: &[u8]) -> Vec<u8> {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+    mac.update(message);
+    mac.finalize().into_bytes().to_vec()
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn token(jwt: &Jwt, claims: Value) -> String {
+        let signing_input = format!("{}.{}", URL_SAFE_NO_PAD.encode(r#"{"alg":"HS256","typ":"JWT"}"#), URL_SAFE_NO_PAD.encode(claims.to_string()));
+        format!("{signing_input}.{}", URL_SAFE_NO_PAD.encode(jwt.sign(signing_input.as_bytes()).unwrap()))
+    }
+
+    #[test]
+    fn rejects_tokens_without_expiry_by_default() {
+        let jwt = Jwt::hs256("secret");
+        assert_eq!(jwt.validate(&token(&jwt, json!({ "sub": "alice" }))), Err(JwtError::MissingExpiry));
+    }
+
+    #[test]
+    fn accepts_tokens_without_expiry_when_allowed() {
+        let jwt = Jwt::hs256("secret").require_exp(false);
+        let claims = jwt.validate(&token(&jwt, json!({ "sub": "alice" }))).unwrap();
+        assert_eq!(claims.subject(), Some("alice"));
+    }
+
+    #[test]
+    fn checks_expiry_of_issued_tokens() {
+        let jwt = Jwt::hs256("secret").leeway(Duration::ZERO);
+        assert!(jwt.validate(&jwt.issue_for("alice").unwrap()).is_ok());
+        assert_eq!(jwt.validate(&token(&jwt, json!({ "sub": "alice", "exp": 1 }))), Err(JwtError::Expired));
+    }
+}
@@ -0,0 +1,127 @@
+use std::env;
+use std::error::Error;
+use std::fmt::{self, Display, Formatter};
+use std::fs;
+use std::path::Path;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+
+pub const COOKIES: &str = "cookies";
+pub const SIGNED_URLS: &str = "signed-urls";
+pub const JWT: &str = "jwt";
+
+#[derive(Debug)]
+pub enum KeyringError {
+    Missing(String),
+    Malformed(String),
+    Empty
+}
+
+impl Display for KeyringError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Missing(source) => write!(f, "Could not read keys from {}", source),
+            Self::Malformed(entry) => write!(f, "Malformed key entry \"{}\". Expected <version>:<base64 secret>", entry),
+            Self::Empty => write!(f, "The keyring does not contain any keys")
+        }
+    }
+}
+
+impl Error for KeyringError {}
+
+#[derive(Clone, Default)]
+pub struct Keyring {
+    keys: Vec<(u32, Vec<u8>)>
+}
+
+impl Keyring {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn parse(spec: &str) -> Result<Self, KeyringError> {
+        let entries = spec.split(|c: char| c == ',' || c.is_whitespace()).filter(|entry| !entry.is_empty());
+        let mut keyring = Self::new();
+
+        for entry in entries {
+            let malformed = || KeyringError::Malformed(entry.to_string());
+            let (version, secret) = entry.split_once(':').ok_or_else(malformed)?;
+            let version = version.parse::<u32>().map_err(|_| malformed())?;
+            let secret = STANDARD.decode(secret).map_err(|_| malformed())?;
+            keyring = keyring.key(version, secret);
+        }
+
+        match keyring.keys.is_empty() {
+            true => Err(KeyringError::Empty),
+            false => Ok(keyring)
+        }
+    }
+
+    pub fn from_env(variable: &str) -> Result<Self, KeyringError> {
+        let spec = env::var(variable).map_err(|_| KeyringError::Missing(format!("environment variable {variable}")))?;
+        Self::parse(&spec)
+    }
+
+    pub fn from_file(path: impl AsRef<Path>) -> Result<Self, KeyringError> {
+        let path = path.as_ref();
+        let spec = fs::read_to_string(path).map_err(|_| KeyringError::Missing(path.display().to_string()))?;
+        Self::parse(&spec)
+    }
+
+    pub fn key(mut self, version: u32, secret: impl AsRef<[u8]>) -> Self {
+        self.keys.retain(|(existing, _)| *existing != version);
+        self.keys.push((version, secret.as_ref().to_vec()));
+        self.keys.sort_by(|(a, _), (b, _)| b.cmp(a));
+        self
+    }
+
+    pub fn rotate(self, secret: impl AsRef<[u8]>) -> Self {
+        let version = self.current().map_or(1, |(version, _)| version + 1);
+        self.key(version, secret)
+    }
+
+    pub fn retire(mut self, version: u32) -> Self {
+        self.keys.retain(|(existing, _)| *existing != version);
+        self
+    }
+
+    pub fn current(&self) -> Option<(u32, &[u8])> {
+        self.keys.first().map(|(version, secret)| (*version, secret.as_slice()))
+    }
+
+    pub fn get(&self, version: u32) -> Option<&[u8]> {
+        self.keys.iter().find(|(existing, _)| *existing == version).map(|(_, secret)| secret.as_slice())
+    }
+
+    pub fn versions(&self) -> Vec<u32> {
+        self.keys.iter().map(|(version, _)| *version).collect()
+    }
+
+    pub fn secrets(&self) -> impl Iterator<Item = &[u8]> {
+        self.keys.iter().map(|(_, secret)| secret.as_slice())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    pub fn derive(&self, purpose: &str) -> Self {
+        let keys = self.keys.iter().map(|(version, secret)| {
+            let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts keys of any length");
+            mac.update(purpose.as_bytes());
+            (*version, mac.finalize().into_bytes().to_vec())
+        });
+
+        Self {
+            keys: keys.collect()
+        }
+    }
+}
+
+impl fmt::Debug for Keyring {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Keyring").field("versions", &self.versions()).finish()
+    }
+}
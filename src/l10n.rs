@@ -0,0 +1,96 @@
+use std::time::{SystemTime, UNIX_EPOCH};
+
+struct LocaleFormat {
+    group: &'static str,
+    decimal: &'static str,
+    date: DateOrder,
+    date_separator: &'static str
+}
+
+enum DateOrder {
+    DayMonthYear,
+    MonthDayYear,
+    YearMonthDay
+}
+
+fn locale_format(locale: &str) -> LocaleFormat {
+    let locale = locale.to_ascii_lowercase();
+    let language = locale.split(['-', '_']).next().unwrap_or("");
+
+    let (group, decimal) = match (locale.as_str(), language) {
+        ("de-ch", _) | (_, "rm") => ("'", "."),
+        (_, "de" | "es" | "it" | "pt" | "nl" | "id" | "tr" | "da") => (".", ","),
+        (_, "fr" | "ru" | "pl" | "sv" | "fi" | "nb" | "no" | "cs" | "uk") => ("\u{a0}", ","),
+        _ => (",", ".")
+    };
+
+    let (date, date_separator) = match (locale.as_str(), language) {
+        ("en-us" | "en", _) => (DateOrder::MonthDayYear, "/"),
+        (_, "ja" | "zh" | "ko") => (DateOrder::YearMonthDay, "/"),
+        (_, "sv" | "lt") => (DateOrder::YearMonthDay, "-"),
+        (_, "de" | "ru" | "pl" | "fi" | "nb" | "no" | "cs" | "uk" | "tr" | "da") => (DateOrder::DayMonthYear, "."),
+        (_, "nl") => (DateOrder::DayMonthYear, "-"),
+        (_, "") => (DateOrder::YearMonthDay, "-"),
+        _ => (DateOrder::DayMonthYear, "/")
+    };
+
+    LocaleFormat {
+        group,
+        decimal,
+        date,
+        date_separator
+    }
+}
+
+pub fn format_number(locale: &str, number: f64, decimals: usize) -> String {
+    let format = locale_format(locale);
+    let formatted = format!("{:.*}", decimals, number.abs());
+    let (integer, fraction) = formatted.split_once('.').unwrap_or((&formatted, ""));
+    let mut grouped = String::new();
+
+    for (i, digit) in integer.chars().enumerate() {
+        if i > 0 && (integer.len() - i) % 3 == 0 {
+            grouped.push_str(format.group);
+        }
+
+        grouped.push(digit);
+    }
+
+    if number.is_sign_negative() && number != 0.0 {
+        grouped.insert(0, '-');
+    }
+
+    if !fraction.is_empty() {
+        grouped.push_str(format.decimal);
+        grouped.push_str(fraction);
+    }
+
+    grouped
+}
+
+pub fn format_date(locale: &str, time: SystemTime) -> String {
+    let format = locale_format(locale);
+    let (year, month, day) = civil_date(time);
+    let sep = format.date_separator;
+
+    match format.date {
+        DateOrder::DayMonthYear => format!("{day:02}{sep}{month:02}{sep}{year}"),
+        DateOrder::MonthDayYear => format!("{month:02}{sep}{day:02}{sep}{year}"),
+        DateOrder::YearMonthDay => format!("{year}{sep}{month:02}{sep}{day:02}")
+    }
+}
+
+pub fn civil_date(time: SystemTime) -> (i64, u32, u32) {
+    let days = time.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs() as i64 / 86400;
+    let z = days + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = (doy - (153 * mp + 2) / 5 + 1) as u32;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 } as u32;
+    let year = yoe + era * 400 + i64::from(month <= 2);
+
+    (year, month, day)
+}
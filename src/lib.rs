@@ -0,0 +1,68 @@
+pub mod http_server;
+pub mod route;
+pub mod predicate;
+pub mod message;
+pub mod method;
+pub mod error;
+pub mod digest;
+pub mod signed_url;
+pub mod request_signing;
+pub mod api_key;
+#[cfg(feature = "oauth")]
+pub mod oauth;
+pub mod form;
+pub mod flash;
+pub mod i18n;
+pub mod l10n;
+pub mod throttle;
+pub mod transfer_stats;
+pub mod sampling;
+pub mod error_log;
+pub mod panic_hook;
+pub mod restart;
+pub mod prefork;
+pub mod connection_limit;
+pub mod usage;
+pub mod sniff;
+pub mod preload;
+pub mod error_pages;
+pub mod middleware;
+pub mod feature_flags;
+pub mod experiment;
+pub mod webhook;
+pub mod client;
+pub mod resolver;
+pub mod parser;
+pub mod stats;
+pub mod static_files;
+pub mod pool;
+pub mod priority;
+pub mod multipart;
+pub mod batch;
+pub mod cookie;
+pub mod cookie_jar;
+pub mod keyring;
+pub mod cors;
+pub mod uri;
+pub mod state;
+pub mod compat;
+pub mod extensions;
+pub mod headers;
+pub mod events;
+pub mod access_log;
+pub mod range;
+pub mod preflight;
+pub mod conditional;
+pub mod cache_control;
+pub mod header_policy;
+#[cfg(feature = "geoip")]
+pub mod geoip;
+pub mod bots;
+pub mod rate_limit;
+pub mod auth;
+#[cfg(feature = "jwt")]
+pub mod jwt;
+pub mod session;
+pub mod status;
+#[cfg(feature = "tokio")]
+pub mod async_server;
@@ -1,14 +1,6 @@
-mod http_server;
-mod route;
-mod message;
-mod method;
-mod error;
-
-use std::fmt::{Display, Formatter};
 use serde::{Deserialize, Serialize};
-use http_server::HttpServer;
-use crate::message::{Request, Response};
-use crate::route::NOT_FOUND_ACTION;
+use http_server::http_server::HttpServer;
+use http_server::message::{Request, Response};
 
 
 #[derive(Serialize, Deserialize)]
@@ -25,15 +17,15 @@ fn main() {
         Ok(Response::text(format!("yuor jason is {}", serde_json::to_string(&json).unwrap()), 200))
     });
 
-    server.get("/", |req: &Request| {
+    server.get("/", |_req: &Request| {
         Ok(Response::text("Welcome to index", 200))
     });
 
-    server.get("/hello", |req: &Request| {
+    server.get("/hello", |_req: &Request| {
         Ok(Response::file("hellsdfo.html", 200)?)
     });
 
-    server.get("/hel", |req: &Request| {
+    server.get("/hel", |_req: &Request| {
         Ok(Response::file("helfdflo.html", 200)?)
     });
 
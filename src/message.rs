@@ -1,18 +1,35 @@
-use std::borrow::Borrow;
 use std::collections::HashMap;
-use std::error::Error;
-use std::fmt::Display;
-use std::fs::File;
-use std::io::{self, BufReader, Read};
+use std::fmt::{self, Debug, Display, Formatter};
+use std::fs::{File, Metadata};
+use std::io::{self, BufRead, BufReader, ErrorKind, Read, Seek, SeekFrom, Write};
 use std::net::SocketAddr;
-use std::string::ParseError;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
 use std::time::SystemTime;
+use log::{debug, trace};
 use url::Url;
+use flate2::read::GzDecoder;
 use serde::{Deserialize, Serialize};
-use serde_json::to_string;
+use serde::de::DeserializeOwned;
+use sha2::{Digest, Sha256};
+use crate::conditional;
+use crate::cookie::{Cookie, CookieParser};
+use crate::digest;
+use crate::extensions::Extensions;
+use crate::headers::HeaderMap;
 use crate::http_server::BUFFER_SIZE;
-use crate::error::{InvalidMethodError, RequestParseError, ServerError};
-use crate::error::DefaultError::RequestParse;
+use crate::parser::MAX_HEAD_SIZE;
+use crate::error::RequestParseError;
+use crate::form::Form;
+use crate::i18n::I18n;
+use crate::l10n;
+use crate::multipart::{self, MultipartLimits, Part};
+use crate::range::{self, RangeRequest};
+use crate::session::Session;
+use crate::sniff::{self, SNIFF_SIZE};
+use crate::state::SharedState;
+use crate::status::StatusCode;
+use crate::uri;
 use crate::method::HttpMethod;
 
 #[derive(Debug)]
@@ -20,36 +37,68 @@ pub struct Request {
     socket_addr: SocketAddr,
     method: HttpMethod,
     route: String,
+    raw_route: String,
+    target: String,
     protocol: String,
     version: f32,
     host: String,
-    headers: HashMap<String, String>,
-    query: HashMap<String, String>,
+    headers: HeaderMap,
+    query: Vec<(String, String)>,
+    cookies: Vec<(String, String)>,
     body: Vec<u8>,
-    url: Url
+    url: Url,
+    params: HashMap<String, String>,
+    experiments: HashMap<String, String>,
+    locale: Option<String>,
+    i18n: Option<Arc<I18n>>,
+    session: Option<Session>,
+    state: Option<Arc<SharedState>>,
+    extensions: Extensions,
+    parsed: Mutex<Extensions>
 }
 
 impl Request {
-    pub fn new(socket_addr: SocketAddr, method: HttpMethod, url: Url, version: f32, headers: HashMap<String, String>, body: Vec<u8>) -> Self {
+    pub fn new(socket_addr: SocketAddr, method: HttpMethod, url: Url, version: f32, headers: impl Into<HeaderMap>, body: Vec<u8>) -> Self {
+        let headers = headers.into();
         let query = url.query_pairs()
             .map(|(key, value)| (key.to_string(), value.to_string()))
             .collect();
 
+        let cookies = Some(headers.get_all("cookie").join("; "))
+            .filter(|header| !header.is_empty())
+            .and_then(|header| CookieParser::lenient().parse(&header).ok())
+            .unwrap_or_default();
+
         Self {
             socket_addr,
             method,
-            route: url.path().to_string(),
+            route: uri::decode(url.path()),
+            raw_route: url.path().to_string(),
+            target: url[url::Position::BeforePath..].to_string(),
             protocol: url.scheme().to_string(),
             version,
-            host: url.host_str().unwrap().to_string(),
+            host: url.host_str().unwrap_or_default().to_string(),
             headers,
             query,
+            cookies,
             url,
-            body
+            body,
+            params: HashMap::new(),
+            experiments: HashMap::new(),
+            locale: None,
+            i18n: None,
+            session: None,
+            state: None,
+            extensions: Extensions::new(),
+            parsed: Mutex::new(Extensions::new())
         }
     }
 
     pub fn from_bytes(socket_addr: SocketAddr, bytes: &[u8]) -> Result<Self, RequestParseError> {
+        Self::from_frame(socket_addr, bytes, true, None)
+    }
+
+    pub(crate) fn from_frame(socket_addr: SocketAddr, bytes: &[u8], with_body: bool, fallback_host: Option<&str>) -> Result<Self, RequestParseError> {
         let head_len = bytes.windows(4).position(|window| matches!(window, b"\r\n\r\n")).unwrap_or(bytes.len());
         let data = std::str::from_utf8(&bytes[..head_len]).map_err(|_| RequestParseError::MalformedRequest)?;
         let mut lines = data.split("\r\n");
@@ -59,31 +108,74 @@ impl Request {
         let mut v = first.next().ok_or(RequestParseError::Protocol)?.split("/");
         let protocol = v.next().ok_or(RequestParseError::Protocol)?.to_ascii_lowercase();
         let version: f32 = v.next().ok_or(RequestParseError::Protocol)?.parse().map_err(|_| RequestParseError::Protocol)?;
-        let mut headers = HashMap::new();
+        let mut headers = HeaderMap::new();
+
+        for line in lines {
+            let (header, value) = line.split_once(':').ok_or_else(|| RequestParseError::Header(line.to_ascii_lowercase()))?;
+
+            if header.is_empty() || header.contains(char::is_whitespace) {
+                return Err(RequestParseError::Header(header.to_ascii_lowercase()));
+            }
 
-        while let Some(line) = lines.next() {
-            let mut l = line.split(": ");
-            let header = l.next().ok_or(RequestParseError::MalformedRequest)?.to_ascii_lowercase();
-            headers.insert(header.to_string(), l.next().ok_or(RequestParseError::Header(header))?.to_string());
+            headers.append(header, value.trim());
+        }
+
+        if headers.contains("transfer-encoding") && headers.contains("content-length") {
+            return Err(RequestParseError::Header("transfer-encoding".to_string()));
+        }
+
+        if let Some(host) = fallback_host.filter(|_| version < 1.1 && !headers.contains("host")) {
+            headers.insert("host", host);
         }
 
         let host = headers.get("host").ok_or(RequestParseError::Host)?;
-        let body = vec![];
+        let body = bytes.get(head_len + 4..).unwrap_or_default();
 
-        let body = if head_len == bytes.len() {
-            body
-        } else {
-            match headers.get("content-length") {
-                Some(len) => {
-                    let len = len.parse::<usize>().map_err(|_| RequestParseError::Header("content-length".to_string()))?;
-                    bytes[head_len + 4..head_len + 4 + len].to_vec()
-                },
-                None => body
-            }
+        let body = match headers.get("content-length") {
+            _ if !with_body => Vec::new(),
+            Some(len) => {
+                let len = len.parse::<usize>().map_err(|_| RequestParseError::Header("content-length".to_string()))?;
+                body.get(..len).ok_or(RequestParseError::Body)?.to_vec()
+            },
+            None => body.to_vec()
         };
 
-        let url = Url::parse(format!("{protocol}://{host}{route}").as_str()).map_err(|_| RequestParseError::Route)?;
-        Ok(Self::new(socket_addr, method, url, version, headers, body))
+        let url = Url::parse(format!("{protocol}://{host}{}", uri::canonicalize(route)).as_str()).map_err(|_| RequestParseError::Route)?;
+        let mut request = Self::new(socket_addr, method, url, version, headers, body);
+        request.target = route.to_string();
+        Ok(request)
+    }
+
+    pub fn outgoing(method: HttpMethod, url: Url, headers: impl Into<HeaderMap>, body: Vec<u8>) -> Self {
+        Self::new(SocketAddr::from(([0, 0, 0, 0], 0)), method, url, 1.1, headers, body)
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let target = match self.url.query() {
+            Some(query) => format!("{}?{query}", self.url.path()),
+            None => self.url.path().to_string()
+        };
+
+        let host = match self.url.port() {
+            Some(port) => format!("{}:{port}", self.host),
+            None => self.host.clone()
+        };
+
+        let mut bytes = format!("{} {target} HTTP/{:.1}\r\nHost: {host}\r\n", self.method.as_str(), self.version).into_bytes();
+
+        for (header, value) in self.headers.iter() {
+            if !header.eq_ignore_ascii_case("host") && !header.eq_ignore_ascii_case("content-length") {
+                bytes.extend_from_slice(format!("{header}: {value}\r\n").as_bytes());
+            }
+        }
+
+        if !self.body.is_empty() || matches!(self.method, HttpMethod::Post | HttpMethod::Put | HttpMethod::Patch) {
+            bytes.extend_from_slice(format!("content-length: {}\r\n", self.body.len()).as_bytes());
+        }
+
+        bytes.extend_from_slice(b"\r\n");
+        bytes.extend_from_slice(&self.body);
+        bytes
     }
 
     pub fn socket_addr(&self) -> SocketAddr {
@@ -91,13 +183,25 @@ impl Request {
     }
 
     pub fn method(&self) -> HttpMethod {
-        self.method
+        self.method.clone()
     }
 
     pub fn route(&self) -> &str {
         &self.route
     }
 
+    pub fn raw_route(&self) -> &str {
+        &self.raw_route
+    }
+
+    pub fn raw_query(&self) -> Option<&str> {
+        self.url.query()
+    }
+
+    pub fn target(&self) -> &str {
+        &self.target
+    }
+
     pub fn protocol(&self) -> &str {
         &self.protocol
     }
@@ -110,8 +214,174 @@ impl Request {
         &self.host
     }
 
+    pub fn url(&self) -> &Url {
+        &self.url
+    }
+
     pub fn header(&self, header: &str) -> Option<&str> {
-        self.headers.get(header).map(|value| value.as_str())
+        self.headers.get(header)
+    }
+
+    pub fn header_all(&self, header: &str) -> Vec<&str> {
+        self.headers.get_all(header)
+    }
+
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    pub fn query(&self, key: &str) -> Option<&str> {
+        self.query.iter()
+            .find(|(name, _)| name == key)
+            .map(|(_, value)| value.as_str())
+    }
+
+    pub fn query_all(&self, key: &str) -> Vec<&str> {
+        self.query.iter()
+            .filter(|(name, _)| name == key)
+            .map(|(_, value)| value.as_str())
+            .collect()
+    }
+
+    pub fn query_as<'a, T: Deserialize<'a>>(&'a self) -> Result<T, RequestParseError> {
+        serde_urlencoded::from_str(self.url.query().unwrap_or("")).map_err(|_| RequestParseError::Query)
+    }
+
+    pub fn cookie(&self, name: &str) -> Option<&str> {
+        self.cookies.iter().find(|(cookie, _)| cookie == name).map(|(_, value)| value.as_str())
+    }
+
+    pub fn cookies(&self) -> HashMap<&str, &str> {
+        self.cookies.iter().map(|(name, value)| (name.as_str(), value.as_str())).collect()
+    }
+
+    pub fn method_override(&self) -> Option<HttpMethod> {
+        if self.method != HttpMethod::Post {
+            return None;
+        }
+
+        let requested = match self.header("x-http-method-override") {
+            Some(method) => method.trim().to_ascii_uppercase(),
+            None => self.form().ok()?.get("_method")?.trim().to_ascii_uppercase()
+        };
+
+        HttpMethod::try_from(requested.as_str()).ok()
+            .filter(|method| matches!(method, HttpMethod::Put | HttpMethod::Patch | HttpMethod::Delete))
+    }
+
+    pub fn session(&self) -> Option<&Session> {
+        self.session.as_ref()
+    }
+
+    pub fn ext<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.extensions.get::<T>()
+    }
+
+    pub fn ext_mut<T: Send + Sync + 'static>(&mut self) -> Option<&mut T> {
+        self.extensions.get_mut::<T>()
+    }
+
+    pub fn insert_ext<T: Send + Sync + 'static>(&mut self, value: T) {
+        self.extensions.insert(value);
+    }
+
+    pub fn remove_ext<T: Send + Sync + 'static>(&mut self) -> Option<Arc<T>> {
+        self.extensions.remove::<T>()
+    }
+
+    pub fn extensions(&self) -> &Extensions {
+        &self.extensions
+    }
+
+    pub fn extensions_mut(&mut self) -> &mut Extensions {
+        &mut self.extensions
+    }
+
+    pub fn state<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.state.as_ref()?.get::<T>()
+    }
+
+    pub(crate) fn set_state(&mut self, state: Arc<SharedState>) {
+        self.state = Some(state);
+    }
+
+    pub(crate) fn parse_cookies(&mut self, parser: &CookieParser) -> Result<(), RequestParseError> {
+        let header = self.header_all("cookie").join("; ");
+
+        if !header.is_empty() {
+            self.cookies = parser.parse(&header)?;
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn set_header(&mut self, header: &str, value: &str) {
+        self.headers.insert(header, value);
+    }
+
+    pub(crate) fn set_session(&mut self, session: Session) {
+        self.session = Some(session);
+    }
+
+    pub fn param(&self, param: &str) -> Option<&str> {
+        self.params.get(param).map(|value| value.as_str())
+    }
+
+    pub fn params(&self) -> &HashMap<String, String> {
+        &self.params
+    }
+
+    pub fn experiment(&self, name: &str) -> Option<&str> {
+        self.experiments.get(name).map(|bucket| bucket.as_str())
+    }
+
+    pub fn set_experiment(&mut self, name: &str, bucket: &str) {
+        self.experiments.insert(name.to_string(), bucket.to_string());
+    }
+
+    pub fn set_method(&mut self, method: HttpMethod) {
+        self.method = method;
+    }
+
+    pub fn set_route(&mut self, route: &str) {
+        self.route = uri::decode(route);
+        self.raw_route = route.to_string();
+    }
+
+    pub(crate) fn decompress(&mut self, limit: usize) -> Result<(), RequestParseError> {
+        let Some(encoding) = self.headers.get("content-encoding") else {
+            return Ok(());
+        };
+
+        let codings: Vec<String> = encoding.split(',')
+            .map(|coding| coding.trim().to_ascii_lowercase())
+            .filter(|coding| !coding.is_empty() && coding != "identity")
+            .collect();
+
+        if !codings.iter().all(|coding| coding == "gzip" || coding == "x-gzip") {
+            return Err(RequestParseError::UnsupportedEncoding);
+        }
+
+        for _ in &codings {
+            let mut body = Vec::new();
+
+            GzDecoder::new(self.body.as_slice())
+                .take(limit as u64 + 1)
+                .read_to_end(&mut body)
+                .map_err(|_| RequestParseError::Body)?;
+
+            if body.len() > limit {
+                return Err(RequestParseError::PayloadTooLarge);
+            }
+
+            self.body = body;
+        }
+
+        self.parsed.lock().unwrap().clear();
+
+        self.headers.remove("content-encoding");
+        self.headers.insert("content-length", &self.body.len().to_string());
+        Ok(())
     }
 
     pub fn text(&self) -> Result<&str, RequestParseError> {
@@ -119,55 +389,260 @@ impl Request {
     }
 
     pub fn json<'a, T: Deserialize<'a>>(&'a self) -> Result<T, RequestParseError> {
-        println!("{:?}", self.body);
+        trace!("Parsing JSON body: {:?}", String::from_utf8_lossy(&self.body));
         serde_json::from_slice(&self.body).map_err(|_| RequestParseError::Body)
     }
 
+    pub fn json_cached<T: DeserializeOwned + Send + Sync + 'static>(&self) -> Result<Arc<T>, RequestParseError> {
+        if let Some(value) = self.cached::<T>() {
+            return Ok(value);
+        }
+
+        let value = serde_json::from_slice::<T>(&self.body).map_err(|_| RequestParseError::Body)?;
+        Ok(self.cache(value))
+    }
+
+    pub fn cached<T: Send + Sync + 'static>(&self) -> Option<Arc<T>> {
+        self.extensions.shared::<T>().or_else(|| self.parsed.lock().unwrap().shared::<T>())
+    }
+
+    pub fn cache<T: Send + Sync + 'static>(&self, value: T) -> Arc<T> {
+        let value = Arc::new(value);
+        self.parsed.lock().unwrap().insert_shared(value.clone());
+        value
+    }
+
+    pub fn form(&self) -> Result<HashMap<String, String>, RequestParseError> {
+        match self.header("content-type") {
+            Some(content_type) if content_type.starts_with("application/x-www-form-urlencoded") => Ok(Form::parse(&self.body)),
+            _ => Err(RequestParseError::Body)
+        }
+    }
+
+    pub fn multipart(&self) -> Result<Vec<Part>, RequestParseError> {
+        self.multipart_with(&MultipartLimits::default())
+    }
+
+    pub fn multipart_with(&self, limits: &MultipartLimits) -> Result<Vec<Part>, RequestParseError> {
+        let boundary = self.header("content-type")
+            .and_then(multipart::boundary)
+            .ok_or(RequestParseError::Body)?;
+
+        multipart::parse(&self.body, boundary, limits)
+    }
+
     pub fn raw(&self) -> &[u8] {
         &self.body
     }
+
+    pub fn locale(&self) -> Option<&str> {
+        self.locale.as_deref()
+    }
+
+    pub fn t(&self, key: &str, args: &[(&str, &str)]) -> String {
+        match (&self.i18n, &self.locale) {
+            (Some(i18n), Some(locale)) => i18n.translate(locale, key, args),
+            _ => key.to_string()
+        }
+    }
+
+    pub fn format_number(&self, number: f64, decimals: usize) -> String {
+        l10n::format_number(self.locale().unwrap_or(""), number, decimals)
+    }
+
+    pub fn format_date(&self, time: SystemTime) -> String {
+        l10n::format_date(self.locale().unwrap_or(""), time)
+    }
+
+    pub(crate) fn set_params(&mut self, params: HashMap<String, String>) {
+        self.params = params;
+    }
+
+    pub(crate) fn localize(&mut self, i18n: Arc<I18n>) {
+        self.locale = Some(i18n.negotiate(self));
+        self.i18n = Some(i18n);
+    }
+}
+
+pub struct BodyStream(Box<dyn Read + Send>);
+
+impl Debug for BodyStream {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "BodyStream")
+    }
 }
 
 #[derive(Debug)]
 pub struct Response {
     protocol: String,
     version: f32,
-    status: u16,
-    headers: HashMap<String, String>,
-    body: Vec<u8>
+    status: StatusCode,
+    headers: HeaderMap,
+    cookies: Vec<Cookie>,
+    body: Vec<u8>,
+    stream: Option<BodyStream>,
+    forward: Option<String>,
+    file: Option<String>,
+    sniffed: Option<&'static str>,
+    digest_trailer: bool
 }
 
 impl Response {
-    pub fn new(status: u16) -> Self {
+    pub fn new(status: impl Into<StatusCode>) -> Self {
         Self {
-            protocol: String::new(),
-            version: 0.0,
-            headers: HashMap::new(),
+            protocol: "http".to_string(),
+            version: 1.1,
+            headers: HeaderMap::new(),
+            cookies: Vec::new(),
             body: Vec::new(),
-            status
+            stream: None,
+            forward: None,
+            file: None,
+            sniffed: None,
+            digest_trailer: false,
+            status: status.into()
         }
     }
 
-    pub fn text(text: impl Display, status: u16) -> Self {
+    pub fn text(text: impl Display, status: impl Into<StatusCode>) -> Self {
         let mut response = Response::new(status);
         response.set_body(text.to_string().as_bytes(), "text/html").unwrap();
         response
     }
 
-    pub fn file(filename: &str, status: u16) -> io::Result<Self> {
+    pub fn file(filename: &str, status: impl Into<StatusCode>) -> io::Result<Self> {
+        Self::stream_file(filename, filename, status.into(), None)
+    }
+
+    pub fn file_range(request: &Request, filename: &str) -> io::Result<Self> {
+        Self::stream_file(filename, filename, StatusCode::from(200), Some(request))
+    }
+
+    pub fn stream(body: impl Read + Send + 'static, content_type: &str, status: impl Into<StatusCode>) -> Self {
         let mut response = Self::new(status);
-        let mut file = BufReader::new(File::open(filename)?);
-        response.set_body(file, &Self::file_content_type(filename))?;
+        response.stream = Some(BodyStream(Box::new(body)));
+        response.header("Content-Type", content_type);
+        response
+    }
+
+    pub fn forward(route: &str) -> Self {
+        let mut response = Self::new(200);
+        response.forward = Some(route.to_string());
+        response
+    }
+
+    pub fn precompressed_file(request: &Request, filename: &str, status: impl Into<StatusCode>) -> io::Result<Self> {
+        let status = status.into();
+        let accepted = request.header("accept-encoding").unwrap_or("");
+
+        for (encoding, extension) in [("br", "br"), ("gzip", "gz")] {
+            let compressed = format!("{filename}.{extension}");
+
+            if Self::accepts_encoding(accepted, encoding) && Path::new(&compressed).is_file() {
+                let mut response = Self::stream_file(filename, &compressed, status, Some(request))?;
+                response.header("Content-Encoding", encoding);
+                response.header("Vary", "Accept-Encoding");
+                return Ok(response);
+            }
+        }
+
+        let mut response = Self::stream_file(filename, filename, status, Some(request))?;
+        response.header("Vary", "Accept-Encoding");
         Ok(response)
     }
 
-    pub fn json(json: impl Serialize, status: u16) -> serde_json::Result<Self> {
+    fn stream_file(filename: &str, served: &str, status: StatusCode, request: Option<&Request>) -> io::Result<Self> {
+        let mut file = File::open(served)?;
+        let metadata = file.metadata()?;
+        let length = metadata.len();
+        let mut response = Self::new(status);
+        response.file = Some(filename.to_string());
+        response.set_validators(&metadata);
+
+        let requested = match request.filter(|_| response.status == 200) {
+            Some(request) => {
+                response.header("Accept-Ranges", "bytes");
+
+                match request.method() {
+                    HttpMethod::Get => range::requested(request, &response, length),
+                    _ => RangeRequest::Full
+                }
+            },
+            None => RangeRequest::Full
+        };
+
+        let range = match requested {
+            RangeRequest::Full => 0..length,
+            RangeRequest::Partial(range) => {
+                response.status = StatusCode::from(206);
+                response.header("Content-Range", &range::content_range(&range, length));
+                range
+            },
+            RangeRequest::Unsatisfiable => {
+                response.unsatisfiable(length);
+                return Ok(response);
+            }
+        };
+
+        let content_type = Self::file_content_type(filename);
+
+        if content_type == "application/octet-stream" && served == filename {
+            let mut head = Vec::new();
+            (&mut file).take(SNIFF_SIZE).read_to_end(&mut head)?;
+            response.sniffed = sniff::sniff(&head);
+        }
+
+        file.seek(SeekFrom::Start(range.start))?;
+        response.stream = Some(BodyStream(Box::new(BufReader::new(file).take(range.end - range.start))));
+        response.header("Content-Length", &(range.end - range.start).to_string());
+        response.header("Content-Type", &content_type);
+        Ok(response)
+    }
+
+    pub fn json(json: impl Serialize, status: impl Into<StatusCode>) -> serde_json::Result<Self> {
         let mut response = Response::new(status);
         let serialized = serde_json::to_string(&json)?;
         response.set_body(serialized.as_bytes(), "application/json").unwrap();
         Ok(response)
     }
 
+    pub fn builder() -> ResponseBuilder {
+        ResponseBuilder::new()
+    }
+
+    pub fn no_content() -> Self {
+        Self::new(204)
+    }
+
+    pub fn redirect(location: &str, status: impl Into<StatusCode>) -> Self {
+        Self::builder().status(status).header("Location", location).empty()
+    }
+
+    pub fn created(location: &str) -> Self {
+        Self::builder().status(201).header("Location", location).empty()
+    }
+
+    pub fn allow(methods: &[HttpMethod]) -> Self {
+        let allow: Vec<&str> = methods.iter().map(HttpMethod::as_str).collect();
+        let mut response = Response::new(204);
+        response.header("Allow", &allow.join(", "));
+        response
+    }
+
+    pub fn method_not_allowed(methods: &[HttpMethod]) -> Self {
+        let allow: Vec<&str> = methods.iter().map(HttpMethod::as_str).collect();
+        let mut response = Response::text("Method not allowed", 405);
+        response.header("Allow", &allow.join(", "));
+        response
+    }
+
+    fn unsatisfiable(&mut self, length: u64) {
+        self.body.clear();
+        self.status = StatusCode::from(416);
+        self.header("Content-Range", &format!("bytes */{length}"));
+        self.header("Content-Length", "0");
+    }
+
     pub fn fill_from(&mut self, request: &Request) {
         self.version = request.version;
         self.protocol = request.protocol.to_string();
@@ -175,47 +650,298 @@ impl Response {
 
     pub fn set_body(&mut self, mut body: impl Read, content_type: &str) -> io::Result<()> {
         let start = SystemTime::now();
-        let mut buffer = [0_u8; BUFFER_SIZE];
+        self.body.clear();
+        body.read_to_end(&mut self.body)?;
+
+        debug!("Reading response data took {} ms", start.elapsed().unwrap_or_default().as_millis());
+        self.header("Content-Length", &self.body.len().to_string());
+        self.header("Content-Type", content_type);
+        Ok(())
+    }
 
-        while let size = body.read(&mut buffer)? {
-            self.body.extend_from_slice(&buffer[..size]);
+    pub fn set_cookie(&mut self, cookie: Cookie) {
+        self.cookies.retain(|existing| existing.name() != cookie.name());
+        self.cookies.push(cookie);
+    }
 
-            if size < BUFFER_SIZE {
-                break;
-            }
+    pub fn cookies(&self) -> &[Cookie] {
+        &self.cookies
+    }
+
+    pub fn buffer(&mut self) -> io::Result<()> {
+        if let Some(BodyStream(body)) = self.stream.take() {
+            let content_type = self.get_header("Content-Type").unwrap_or("application/octet-stream").to_string();
+            self.set_body(body, &content_type)?;
         }
 
-        println!("Reading response data took {} ms", start.elapsed().unwrap().as_millis());
-        self.header("Content-Length", &self.body.len().to_string());
-        self.header("Content-Type", content_type);
         Ok(())
     }
 
     pub fn header(&mut self, header: &str, value: &str) {
-        self.headers.insert(header.to_string(), value.to_string());
+        self.headers.insert(header, value);
+    }
+
+    pub fn append_header(&mut self, header: &str, value: &str) {
+        self.headers.append(header, value);
+    }
+
+    pub fn remove_header(&mut self, header: &str) -> Option<String> {
+        self.headers.remove(header)
+    }
+
+    pub fn read_from(reader: &mut impl BufRead, has_body: bool, max_size: usize) -> io::Result<Self> {
+        let invalid = |message: &str| io::Error::new(ErrorKind::InvalidData, message.to_string());
+        let mut head_budget = MAX_HEAD_SIZE;
+        let mut line = String::new();
+        read_capped_line(reader, &mut line, &mut head_budget)?;
+
+        let mut status_line = line.trim_end().splitn(3, ' ');
+        let (protocol, version) = status_line.next()
+            .and_then(|protocol| protocol.split_once('/'))
+            .ok_or_else(|| invalid("Malformed status line"))?;
+
+        let version: f32 = version.parse().map_err(|_| invalid("Malformed protocol version"))?;
+        let status: u16 = status_line.next()
+            .and_then(|status| status.parse().ok())
+            .ok_or_else(|| invalid("Malformed status code"))?;
+
+        let mut response = Self::new(status);
+        response.protocol = protocol.to_ascii_lowercase();
+        response.version = version;
+
+        loop {
+            if read_capped_line(reader, &mut line, &mut head_budget)? == 0 {
+                return Err(invalid("Connection closed before end of headers"));
+            }
+
+            let header = line.trim_end();
+
+            if header.is_empty() {
+                break;
+            }
+
+            let (name, value) = header.split_once(':').ok_or_else(|| invalid("Malformed header"))?;
+            response.headers.append(name.trim(), value.trim());
+        }
+
+        if !has_body || status / 100 == 1 || status == 204 || status == 304 {
+            return Ok(response);
+        }
+
+        let chunked = response.get_header("Transfer-Encoding").is_some_and(|encoding| encoding.eq_ignore_ascii_case("chunked"));
+        let length = response.get_header("Content-Length").and_then(|length| length.parse::<usize>().ok());
+        let too_large = || invalid("Response body exceeds the maximum response size");
+
+        if chunked {
+            loop {
+                let mut line_budget = MAX_HEAD_SIZE;
+                read_capped_line(reader, &mut line, &mut line_budget)?;
+                let size = usize::from_str_radix(line.trim().split(';').next().unwrap_or(""), 16).map_err(|_| invalid("Malformed chunk size"))?;
+
+                if size == 0 {
+                    while read_capped_line(reader, &mut line, &mut head_budget)? > 0 && !line.trim().is_empty() {}
+                    break;
+                }
+
+                if size > max_size - response.body.len() {
+                    return Err(too_large());
+                }
+
+                read_exactly(reader, &mut response.body, size)?;
+                read_capped_line(reader, &mut line, &mut line_budget)?;
+            }
+        } else if let Some(length) = length {
+            if length > max_size {
+                return Err(too_large());
+            }
+
+            read_exactly(reader, &mut response.body, length)?;
+        } else {
+            reader.take((max_size as u64).saturating_add(1)).read_to_end(&mut response.body)?;
+
+            if response.body.len() > max_size {
+                return Err(too_large());
+            }
+        }
+
+        Ok(response)
+    }
+
+    pub fn status(&self) -> StatusCode {
+        self.status
+    }
+
+    pub fn version(&self) -> f32 {
+        self.version
+    }
+
+    pub fn body(&self) -> &[u8] {
+        &self.body
+    }
+
+    pub fn headers(&self) -> &HeaderMap {
+        &self.headers
+    }
+
+    pub fn get_header(&self, header: &str) -> Option<&str> {
+        self.headers.get(header)
+    }
+
+    pub fn body_len(&self) -> usize {
+        self.body.len()
+    }
+
+    pub fn sniff_content_type(&mut self) {
+        if self.get_header("Content-Type") != Some("application/octet-stream") {
+            return;
+        }
+
+        let sniffed = match self.stream.is_some() {
+            true => self.sniffed,
+            false => sniff::sniff(&self.body)
+        };
+
+        if let Some(content_type) = sniffed {
+            self.header("Content-Type", content_type);
+        }
+    }
+
+    pub fn served_file(&self) -> Option<&str> {
+        self.file.as_deref()
+    }
+
+    pub fn forwarded_to(&self) -> Option<&str> {
+        self.forward.as_deref()
+    }
+
+    pub(crate) fn take_forward(&mut self) -> Option<String> {
+        self.forward.take()
+    }
+
+    pub fn is_stream(&self) -> bool {
+        self.stream.is_some()
+    }
+
+    pub fn strip_body(&mut self) {
+        if self.stream.take().is_some() {
+            if self.version >= 1.1 && self.get_header("Content-Length").is_none() {
+                self.header("Transfer-Encoding", "chunked");
+            }
+
+            return;
+        }
+
+        if self.get_header("Content-Length").is_none() {
+            let length = self.body.len().to_string();
+            self.header("Content-Length", &length);
+        }
+
+        self.body.clear();
+    }
+
+    pub fn not_modified(&mut self) {
+        self.status = StatusCode::from(304);
+        self.strip_body();
+    }
+
+    fn set_validators(&mut self, metadata: &Metadata) {
+        self.header("ETag", &conditional::etag(metadata));
+
+        if let Some(modified) = conditional::last_modified(metadata) {
+            self.header("Last-Modified", &modified);
+        }
+    }
+
+    pub fn add_digests(&mut self) {
+        if self.is_stream() {
+            if self.version >= 1.1 && self.get_header("Content-Length").is_none() {
+                self.digest_trailer = true;
+                self.header("Trailer", "Digest");
+            }
+
+            return;
+        }
+
+        let md5 = digest::content_md5(&self.body);
+        let sha256 = digest::sha256(&self.body);
+        self.header("Content-MD5", &md5);
+        self.header("Digest", &sha256);
     }
 
-    pub fn to_bytes(mut self) -> Vec<u8> {
+    pub fn write_to(&mut self, writer: &mut impl Write) -> io::Result<()> {
+        let Some(BodyStream(mut body)) = self.stream.take() else {
+            return writer.write_all(&self.to_bytes());
+        };
+
+        let chunked = self.version >= 1.1 && self.get_header("Content-Length").is_none();
+
+        if chunked {
+            self.header("Transfer-Encoding", "chunked");
+        }
+
+        writer.write_all(&self.to_bytes())?;
+        let mut buffer = [0_u8; BUFFER_SIZE];
+        let mut hasher = (chunked && self.digest_trailer).then(Sha256::new);
+
+        loop {
+            let size = body.read(&mut buffer)?;
+
+            if size == 0 {
+                break;
+            }
+
+            if let Some(hasher) = &mut hasher {
+                hasher.update(&buffer[..size]);
+            }
+
+            if chunked {
+                writer.write_all(format!("{size:X}\r\n").as_bytes())?;
+                writer.write_all(&buffer[..size])?;
+                writer.write_all(b"\r\n")?;
+            } else {
+                writer.write_all(&buffer[..size])?;
+            }
+        }
+
+        match hasher {
+            Some(hasher) => writer.write_all(format!("0\r\nDigest: {}\r\n\r\n", digest::sha256_value(&hasher.finalize())).as_bytes())?,
+            None if chunked => writer.write_all(b"0\r\n\r\n")?,
+            None => ()
+        }
+
+        writer.flush()
+    }
+
+    pub fn to_bytes(&self) -> Vec<u8> {
         let mut bytes = Vec::new();
         bytes.extend_from_slice((self.protocol.to_ascii_uppercase() + "/").as_bytes());
-        bytes.extend_from_slice((self.version.to_string() + " ").as_bytes());
+        bytes.extend_from_slice(format!("{:.1} ", self.version).as_bytes());
         bytes.extend_from_slice((self.status.to_string() + "\r\n").as_bytes());
 
-        for (header, value) in self.headers {
-            bytes.extend_from_slice((header + ": ").as_bytes());
-            bytes.extend_from_slice((value + "\r\n").as_bytes());
+        for (header, value) in self.headers.iter() {
+            bytes.extend_from_slice(format!("{header}: {value}\r\n").as_bytes());
+        }
+
+        for cookie in &self.cookies {
+            bytes.extend_from_slice(format!("Set-Cookie: {cookie}\r\n").as_bytes());
         }
 
         bytes.extend_from_slice("\r\n".as_bytes());
 
-        if self.body.len() > 0 {
-            bytes.extend_from_slice(&self.body);
-            bytes.extend_from_slice("\r\n\r\n".as_bytes());
-        }
+        bytes.extend_from_slice(&self.body);
 
         bytes
     }
 
+    fn accepts_encoding(accept_encoding: &str, encoding: &str) -> bool {
+        accept_encoding.split(',').any(|entry| {
+            let mut parts = entry.split(';');
+            let name = parts.next().unwrap_or("").trim();
+            let quality = parts.find_map(|param| param.trim().strip_prefix("q=")?.parse::<f32>().ok()).unwrap_or(1.0);
+            (name.eq_ignore_ascii_case(encoding) || name == "*") && quality > 0.0
+        })
+    }
+
     fn file_content_type(filename: &str) -> String {
         let extension = filename.rsplit('.').next().unwrap_or("").to_lowercase();
 
@@ -294,3 +1020,165 @@ impl Response {
         String::from(content_type)
     }
 }
+
+#[derive(Debug, Clone)]
+pub struct ResponseBuilder {
+    status: StatusCode,
+    headers: Vec<(String, String)>,
+    cookies: Vec<Cookie>
+}
+
+impl ResponseBuilder {
+    pub fn new() -> Self {
+        Self {
+            status: StatusCode::OK,
+            headers: Vec::new(),
+            cookies: Vec::new()
+        }
+    }
+
+    pub fn status(mut self, status: impl Into<StatusCode>) -> Self {
+        self.status = status.into();
+        self
+    }
+
+    pub fn header(mut self, header: &str, value: &str) -> Self {
+        self.headers.push((header.to_string(), value.to_string()));
+        self
+    }
+
+    pub fn cookie(mut self, cookie: Cookie) -> Self {
+        self.cookies.push(cookie);
+        self
+    }
+
+    pub fn empty(self) -> Response {
+        let mut response = Response::new(self.status);
+
+        if !matches!(self.status.as_u16(), 100..=199 | 204 | 304) {
+            response.header("Content-Length", "0");
+        }
+
+        self.apply(response)
+    }
+
+    pub fn text(self, text: impl Display) -> Response {
+        let response = Response::text(text, self.status);
+        self.apply(response)
+    }
+
+    pub fn body(self, body: impl Into<Vec<u8>>, content_type: &str) -> Response {
+        let mut response = Response::new(self.status);
+        let body = body.into();
+        response.set_body(body.as_slice(), content_type).unwrap();
+        self.apply(response)
+    }
+
+    pub fn json(self, json: impl Serialize) -> serde_json::Result<Response> {
+        let response = Response::json(json, self.status)?;
+        Ok(self.apply(response))
+    }
+
+    pub fn file(self, filename: &str) -> io::Result<Response> {
+        let response = Response::file(filename, self.status)?;
+        Ok(self.apply(response))
+    }
+
+    pub fn stream(self, body: impl Read + Send + 'static, content_type: &str) -> Response {
+        let response = Response::stream(body, content_type, self.status);
+        self.apply(response)
+    }
+
+    fn apply(self, mut response: Response) -> Response {
+        for (header, value) in &self.headers {
+            response.header(header, value);
+        }
+
+        for cookie in self.cookies {
+            response.set_cookie(cookie);
+        }
+
+        response
+    }
+}
+
+impl Default for ResponseBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn read_capped_line(reader: &mut impl BufRead, line: &mut String, budget: &mut usize) -> io::Result<usize> {
+    line.clear();
+    let read = reader.take(*budget as u64).read_line(line)?;
+    *budget -= read;
+
+    if *budget == 0 && !line.ends_with('\n') {
+        return Err(io::Error::new(ErrorKind::InvalidData, "Response head is too large"));
+    }
+
+    Ok(read)
+}
+
+fn read_exactly(reader: &mut impl BufRead, body: &mut Vec<u8>, size: usize) -> io::Result<()> {
+    match reader.take(size as u64).read_to_end(body)? {
+        read if read < size => Err(io::Error::new(ErrorKind::UnexpectedEof, "Connection closed before end of body")),
+        _ => Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use flate2::Compression;
+    use flate2::write::GzEncoder;
+    use super::*;
+
+    fn encoded(encoding: &str, body: &[u8]) -> Request {
+        let mut bytes = format!("POST /upload HTTP/1.1\r\nHost: localhost\r\nContent-Encoding: {encoding}\r\nContent-Length: {}\r\n\r\n", body.len()).into_bytes();
+        bytes.extend_from_slice(body);
+        Request::from_bytes(SocketAddr::from(([127, 0, 0, 1], 1234)), &bytes).unwrap()
+    }
+
+    fn gzip(body: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(body).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn decompresses_gzip_bodies() {
+        let mut request = encoded("gzip", &gzip(b"hello world"));
+        request.decompress(1024).unwrap();
+
+        assert_eq!(request.raw(), b"hello world");
+        assert_eq!(request.header("content-encoding"), None);
+        assert_eq!(request.header("content-length"), Some("11"));
+    }
+
+    #[test]
+    fn caps_the_decompressed_size() {
+        let mut request = encoded("gzip", &gzip(&vec![0; 1024 * 1024]));
+        assert!(matches!(request.decompress(64 * 1024), Err(RequestParseError::PayloadTooLarge)));
+    }
+
+    #[test]
+    fn sends_the_digest_of_chunked_streams_as_a_trailer() {
+        let mut response = Response::stream(&b"hello world"[..], "text/plain", 200);
+        response.add_digests();
+
+        let mut written = Vec::new();
+        response.write_to(&mut written).unwrap();
+        let written = String::from_utf8(written).unwrap();
+
+        assert!(written.contains("Trailer: Digest\r\n"));
+        assert!(written.ends_with(&format!("\r\nB\r\nhello world\r\n0\r\nDigest: {}\r\n\r\n", digest::sha256(b"hello world"))));
+    }
+
+    #[test]
+    fn rejects_unsupported_encodings() {
+        for encoding in ["br", "deflate", "zstd", "gzip, br"] {
+            let mut request = encoded(encoding, b"data");
+            assert!(matches!(request.decompress(1024), Err(RequestParseError::UnsupportedEncoding)), "{encoding}");
+        }
+    }
+}
@@ -1,12 +1,50 @@
 use crate::error::InvalidMethodError;
 
-#[derive(Debug, Eq, PartialEq, Copy, Clone)]
+#[derive(Debug, Eq, PartialEq, Hash, Clone)]
 pub enum HttpMethod {
     Get,
     Post,
     Put,
     Patch,
-    Delete
+    Delete,
+    Head,
+    Options,
+    Trace,
+    Connect,
+    Extension(String)
+}
+
+impl HttpMethod {
+    pub const ALL: [HttpMethod; 9] = [
+        HttpMethod::Get,
+        HttpMethod::Post,
+        HttpMethod::Put,
+        HttpMethod::Patch,
+        HttpMethod::Delete,
+        HttpMethod::Head,
+        HttpMethod::Options,
+        HttpMethod::Trace,
+        HttpMethod::Connect
+    ];
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            HttpMethod::Get => "GET",
+            HttpMethod::Post => "POST",
+            HttpMethod::Put => "PUT",
+            HttpMethod::Patch => "PATCH",
+            HttpMethod::Delete => "DELETE",
+            HttpMethod::Head => "HEAD",
+            HttpMethod::Options => "OPTIONS",
+            HttpMethod::Trace => "TRACE",
+            HttpMethod::Connect => "CONNECT",
+            HttpMethod::Extension(method) => method
+        }
+    }
+
+    pub fn is_idempotent(&self) -> bool {
+        matches!(self, HttpMethod::Get | HttpMethod::Head | HttpMethod::Put | HttpMethod::Delete | HttpMethod::Options | HttpMethod::Trace)
+    }
 }
 
 impl TryFrom<&str> for HttpMethod {
@@ -19,7 +57,15 @@ impl TryFrom<&str> for HttpMethod {
             "PUT" => Ok(HttpMethod::Put),
             "PATCH" => Ok(HttpMethod::Patch),
             "DELETE" => Ok(HttpMethod::Delete),
+            "HEAD" => Ok(HttpMethod::Head),
+            "OPTIONS" => Ok(HttpMethod::Options),
+            "TRACE" => Ok(HttpMethod::Trace),
+            "CONNECT" => Ok(HttpMethod::Connect),
+            method if is_token(method) => Ok(HttpMethod::Extension(method.to_string())),
             _ => Err(InvalidMethodError)
         }
     }
-}
\ No newline at end of file
+}
+fn is_token(value: &str) -> bool {
+    !value.is_empty() && value.bytes().all(|byte| byte.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&byte))
+}
@@ -0,0 +1,140 @@
+use std::fmt::{self, Debug, Display, Formatter};
+use crate::error::ServerError;
+use crate::message::{Request, Response};
+use crate::method::HttpMethod;
+use crate::predicate::Predicate;
+use crate::route::{matches_prefix, Router};
+
+pub trait MiddlewareAction<E: ServerError> : Fn(&mut Request, Next<'_, E>) -> Result<Response, E> + Sync + Send + 'static {}
+impl <E: ServerError, F: Fn(&mut Request, Next<'_, E>) -> Result<Response, E> + Sync + Send + 'static> MiddlewareAction<E> for F {}
+
+pub struct Middleware<E: ServerError> {
+    name: Option<String>,
+    prefix: String,
+    priority: i32,
+    condition: Option<Predicate>,
+    action: Box<dyn MiddlewareAction<E>>
+}
+
+impl <E: ServerError> Middleware<E> {
+    pub fn new(prefix: &str, action: impl MiddlewareAction<E>) -> Self {
+        Self {
+            name: None,
+            prefix: format!("/{}", prefix.trim_matches('/')),
+            priority: 0,
+            condition: None,
+            action: Box::new(action)
+        }
+    }
+
+    pub fn named(mut self, name: &str) -> Self {
+        self.name = Some(name.to_string());
+        self
+    }
+
+    pub fn priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
+        self
+    }
+
+    pub fn when(mut self, condition: Predicate) -> Self {
+        self.condition = Some(condition);
+        self
+    }
+
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    pub fn info(&self) -> MiddlewareInfo {
+        MiddlewareInfo {
+            name: self.name.clone(),
+            prefix: self.prefix.clone(),
+            priority: self.priority,
+            condition: self.condition.as_ref().map(|condition| format!("{condition:?}"))
+        }
+    }
+
+    fn applies(&self, request: &Request) -> bool {
+        matches_prefix(request.route(), &self.prefix) && self.condition.as_ref().is_none_or(|condition| condition.matches(request))
+    }
+}
+
+impl <E: ServerError> Debug for Middleware<E> {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        Debug::fmt(&self.info(), f)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MiddlewareInfo {
+    pub name: Option<String>,
+    pub prefix: String,
+    pub priority: i32,
+    pub condition: Option<String>
+}
+
+impl Display for MiddlewareInfo {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{} at {} (priority {})", self.name.as_deref().unwrap_or("<unnamed>"), self.prefix, self.priority)?;
+
+        match &self.condition {
+            Some(condition) => write!(f, " when {condition}"),
+            None => Ok(())
+        }
+    }
+}
+
+pub fn insert_ordered<E: ServerError>(chain: &mut Vec<Middleware<E>>, middleware: Middleware<E>) {
+    let index = chain.partition_point(|existing| existing.priority >= middleware.priority);
+    chain.insert(index, middleware);
+}
+
+pub struct Next<'a, E: ServerError> {
+    middleware: &'a [Middleware<E>],
+    router: &'a Router<E>
+}
+
+impl <'a, E: ServerError> Next<'a, E> {
+    pub fn new(middleware: &'a [Middleware<E>], router: &'a Router<E>) -> Self {
+        Self {
+            middleware,
+            router
+        }
+    }
+
+    pub fn run(self, request: &mut Request) -> Result<Response, E> {
+        match self.middleware.split_first() {
+            Some((current, rest)) => {
+                let next = Next::new(rest, self.router);
+
+                if current.applies(request) {
+                    (current.action)(request, next)
+                } else {
+                    next.run(request)
+                }
+            },
+            None => {
+                if let Some(redirect) = self.router.redirect(request) {
+                    return Ok(redirect);
+                }
+
+                if request.method() == HttpMethod::Options && !self.router.has_route(HttpMethod::Options, request) {
+                    let methods = self.router.allowed_methods(request);
+
+                    if !methods.is_empty() {
+                        return Ok(Response::allow(&methods));
+                    }
+                }
+
+                if let Some(methods) = self.router.method_not_allowed(request) {
+                    return Ok(Response::method_not_allowed(&methods));
+                }
+
+                let (action, params) = self.router.resolve(request);
+                request.set_params(params);
+                action(request)
+            }
+        }
+    }
+}
@@ -0,0 +1,346 @@
+use std::collections::{HashMap, VecDeque};
+use std::env;
+use std::fs::{self, File};
+use std::io::{self, Cursor, Read};
+use std::path::{Path, PathBuf};
+use rand::Rng;
+use crate::error::RequestParseError;
+use crate::message::Response;
+use crate::status::StatusCode;
+
+#[derive(Debug, Clone)]
+pub struct MultipartLimits {
+    pub max_part_size: usize,
+    pub memory_threshold: usize,
+    pub temp_dir: PathBuf
+}
+
+impl Default for MultipartLimits {
+    fn default() -> Self {
+        Self {
+            max_part_size: 10 * 1024 * 1024,
+            memory_threshold: 64 * 1024,
+            temp_dir: env::temp_dir()
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum PartBody {
+    Memory(Vec<u8>),
+    File(PathBuf)
+}
+
+#[derive(Debug)]
+pub struct Part {
+    name: String,
+    filename: Option<String>,
+    content_type: Option<String>,
+    headers: HashMap<String, String>,
+    body: PartBody
+}
+
+impl Part {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn filename(&self) -> Option<&str> {
+        self.filename.as_deref()
+    }
+
+    pub fn content_type(&self) -> Option<&str> {
+        self.content_type.as_deref()
+    }
+
+    pub fn header(&self, header: &str) -> Option<&str> {
+        self.headers.get(&header.to_ascii_lowercase()).map(|value| value.as_str())
+    }
+
+    pub fn body(&self) -> &PartBody {
+        &self.body
+    }
+
+    pub fn bytes(&self) -> io::Result<Vec<u8>> {
+        match &self.body {
+            PartBody::Memory(bytes) => Ok(bytes.clone()),
+            PartBody::File(path) => fs::read(path)
+        }
+    }
+
+    pub fn text(&self) -> io::Result<String> {
+        String::from_utf8(self.bytes()?).map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+
+    pub fn persist(mut self, path: impl AsRef<Path>) -> io::Result<()> {
+        match std::mem::replace(&mut self.body, PartBody::Memory(Vec::new())) {
+            PartBody::Memory(bytes) => fs::write(path, bytes),
+            PartBody::File(temp) => fs::rename(&temp, &path).or_else(|_| {
+                fs::copy(&temp, &path)?;
+                fs::remove_file(&temp)
+            })
+        }
+    }
+}
+
+impl Drop for Part {
+    fn drop(&mut self) {
+        if let PartBody::File(path) = &self.body {
+            fs::remove_file(path).ok();
+        }
+    }
+}
+
+pub fn boundary(content_type: &str) -> Option<&str> {
+    let mut params = content_type.split(';');
+
+    if !params.next()?.trim().to_ascii_lowercase().starts_with("multipart/") {
+        return None;
+    }
+
+    params.find_map(|param| {
+        let (name, value) = param.split_once('=')?;
+        name.trim().eq_ignore_ascii_case("boundary").then(|| value.trim().trim_matches('"'))
+    })
+}
+
+pub fn parse(body: &[u8], boundary: &str, limits: &MultipartLimits) -> Result<Vec<Part>, RequestParseError> {
+    let delimiter = format!("--{boundary}").into_bytes();
+    let separator = format!("\r\n--{boundary}").into_bytes();
+    let mut rest = body.strip_prefix(delimiter.as_slice()).ok_or(RequestParseError::Multipart)?;
+    let mut parts = Vec::new();
+
+    loop {
+        if rest.starts_with(b"--") {
+            return Ok(parts);
+        }
+
+        rest = rest.strip_prefix(b"\r\n").ok_or(RequestParseError::Multipart)?;
+        let head_len = find(rest, b"\r\n\r\n").ok_or(RequestParseError::Multipart)?;
+        let headers = parse_headers(&rest[..head_len])?;
+        rest = &rest[head_len + 4..];
+
+        let body_len = find(rest, &separator).ok_or(RequestParseError::Multipart)?;
+
+        if body_len > limits.max_part_size {
+            return Err(RequestParseError::Multipart);
+        }
+
+        parts.push(part(headers, &rest[..body_len], limits)?);
+        rest = &rest[body_len + separator.len()..];
+    }
+}
+
+fn part(headers: HashMap<String, String>, body: &[u8], limits: &MultipartLimits) -> Result<Part, RequestParseError> {
+    let disposition = headers.get("content-disposition").map_or("", |disposition| disposition.as_str());
+    let params = disposition.split(';').skip(1).filter_map(|param| {
+        let (name, value) = param.split_once('=')?;
+        Some((name.trim().to_ascii_lowercase(), value.trim().trim_matches('"').to_string()))
+    });
+
+    let mut name = None;
+    let mut filename = None;
+
+    for (param, value) in params {
+        match param.as_str() {
+            "name" => name = Some(value),
+            "filename" => filename = Some(value),
+            _ => ()
+        }
+    }
+
+    let name = name.unwrap_or_default();
+
+    let body = match filename.is_some() && body.len() > limits.memory_threshold {
+        true => {
+            let path = limits.temp_dir.join(format!("upload-{:016x}", rand::thread_rng().gen::<u64>()));
+            fs::write(&path, body).map_err(|_| RequestParseError::Multipart)?;
+            PartBody::File(path)
+        },
+        false => PartBody::Memory(body.to_vec())
+    };
+
+    Ok(Part {
+        name,
+        filename,
+        content_type: headers.get("content-type").cloned(),
+        headers,
+        body
+    })
+}
+
+fn parse_headers(head: &[u8]) -> Result<HashMap<String, String>, RequestParseError> {
+    let head = std::str::from_utf8(head).map_err(|_| RequestParseError::Multipart)?;
+
+    head.split("\r\n")
+        .map(|line| {
+            let (name, value) = line.split_once(':').ok_or(RequestParseError::Multipart)?;
+            Ok((name.trim().to_ascii_lowercase(), value.trim().to_string()))
+        })
+        .collect()
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|window| window == needle)
+}
+
+pub struct MultipartBuilder {
+    subtype: String,
+    boundary: String,
+    readers: Vec<Box<dyn Read + Send>>
+}
+
+impl MultipartBuilder {
+    pub fn new(subtype: &str) -> Self {
+        Self {
+            subtype: subtype.to_string(),
+            boundary: format!("boundary-{:016x}", rand::thread_rng().gen::<u64>()),
+            readers: Vec::new()
+        }
+    }
+
+    pub fn mixed() -> Self {
+        Self::new("mixed")
+    }
+
+    pub fn form_data() -> Self {
+        Self::new("form-data")
+    }
+
+    pub fn boundary(&self) -> &str {
+        &self.boundary
+    }
+
+    pub fn content_type(&self) -> String {
+        format!("multipart/{}; boundary={}", self.subtype, self.boundary)
+    }
+
+    pub fn part(mut self, headers: &[(&str, &str)], body: impl Read + Send + 'static) -> Self {
+        let mut head = format!("--{}\r\n", self.boundary);
+
+        for (name, value) in headers {
+            head.push_str(&format!("{name}: {value}\r\n"));
+        }
+
+        head.push_str("\r\n");
+        self.readers.push(Box::new(Cursor::new(head.into_bytes())));
+        self.readers.push(Box::new(body));
+        self.readers.push(Box::new(Cursor::new(b"\r\n".to_vec())));
+        self
+    }
+
+    pub fn bytes(self, content_type: &str, body: impl Into<Vec<u8>>) -> Self {
+        self.part(&[("Content-Type", content_type)], Cursor::new(body.into()))
+    }
+
+    pub fn field(self, name: &str, value: &str) -> Self {
+        let disposition = format!("form-data; name=\"{name}\"");
+        self.part(&[("Content-Disposition", &disposition)], Cursor::new(value.as_bytes().to_vec()))
+    }
+
+    pub fn file(self, name: &str, path: impl AsRef<Path>, content_type: &str) -> io::Result<Self> {
+        let path = path.as_ref();
+        let filename = path.file_name().map(|filename| filename.to_string_lossy()).unwrap_or_default();
+        let disposition = format!("form-data; name=\"{name}\"; filename=\"{filename}\"");
+        let file = File::open(path)?;
+        Ok(self.part(&[("Content-Disposition", &disposition), ("Content-Type", content_type)], file))
+    }
+
+    pub fn into_response(mut self, status: impl Into<StatusCode>) -> Response {
+        let content_type = self.content_type();
+        self.readers.push(Box::new(Cursor::new(format!("--{}--\r\n", self.boundary).into_bytes())));
+        Response::stream(PartStream(self.readers.into()), &content_type, status)
+    }
+}
+
+struct PartStream(VecDeque<Box<dyn Read + Send>>);
+
+impl Read for PartStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while let Some(reader) = self.0.front_mut() {
+            match reader.read(buf)? {
+                0 if !buf.is_empty() => {
+                    self.0.pop_front();
+                },
+                size => return Ok(size)
+            }
+        }
+
+        Ok(0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn body(file: &[u8]) -> Vec<u8> {
+        let mut body = b"--XYZ\r\nContent-Disposition: form-data; name=\"title\"\r\n\r\nHello\r\n".to_vec();
+        body.extend_from_slice(b"--XYZ\r\nContent-Disposition: form-data; name=\"upload\"; filename=\"a.bin\"\r\nContent-Type: application/octet-stream\r\n\r\n");
+        body.extend_from_slice(file);
+        body.extend_from_slice(b"\r\n--XYZ--\r\n");
+        body
+    }
+
+    fn limits(name: &str, max_part_size: usize, memory_threshold: usize) -> MultipartLimits {
+        let temp_dir = env::temp_dir().join(format!("multipart-{name}-{:016x}", rand::thread_rng().gen::<u64>()));
+        fs::create_dir_all(&temp_dir).unwrap();
+
+        MultipartLimits {
+            max_part_size,
+            memory_threshold,
+            temp_dir
+        }
+    }
+
+    #[test]
+    fn keeps_small_parts_in_memory() {
+        let limits = limits("memory", 1024, 16);
+        let parts = parse(&body(b"0123456789"), "XYZ", &limits).unwrap();
+
+        assert_eq!(parts[0].name(), "title");
+        assert_eq!(parts[0].text().unwrap(), "Hello");
+        assert_eq!(parts[1].filename(), Some("a.bin"));
+        assert!(matches!(parts[1].body(), PartBody::Memory(bytes) if bytes == b"0123456789"));
+        assert_eq!(fs::read_dir(&limits.temp_dir).unwrap().count(), 0);
+
+        fs::remove_dir_all(&limits.temp_dir).ok();
+    }
+
+    #[test]
+    fn spills_large_files_to_the_temp_dir() {
+        let limits = limits("spill", 1024, 16);
+        let file = [7_u8; 100];
+        let mut parts = parse(&body(&file), "XYZ", &limits).unwrap();
+        let upload = parts.pop().unwrap();
+
+        let PartBody::File(path) = upload.body() else {
+            panic!("upload was kept in memory");
+        };
+
+        let path = path.clone();
+        assert!(path.starts_with(&limits.temp_dir));
+        assert_eq!(upload.bytes().unwrap(), file);
+
+        drop(upload);
+        assert!(!path.exists());
+
+        let persisted = limits.temp_dir.join("kept.bin");
+        parse(&body(&file), "XYZ", &limits).unwrap().pop().unwrap().persist(&persisted).unwrap();
+        assert_eq!(fs::read(&persisted).unwrap(), file);
+        assert_eq!(fs::read_dir(&limits.temp_dir).unwrap().count(), 1);
+
+        fs::remove_dir_all(&limits.temp_dir).ok();
+    }
+
+    #[test]
+    fn rejects_parts_over_the_size_limit() {
+        let limits = limits("limit", 64, 16);
+
+        assert!(parse(&body(&[0; 64]), "XYZ", &limits).is_ok());
+        assert!(matches!(parse(&body(&[0; 65]), "XYZ", &limits), Err(RequestParseError::Multipart)));
+        assert_eq!(fs::read_dir(&limits.temp_dir).unwrap().count(), 0);
+
+        fs::remove_dir_all(&limits.temp_dir).ok();
+    }
+}
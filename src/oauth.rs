@@ -0,0 +1,349 @@
+use std::error::Error;
+use std::fmt::{self, Debug, Display, Formatter};
+use std::sync::Arc;
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use url::{form_urlencoded, Url};
+use crate::client::Client;
+use crate::message::{Request, Response};
+use crate::method::HttpMethod;
+
+pub const STATE_KEY: &str = "oauth_state";
+pub const VERIFIER_KEY: &str = "oauth_code_verifier";
+pub const TOKEN_KEY: &str = "oauth_token";
+
+#[derive(Clone)]
+pub struct OAuthClient {
+    client_id: String,
+    client_secret: Option<String>,
+    authorization_url: Url,
+    token_url: Url,
+    redirect_url: String,
+    scopes: Vec<String>,
+    http: Arc<Client>
+}
+
+#[derive(Debug, Clone)]
+pub struct AuthorizationRequest {
+    pub url: Url,
+    pub state: String,
+    pub code_verifier: String
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TokenResponse {
+    pub access_token: String,
+    pub token_type: String,
+    pub expires_in: Option<u64>,
+    pub refresh_token: Option<String>,
+    pub id_token: Option<String>,
+    pub scope: Option<String>
+}
+
+#[derive(Debug)]
+pub enum OAuthError {
+    State,
+    Denied(String),
+    MissingCode,
+    Session,
+    Exchange(String),
+    Token,
+    Scheme
+}
+
+impl Display for OAuthError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::State => write!(f, "The authorization state does not match"),
+            Self::Denied(reason) => write!(f, "The authorization was denied: {reason}"),
+            Self::MissingCode => write!(f, "The authorization response has no code"),
+            Self::Session => write!(f, "The request has no session to keep the login state in"),
+            Self::Exchange(reason) => write!(f, "Failed to exchange the authorization code: {reason}"),
+            Self::Token => write!(f, "Failed to parse the token response"),
+            Self::Scheme => write!(f, "The token endpoint must be an http url, the client has no TLS connector")
+        }
+    }
+}
+
+impl Error for OAuthError {}
+
+impl OAuthClient {
+    pub fn new(client_id: &str, authorization_url: Url, token_url: Url, redirect_url: &str) -> Result<Self, OAuthError> {
+        if token_url.scheme() != "http" {
+            return Err(OAuthError::Scheme);
+        }
+
+        Ok(Self {
+            client_id: client_id.to_string(),
+            client_secret: None,
+            authorization_url,
+            token_url,
+            redirect_url: redirect_url.to_string(),
+            scopes: Vec::new(),
+            http: Arc::new(Client::new())
+        })
+    }
+
+    pub fn http_client(mut self, client: Client) -> Self {
+        self.http = Arc::new(client);
+        self
+    }
+
+    pub fn client_secret(mut self, secret: &str) -> Self {
+        self.client_secret = Some(secret.to_string());
+        self
+    }
+
+    pub fn scope(mut self, scope: &str) -> Self {
+        self.scopes.push(scope.to_string());
+        self
+    }
+
+    pub fn authorize(&self) -> AuthorizationRequest {
+        let state = random_token();
+        let code_verifier = random_token();
+        let challenge = URL_SAFE_NO_PAD.encode(Sha256::digest(code_verifier.as_bytes()));
+        let mut url = self.authorization_url.clone();
+
+        url.query_pairs_mut()
+            .append_pair("response_type", "code")
+            .append_pair("client_id", &self.client_id)
+            .append_pair("redirect_uri", &self.redirect_url)
+            .append_pair("scope", &self.scopes.join(" "))
+            .append_pair("state", &state)
+            .append_pair("code_challenge", &challenge)
+            .append_pair("code_challenge_method", "S256");
+
+        AuthorizationRequest {
+            url,
+            state,
+            code_verifier
+        }
+    }
+
+    pub fn login(&self, request: &Request) -> Result<Response, OAuthError> {
+        let session = request.session().ok_or(OAuthError::Session)?;
+        let authorization = self.authorize();
+
+        session.set(STATE_KEY, &authorization.state).map_err(|_| OAuthError::Session)?;
+        session.set(VERIFIER_KEY, &authorization.code_verifier).map_err(|_| OAuthError::Session)?;
+        Ok(authorization.redirect())
+    }
+
+    pub fn complete(&self, request: &Request) -> Result<TokenResponse, OAuthError> {
+        let session = request.session().ok_or(OAuthError::Session)?;
+        let state: String = session.get(STATE_KEY).ok_or(OAuthError::State)?;
+        let code_verifier: String = session.get(VERIFIER_KEY).ok_or(OAuthError::State)?;
+        session.remove(STATE_KEY);
+        session.remove(VERIFIER_KEY);
+
+        let code = self.callback(request, &state)?;
+        let token = self.exchange(&code, &code_verifier)?;
+        session.regenerate();
+        session.set(TOKEN_KEY, &token).map_err(|_| OAuthError::Session)?;
+        Ok(token)
+    }
+
+    pub fn callback(&self, request: &Request, state: &str) -> Result<String, OAuthError> {
+        let mut code = None;
+        let mut returned_state = None;
+
+        for (key, value) in request.url().query_pairs() {
+            match key.as_ref() {
+                "code" => code = Some(value.into_owned()),
+                "state" => returned_state = Some(value.into_owned()),
+                "error" => return Err(OAuthError::Denied(value.into_owned())),
+                _ => ()
+            }
+        }
+
+        if returned_state.as_deref() != Some(state) {
+            return Err(OAuthError::State);
+        }
+
+        code.ok_or(OAuthError::MissingCode)
+    }
+
+    pub fn token_request(&self, code: &str, code_verifier: &str) -> (Url, String) {
+        let mut body = form_urlencoded::Serializer::new(String::new());
+
+        body.append_pair("grant_type", "authorization_code")
+            .append_pair("code", code)
+            .append_pair("redirect_uri", &self.redirect_url)
+            .append_pair("client_id", &self.client_id)
+            .append_pair("code_verifier", code_verifier);
+
+        if let Some(secret) = &self.client_secret {
+            body.append_pair("client_secret", secret);
+        }
+
+        (self.token_url.clone(), body.finish())
+    }
+
+    pub fn exchange(&self, code: &str, code_verifier: &str) -> Result<TokenResponse, OAuthError> {
+        let (url, body) = self.token_request(code, code_verifier);
+        let headers = [("content-type", "application/x-www-form-urlencoded"), ("accept", "application/json")];
+
+        let response = self.http.request(HttpMethod::Post, url.as_str(), &headers, body.into_bytes())
+            .map_err(|err| OAuthError::Exchange(err.to_string()))?;
+
+        if response.status() != 200 {
+            return Err(OAuthError::Exchange(format!("the token endpoint answered {}", response.status())));
+        }
+
+        Self::parse_token(response.body())
+    }
+
+    pub fn parse_token(body: &[u8]) -> Result<TokenResponse, OAuthError> {
+        serde_json::from_slice(body).map_err(|_| OAuthError::Token)
+    }
+}
+
+impl Debug for OAuthClient {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("OAuthClient")
+            .field("client_id", &self.client_id)
+            .field("authorization_url", &self.authorization_url)
+            .field("token_url", &self.token_url)
+            .field("redirect_url", &self.redirect_url)
+            .field("scopes", &self.scopes)
+            .finish()
+    }
+}
+
+impl AuthorizationRequest {
+    pub fn redirect(&self) -> Response {
+        Response::redirect(self.url.as_str(), 302)
+    }
+}
+
+fn random_token() -> String {
+    let mut bytes = [0_u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::{BufRead, BufReader, Read, Write};
+    use std::net::{SocketAddr, TcpListener};
+    use std::thread;
+    use std::time::Duration;
+    use crate::session::{MemoryStore, Session, SessionData, SessionStore, Sessions};
+    use super::*;
+
+    fn token_endpoint(response: String) -> (Url, thread::JoinHandle<String>) {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = Url::parse(&format!("http://{}/token", listener.local_addr().unwrap())).unwrap();
+
+        let handle = thread::spawn(move || {
+            let (stream, _) = listener.accept().unwrap();
+            let mut reader = BufReader::new(stream);
+            let mut head = String::new();
+
+            while reader.read_line(&mut head).unwrap() > 2 && !head.ends_with("\r\n\r\n") {}
+
+            let length = head.lines()
+                .find_map(|line| line.to_ascii_lowercase().strip_prefix("content-length:").map(|length| length.trim().parse::<usize>().unwrap()))
+                .unwrap_or(0);
+
+            let mut body = vec![0; length];
+            reader.read_exact(&mut body).unwrap();
+            reader.into_inner().write_all(response.as_bytes()).unwrap();
+            String::from_utf8(body).unwrap()
+        });
+
+        (url, handle)
+    }
+
+    fn request(target: &str, session: &Session) -> Request {
+        let bytes = format!("GET {target} HTTP/1.1\r\nHost: localhost\r\n\r\n");
+        let mut request = Request::from_bytes(SocketAddr::from(([127, 0, 0, 1], 1234)), bytes.as_bytes()).unwrap();
+        request.set_session(session.clone());
+        request
+    }
+
+    fn client(token_url: Url) -> OAuthClient {
+        OAuthClient::new("app", Url::parse("http://idp.test/authorize").unwrap(), token_url, "http://localhost/callback").unwrap()
+    }
+
+    #[test]
+    fn exchanges_the_code_and_fills_the_session() {
+        let id_token = format!("e30.{}.sig", URL_SAFE_NO_PAD.encode(r#"{"sub":"user-42"}"#));
+        let json = format!(r#"{{"access_token":"at","token_type":"Bearer","id_token":"{id_token}"}}"#);
+        let (token_url, endpoint) = token_endpoint(format!("HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\n\r\n{json}", json.len()));
+        let oauth = client(token_url);
+        let session = Session::default();
+
+        let redirect = oauth.login(&request("/login", &session)).unwrap();
+        assert_eq!(redirect.status(), 302);
+
+        let state: String = session.get(STATE_KEY).unwrap();
+        let verifier: String = session.get(VERIFIER_KEY).unwrap();
+        let token = oauth.complete(&request(&format!("/callback?code=abc&state={state}"), &session)).unwrap();
+        let form = endpoint.join().unwrap();
+
+        assert_eq!(token.access_token, "at");
+        assert!(form.contains("grant_type=authorization_code") && form.contains("code=abc"));
+        assert!(form.contains(&format!("code_verifier={verifier}")));
+        assert_eq!(session.get::<TokenResponse>(TOKEN_KEY).unwrap().access_token, "at");
+        assert!(session.get::<String>(STATE_KEY).is_none());
+    }
+
+    #[test]
+    fn rotates_the_session_on_login() {
+        let json = r#"{"access_token":"at","token_type":"Bearer"}"#;
+        let (token_url, endpoint) = token_endpoint(format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\n\r\n{json}", json.len()));
+        let oauth = client(token_url);
+
+        let store = MemoryStore::new();
+        store.save("fixed", &SessionData::new(), Duration::from_secs(60));
+        let sessions = Sessions::new(store);
+        let cookie = Request::from_bytes(SocketAddr::from(([127, 0, 0, 1], 1234)), b"GET / HTTP/1.1\r\nHost: localhost\r\nCookie: session=fixed\r\n\r\n").unwrap();
+        let session = sessions.load(&cookie);
+        assert_eq!(session.id().as_deref(), Some("fixed"));
+
+        oauth.login(&request("/login", &session)).unwrap();
+        let state: String = session.get(STATE_KEY).unwrap();
+        oauth.complete(&request(&format!("/callback?code=abc&state={state}"), &session)).unwrap();
+        endpoint.join().unwrap();
+
+        let mut response = Response::new(302);
+        sessions.commit(&session, &mut response);
+
+        let id = session.id().unwrap();
+        assert_ne!(id, "fixed");
+        assert!(sessions.load(&cookie).get::<TokenResponse>(TOKEN_KEY).is_none());
+        assert!(response.cookies().iter().any(|cookie| cookie.name() == "session" && cookie.value() == id));
+    }
+
+    #[test]
+    fn rejects_token_endpoints_the_client_cannot_reach() {
+        let result = OAuthClient::new("app", Url::parse("https://idp.test/authorize").unwrap(), Url::parse("https://idp.test/token").unwrap(), "http://localhost/callback");
+        assert!(matches!(result, Err(OAuthError::Scheme)));
+    }
+
+    #[test]
+    fn rejects_a_callback_with_another_state() {
+        let oauth = client(Url::parse("http://127.0.0.1:9/token").unwrap());
+        let session = Session::default();
+        oauth.login(&request("/login", &session)).unwrap();
+
+        let result = oauth.complete(&request("/callback?code=abc&state=forged", &session));
+        assert!(matches!(result, Err(OAuthError::State)));
+        assert!(session.get::<TokenResponse>(TOKEN_KEY).is_none());
+    }
+
+    #[test]
+    fn reports_token_endpoint_errors() {
+        let body = r#"{"error":"invalid_grant"}"#;
+        let (token_url, endpoint) = token_endpoint(format!("HTTP/1.1 400 Bad Request\r\nContent-Length: {}\r\n\r\n{body}", body.len()));
+        let result = client(token_url).exchange("abc", "verifier");
+        endpoint.join().unwrap();
+
+        assert!(matches!(result, Err(OAuthError::Exchange(_))));
+    }
+}
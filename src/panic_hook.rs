@@ -0,0 +1,82 @@
+use std::cell::RefCell;
+use std::panic::{self, PanicHookInfo};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+use log::error;
+use crate::message::Request;
+
+static NEXT_REQUEST_ID: AtomicU64 = AtomicU64::new(1);
+
+thread_local! {
+    static ACTIVE: RefCell<Option<ActiveRequest>> = const { RefCell::new(None) };
+}
+
+#[derive(Debug, Clone)]
+pub struct ActiveRequest {
+    pub id: u64,
+    pub client: String,
+    pub method: String,
+    pub target: String
+}
+
+pub struct ActiveGuard {
+    previous: Option<ActiveRequest>
+}
+
+impl Drop for ActiveGuard {
+    fn drop(&mut self) {
+        let previous = self.previous.take();
+        ACTIVE.with(|active| *active.borrow_mut() = previous);
+    }
+}
+
+pub fn track(request: &Request) -> ActiveGuard {
+    resume(ActiveRequest {
+        id: NEXT_REQUEST_ID.fetch_add(1, Ordering::Relaxed),
+        client: request.socket_addr().to_string(),
+        method: request.method().as_str().to_string(),
+        target: request.target().to_string()
+    })
+}
+
+pub fn resume(request: ActiveRequest) -> ActiveGuard {
+    ActiveGuard {
+        previous: ACTIVE.with(|active| active.borrow_mut().replace(request))
+    }
+}
+
+pub fn active_request() -> Option<ActiveRequest> {
+    ACTIVE.with(|active| active.borrow().clone())
+}
+
+pub fn active_request_id() -> Option<u64> {
+    ACTIVE.with(|active| active.borrow().as_ref().map(|request| request.id))
+}
+
+pub fn install() {
+    panic::set_hook(Box::new(report));
+}
+
+fn report(info: &PanicHookInfo<'_>) {
+    let thread = thread::current();
+    let thread = thread.name().unwrap_or("<unnamed>");
+
+    let message = match (info.payload().downcast_ref::<&str>(), info.payload().downcast_ref::<String>()) {
+        (Some(message), _) => message,
+        (_, Some(message)) => message.as_str(),
+        _ => "Box<dyn Any>"
+    };
+
+    let location = info.location().map(|location| format!("{}:{}", location.file(), location.line())).unwrap_or_default();
+
+    match active_request() {
+        Some(request) => error!(
+            "panic thread={thread} request_id={} client={} method={} target={:?} location={location} message={message:?}",
+            request.id,
+            request.client,
+            request.method,
+            request.target
+        ),
+        None => error!("panic thread={thread} location={location} message={message:?}")
+    }
+}
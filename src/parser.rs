@@ -0,0 +1,560 @@
+use std::io::{self, ErrorKind, Read, Write};
+use std::time::{Duration, Instant};
+use crate::http_server::BUFFER_SIZE;
+use crate::route::matches_prefix;
+
+pub const MAX_HEAD_SIZE: usize = 64 * 1024;
+pub const DEFAULT_BODY_LIMIT: usize = 8 * 1024 * 1024;
+
+#[derive(Debug, Clone)]
+pub struct BodyLimits {
+    default: Option<usize>,
+    scopes: Vec<(String, usize)>
+}
+
+impl Default for BodyLimits {
+    fn default() -> Self {
+        Self {
+            default: Some(DEFAULT_BODY_LIMIT),
+            scopes: Vec::new()
+        }
+    }
+}
+
+impl BodyLimits {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn limit(mut self, bytes: usize) -> Self {
+        self.default = Some(bytes);
+        self
+    }
+
+    pub fn scope(mut self, prefix: &str, bytes: usize) -> Self {
+        let prefix = format!("/{}", prefix.trim_matches('/'));
+        self.scopes.retain(|(scope, _)| *scope != prefix);
+        self.scopes.push((prefix, bytes));
+        self
+    }
+
+    pub fn for_route(&self, route: &str) -> Option<usize> {
+        self.scopes.iter()
+            .filter(|(prefix, _)| matches_prefix(route, prefix))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, bytes)| *bytes)
+            .or(self.default)
+    }
+
+    pub fn for_head(&self, head: &[u8]) -> Option<usize> {
+        let line = head.split(|byte| *byte == b'\r').next().unwrap_or_default();
+        let line = String::from_utf8_lossy(line);
+        let target = line.split(' ').nth(1).unwrap_or("/");
+        self.for_route(target.split(['?', '#']).next().unwrap_or("/"))
+    }
+}
+
+#[derive(Debug)]
+pub enum Frame {
+    Request(Vec<u8>),
+    TooLarge(Vec<u8>)
+}
+
+pub trait MemoryBudget {
+    fn reserve(&mut self, bytes: usize) -> bool;
+}
+
+impl MemoryBudget for () {
+    fn reserve(&mut self, _: usize) -> bool {
+        true
+    }
+}
+
+#[derive(Clone, Copy)]
+enum State {
+    Body(usize),
+    ChunkSize,
+    ChunkData(usize),
+    ChunkEnd,
+    Trailers
+}
+
+struct Partial {
+    state: State,
+    message: Vec<u8>,
+    head_len: usize
+}
+
+pub struct RequestReader<T: Read> {
+    inner: T,
+    buffer: Vec<u8>,
+    pending: Option<State>,
+    partial: Option<Partial>,
+    header_timeout: Option<Duration>,
+    body_timeout: Option<Duration>,
+    deadline: Option<Instant>,
+    interim: Option<Box<dyn Write + Send>>
+}
+
+impl <T: Read> RequestReader<T> {
+    pub fn new(inner: T) -> Self {
+        Self {
+            inner,
+            buffer: Vec::new(),
+            pending: None,
+            partial: None,
+            header_timeout: None,
+            body_timeout: None,
+            deadline: None,
+            interim: None
+        }
+    }
+
+    pub fn send_continue(&mut self, writer: impl Write + Send + 'static) {
+        self.interim = Some(Box::new(writer));
+    }
+
+    pub fn timeouts(&mut self, header: Option<Duration>, body: Option<Duration>) {
+        self.header_timeout = header;
+        self.body_timeout = body;
+    }
+
+    pub fn in_progress(&self) -> bool {
+        self.partial.is_some() || !self.buffer.iter().all(u8::is_ascii_whitespace)
+    }
+
+    pub fn get_ref(&self) -> &T {
+        &self.inner
+    }
+
+    pub fn get_mut(&mut self) -> &mut T {
+        &mut self.inner
+    }
+
+    pub fn buffered(&self) -> &[u8] {
+        &self.buffer
+    }
+
+    pub fn next_request(&mut self, limits: &BodyLimits, budget: &mut impl MemoryBudget) -> io::Result<Option<Frame>> {
+        self.pending = None;
+
+        let (mut state, mut message, head_len, mut expects_continue) = match self.partial.take() {
+            Some(Partial { state, message, head_len }) => (state, message, head_len, false),
+            None => match self.next_head(budget)? {
+                Some((state, message)) => {
+                    self.deadline = self.body_timeout.map(|timeout| Instant::now() + timeout);
+                    let head_len = message.len();
+                    let expects_continue = self.interim.is_some() && expects_continue(&message);
+                    (state, message, head_len, expects_continue)
+                },
+                None => return Ok(None)
+            }
+        };
+
+        let limit = limits.for_head(&message[..head_len]);
+
+        loop {
+            let exceeded = match state {
+                State::Body(remaining) | State::ChunkData(remaining) => limit.is_some_and(|limit| message.len() - head_len + remaining > limit),
+                _ => false
+            };
+
+            if exceeded {
+                message.truncate(head_len);
+                self.pending = Some(state);
+                self.deadline = None;
+                return Ok(Some(Frame::TooLarge(message)));
+            }
+
+            if expects_continue && self.buffer.is_empty() && !matches!(state, State::Body(0)) {
+                if let Some(interim) = &mut self.interim {
+                    interim.write_all(b"HTTP/1.1 100 Continue\r\n\r\n")?;
+                }
+            }
+
+            expects_continue = false;
+
+            let needed = message.len() + match state {
+                State::Body(remaining) | State::ChunkData(remaining) => remaining.max(self.buffer.len()),
+                _ => self.buffer.len()
+            };
+
+            if !budget.reserve(needed) {
+                self.deadline = None;
+                return Err(overloaded());
+            }
+
+            state = match self.step(state, &mut message) {
+                Ok(Some(state)) => state,
+                Ok(None) => {
+                    self.deadline = None;
+                    return Ok(Some(Frame::Request(message)));
+                },
+                Err(err) if matches!(err.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) => {
+                    self.partial = Some(Partial { state, message, head_len });
+                    return Err(err);
+                },
+                Err(err) => return Err(err)
+            };
+        }
+    }
+
+    pub fn pending_body(&self) -> Option<usize> {
+        match self.pending {
+            Some(State::Body(remaining)) => Some(remaining),
+            _ => None
+        }
+    }
+
+    pub fn drain(&mut self, limit: usize) -> io::Result<bool> {
+        let Some(mut state) = self.pending.take() else {
+            return Ok(true);
+        };
+
+        let mut drained = 0;
+        let mut discarded = Vec::new();
+
+        loop {
+            if matches!(state, State::Body(remaining) if drained + remaining > limit) {
+                return Ok(false);
+            }
+
+            state = match self.step(state, &mut discarded)? {
+                Some(state) => state,
+                None => return Ok(true)
+            };
+
+            drained += discarded.len();
+            discarded.clear();
+
+            if drained > limit {
+                return Ok(false);
+            }
+        }
+    }
+
+    fn next_head(&mut self, budget: &mut impl MemoryBudget) -> io::Result<Option<(State, Vec<u8>)>> {
+        loop {
+            let blank = self.buffer.iter().take_while(|byte| matches!(byte, b'\r' | b'\n')).count();
+            self.buffer.drain(..blank);
+
+            if self.deadline.is_none() && !self.buffer.is_empty() {
+                self.deadline = self.header_timeout.map(|timeout| Instant::now() + timeout);
+            }
+
+            match find(&self.buffer, b"\r\n\r\n") {
+                Some(end) => {
+                    let message: Vec<u8> = self.buffer.drain(..end + 4).collect();
+                    return Ok(Some((framing(&message)?, message)));
+                },
+                None if self.buffer.len() > MAX_HEAD_SIZE => return Err(invalid("Request head is too large")),
+                None => {
+                    if !self.fill()? {
+                        return match self.buffer.iter().all(u8::is_ascii_whitespace) {
+                            true => Ok(None),
+                            false => Err(eof())
+                        };
+                    }
+
+                    if !budget.reserve(self.buffer.len()) {
+                        self.deadline = None;
+                        return Err(overloaded());
+                    }
+                }
+            }
+        }
+    }
+
+    fn step(&mut self, state: State, message: &mut Vec<u8>) -> io::Result<Option<State>> {
+        Ok(Some(match state {
+            State::Body(0) => return Ok(None),
+            State::Body(remaining) => {
+                let taken = self.take(remaining, message)?;
+                State::Body(remaining - taken)
+            },
+            State::ChunkSize => match self.line()? {
+                Some(line) => {
+                    let size = line.split(';').next().unwrap_or("").trim_end_matches([' ', '\t']);
+
+                    if size.is_empty() || !size.bytes().all(|byte| byte.is_ascii_hexdigit()) {
+                        return Err(invalid("Malformed chunk size"));
+                    }
+
+                    match usize::from_str_radix(size, 16).map_err(|_| invalid("Malformed chunk size"))? {
+                        0 => State::Trailers,
+                        size => State::ChunkData(size)
+                    }
+                },
+                None => State::ChunkSize
+            },
+            State::ChunkData(0) => State::ChunkEnd,
+            State::ChunkData(remaining) => {
+                let taken = self.take(remaining, message)?;
+                State::ChunkData(remaining - taken)
+            },
+            State::ChunkEnd => match self.line()? {
+                Some(line) if line.is_empty() => State::ChunkSize,
+                Some(_) => return Err(invalid("Chunk is longer than its declared size")),
+                None => State::ChunkEnd
+            },
+            State::Trailers => match self.line()? {
+                Some(line) if line.is_empty() => return Ok(None),
+                _ => State::Trailers
+            }
+        }))
+    }
+
+    fn fill(&mut self) -> io::Result<bool> {
+        if self.deadline.is_some_and(|deadline| Instant::now() >= deadline) {
+            return Err(io::Error::new(ErrorKind::TimedOut, "Request was not received in time"));
+        }
+
+        let mut buffer = [0_u8; BUFFER_SIZE];
+
+        loop {
+            match self.inner.read(&mut buffer) {
+                Ok(size) => {
+                    self.buffer.extend_from_slice(&buffer[..size]);
+                    return Ok(size > 0);
+                },
+                Err(err) if err.kind() == ErrorKind::Interrupted => continue,
+                Err(err) => return Err(err)
+            }
+        }
+    }
+
+    fn take(&mut self, remaining: usize, message: &mut Vec<u8>) -> io::Result<usize> {
+        if self.buffer.is_empty() && !self.fill()? {
+            return Err(eof());
+        }
+
+        let taken = remaining.min(self.buffer.len());
+        message.extend(self.buffer.drain(..taken));
+        Ok(taken)
+    }
+
+    fn line(&mut self) -> io::Result<Option<String>> {
+        match find(&self.buffer, b"\r\n") {
+            Some(end) => {
+                let line = String::from_utf8_lossy(&self.buffer[..end]).into_owned();
+                self.buffer.drain(..end + 2);
+                Ok(Some(line))
+            },
+            None if self.buffer.len() > MAX_HEAD_SIZE => Err(invalid("Chunk line is too long")),
+            None if self.fill()? => Ok(None),
+            None => Err(eof())
+        }
+    }
+}
+
+fn framing(head: &[u8]) -> io::Result<State> {
+    let head = String::from_utf8_lossy(head);
+    let mut length = None;
+    let mut codings = Vec::new();
+
+    for line in head.split("\r\n").skip(1).filter(|line| !line.is_empty()) {
+        let (name, value) = line.split_once(':').ok_or_else(|| invalid("Malformed header line"))?;
+
+        if name.is_empty() || !name.bytes().all(is_token) {
+            return Err(invalid("Malformed header name"));
+        }
+
+        let value = value.trim();
+
+        if name.eq_ignore_ascii_case("transfer-encoding") {
+            codings.extend(value.split(',').map(|coding| coding.trim().to_ascii_lowercase()).filter(|coding| !coding.is_empty()));
+        } else if name.eq_ignore_ascii_case("content-length") {
+            if value.is_empty() || !value.bytes().all(|byte| byte.is_ascii_digit()) {
+                return Err(invalid("Malformed Content-Length"));
+            }
+
+            let value = value.parse::<usize>().map_err(|_| invalid("Malformed Content-Length"))?;
+
+            if length.is_some_and(|length| length != value) {
+                return Err(invalid("Conflicting Content-Length headers"));
+            }
+
+            length = Some(value);
+        }
+    }
+
+    if codings.is_empty() {
+        return Ok(State::Body(length.unwrap_or(0)));
+    }
+
+    if length.is_some() {
+        return Err(invalid("Request has both Transfer-Encoding and Content-Length"));
+    }
+
+    match codings.last().map(String::as_str) {
+        Some("chunked") if codings.iter().filter(|coding| *coding == "chunked").count() == 1 => Ok(State::ChunkSize),
+        _ => Err(invalid("Transfer-Encoding must end with chunked"))
+    }
+}
+
+fn is_token(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || b"!#$%&'*+-.^_`|~".contains(&byte)
+}
+
+fn expects_continue(head: &[u8]) -> bool {
+    let head = String::from_utf8_lossy(head);
+    let mut lines = head.split("\r\n");
+
+    if !lines.next().is_some_and(|line| line.ends_with("HTTP/1.1")) {
+        return false;
+    }
+
+    lines.filter_map(|line| line.split_once(':'))
+        .any(|(name, value)| name.trim().eq_ignore_ascii_case("expect") && value.trim().eq_ignore_ascii_case("100-continue"))
+}
+
+fn find(haystack: &[u8], needle: &[u8]) -> Option<usize> {
+    haystack.windows(needle.len()).position(|window| window == needle)
+}
+
+fn invalid(message: &str) -> io::Error {
+    io::Error::new(ErrorKind::InvalidData, message.to_string())
+}
+
+fn overloaded() -> io::Error {
+    io::Error::new(ErrorKind::OutOfMemory, "Request does not fit in the memory budget")
+}
+
+fn eof() -> io::Error {
+    io::Error::new(ErrorKind::UnexpectedEof, "Connection closed in the middle of a request")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+    use super::*;
+
+    struct Segments(VecDeque<Vec<u8>>);
+
+    impl Read for Segments {
+        fn read(&mut self, buffer: &mut [u8]) -> io::Result<usize> {
+            let Some(mut segment) = self.0.pop_front() else {
+                return Ok(0);
+            };
+
+            let size = segment.len().min(buffer.len());
+            buffer[..size].copy_from_slice(&segment[..size]);
+
+            if size < segment.len() {
+                self.0.push_front(segment.split_off(size));
+            }
+
+            Ok(size)
+        }
+    }
+
+    fn reader(segments: &[&[u8]]) -> RequestReader<Segments> {
+        RequestReader::new(Segments(segments.iter().map(|segment| segment.to_vec()).collect()))
+    }
+
+    fn frames(segments: &[&[u8]]) -> io::Result<Vec<Vec<u8>>> {
+        let mut reader = reader(segments);
+        let mut frames = Vec::new();
+
+        while let Some(frame) = reader.next_request(&BodyLimits::new().limit(usize::MAX), &mut ())? {
+            match frame {
+                Frame::Request(data) => frames.push(data),
+                Frame::TooLarge(_) => panic!("unexpected TooLarge frame")
+            }
+        }
+
+        Ok(frames)
+    }
+
+    fn rejected(request: &[u8]) -> bool {
+        frames(&[request]).is_err_and(|err| err.kind() == ErrorKind::InvalidData)
+    }
+
+    struct Cap(usize);
+
+    impl MemoryBudget for Cap {
+        fn reserve(&mut self, bytes: usize) -> bool {
+            bytes <= self.0
+        }
+    }
+
+    #[test]
+    fn sheds_bodies_over_the_memory_budget_before_reading_them() {
+        let head = b"POST /a HTTP/1.1\r\nHost: x\r\nContent-Length: 1000\r\n\r\n";
+        let err = reader(&[head]).next_request(&BodyLimits::default(), &mut Cap(256)).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::OutOfMemory);
+
+        let chunked = b"POST /a HTTP/1.1\r\nHost: x\r\nTransfer-Encoding: chunked\r\n\r\n";
+        let chunk = format!("200\r\n{}\r\n", "a".repeat(0x200));
+        let err = reader(&[chunked, chunk.as_bytes()]).next_request(&BodyLimits::default(), &mut Cap(256)).unwrap_err();
+        assert_eq!(err.kind(), ErrorKind::OutOfMemory);
+    }
+
+    #[test]
+    fn limits_bodies_by_default() {
+        let head = format!("POST /a HTTP/1.1\r\nHost: x\r\nContent-Length: {}\r\n\r\n", DEFAULT_BODY_LIMIT + 1);
+        let frame = reader(&[head.as_bytes()]).next_request(&BodyLimits::default(), &mut ()).unwrap();
+        assert!(matches!(frame, Some(Frame::TooLarge(_))));
+    }
+
+    #[test]
+    fn reads_requests_split_across_segments() {
+        let request = b"POST /a HTTP/1.1\r\nHost: x\r\nContent-Length: 5\r\n\r\nhello";
+        let segments: Vec<&[u8]> = request.chunks(1).collect();
+        assert_eq!(frames(&segments).unwrap(), vec![request.to_vec()]);
+    }
+
+    #[test]
+    fn reads_bodies_filling_whole_buffers() {
+        let head = format!("POST /a HTTP/1.1\r\nHost: x\r\nContent-Length: {}\r\n\r\n", 2 * BUFFER_SIZE);
+        let body = vec![b'a'; 2 * BUFFER_SIZE];
+        let frames = frames(&[head.as_bytes(), &body[..BUFFER_SIZE], &body[BUFFER_SIZE..]]).unwrap();
+
+        assert_eq!(frames.len(), 1);
+        assert_eq!(frames[0].len(), head.len() + body.len());
+    }
+
+    #[test]
+    fn reads_chunked_bodies_with_trailers() {
+        let request = b"POST /a HTTP/1.1\r\nHost: x\r\nTransfer-Encoding: chunked\r\n\r\n5;ext=1\r\nhello\r\n6\r\n world\r\n0\r\nX-Trailer: 1\r\n\r\nGET /b HTTP/1.1\r\nHost: x\r\n\r\n";
+        let frames = frames(&[request]).unwrap();
+
+        assert_eq!(frames.len(), 2);
+        assert!(frames[0].ends_with(b"\r\n\r\nhello world"));
+        assert!(frames[1].starts_with(b"GET /b "));
+    }
+
+    #[test]
+    fn reads_pipelined_requests() {
+        let request = b"GET /a HTTP/1.1\r\nHost: x\r\n\r\nPOST /b HTTP/1.1\r\nHost: x\r\nContent-Length: 2\r\n\r\nhiGET /c HTTP/1.1\r\nHost: x\r\n\r\n";
+        let frames = frames(&[request]).unwrap();
+        let lines: Vec<&[u8]> = frames.iter().map(|frame| &frame[..6]).collect();
+
+        assert_eq!(lines, vec![&b"GET /a"[..], b"POST /", b"GET /c"]);
+        assert!(frames[1].ends_with(b"\r\n\r\nhi"));
+    }
+
+    #[test]
+    fn rejects_transfer_encoding_with_content_length() {
+        assert!(rejected(b"POST /a HTTP/1.1\r\nHost: x\r\nContent-Length: 4\r\nTransfer-Encoding: chunked\r\n\r\n0\r\n\r\n"));
+    }
+
+    #[test]
+    fn rejects_transfer_encoding_without_final_chunked() {
+        assert!(rejected(b"POST /a HTTP/1.1\r\nHost: x\r\nTransfer-Encoding: gzip\r\n\r\nGET /smuggled HTTP/1.1\r\n\r\n"));
+        assert!(rejected(b"POST /a HTTP/1.1\r\nHost: x\r\nTransfer-Encoding: chunked, gzip\r\n\r\n0\r\n\r\n"));
+        assert!(rejected(b"POST /a HTTP/1.1\r\nHost: x\r\nTransfer-Encoding: chunked\r\nTransfer-Encoding: chunked\r\n\r\n0\r\n\r\n"));
+    }
+
+    #[test]
+    fn rejects_malformed_framing_headers() {
+        assert!(rejected(b"POST /a HTTP/1.1\r\nHost: x\r\nContent-Length : 4\r\n\r\nbody"));
+        assert!(rejected(b"POST /a HTTP/1.1\r\nHost: x\r\n Transfer-Encoding: chunked\r\n\r\n0\r\n\r\n"));
+        assert!(rejected(b"POST /a HTTP/1.1\r\nHost: x\r\nContent-Length: +4\r\n\r\nbody"));
+    }
+
+    #[test]
+    fn rejects_signed_chunk_sizes() {
+        assert!(rejected(b"POST /a HTTP/1.1\r\nHost: x\r\nTransfer-Encoding: chunked\r\n\r\n+5\r\nhello\r\n0\r\n\r\n"));
+    }
+}
@@ -0,0 +1,235 @@
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::{Arc, Condvar, Mutex};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+use log::error;
+use serde::Serialize;
+use crate::priority::Priority;
+use crate::usage;
+
+type Task = Box<dyn FnOnce() + Send + 'static>;
+
+thread_local! {
+    static CURRENT_WORKER: RefCell<Option<Arc<WorkerStats>>> = const { RefCell::new(None) };
+}
+
+pub(crate) fn record_request() {
+    CURRENT_WORKER.with(|worker| {
+        if let Some(worker) = &*worker.borrow() {
+            worker.requests.fetch_add(1, Ordering::Relaxed);
+            worker.update_cpu_time();
+        }
+    });
+}
+
+#[derive(Default)]
+struct Lanes {
+    queues: [VecDeque<(Instant, Task)>; 3],
+    closed: bool
+}
+
+impl Lanes {
+    fn pop(&mut self) -> Option<(Instant, Task)> {
+        self.queues.iter_mut().find_map(VecDeque::pop_front)
+    }
+}
+
+#[derive(Default)]
+struct Queue {
+    lanes: Mutex<Lanes>,
+    available: Condvar
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct PoolSnapshot {
+    pub workers: u64,
+    pub busy_workers: u64,
+    pub queue_depth: u64,
+    pub completed_tasks: u64,
+    pub average_wait: Duration,
+    pub max_wait: Duration
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct WorkerSnapshot {
+    pub index: usize,
+    pub busy: bool,
+    pub tasks: u64,
+    pub requests: u64,
+    pub cpu_time: Option<Duration>
+}
+
+#[derive(Debug, Default)]
+struct WorkerStats {
+    busy: AtomicBool,
+    tasks: AtomicU64,
+    requests: AtomicU64,
+    cpu_micros: Option<AtomicU64>
+}
+
+impl WorkerStats {
+    fn snapshot(&self, index: usize) -> WorkerSnapshot {
+        WorkerSnapshot {
+            index,
+            busy: self.busy.load(Ordering::Relaxed),
+            tasks: self.tasks.load(Ordering::Relaxed),
+            requests: self.requests.load(Ordering::Relaxed),
+            cpu_time: self.cpu_micros.as_ref().map(|micros| Duration::from_micros(micros.load(Ordering::Relaxed)))
+        }
+    }
+
+    fn update_cpu_time(&self) {
+        if let (Some(micros), Some(time)) = (&self.cpu_micros, usage::thread_cpu_time()) {
+            micros.store(time.as_micros() as u64, Ordering::Relaxed);
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct PoolStats {
+    worker_stats: Mutex<Vec<Arc<WorkerStats>>>,
+    workers: AtomicU64,
+    busy_workers: AtomicU64,
+    queue_depth: AtomicU64,
+    started_tasks: AtomicU64,
+    completed_tasks: AtomicU64,
+    total_wait_micros: AtomicU64,
+    max_wait_micros: AtomicU64
+}
+
+impl PoolStats {
+    pub fn snapshot(&self) -> PoolSnapshot {
+        let started = self.started_tasks.load(Ordering::Relaxed);
+        let total_wait = self.total_wait_micros.load(Ordering::Relaxed);
+
+        PoolSnapshot {
+            workers: self.workers.load(Ordering::Relaxed),
+            busy_workers: self.busy_workers.load(Ordering::Relaxed),
+            queue_depth: self.queue_depth.load(Ordering::Relaxed),
+            completed_tasks: self.completed_tasks.load(Ordering::Relaxed),
+            average_wait: Duration::from_micros(total_wait.checked_div(started).unwrap_or(0)),
+            max_wait: Duration::from_micros(self.max_wait_micros.load(Ordering::Relaxed))
+        }
+    }
+
+    pub fn queue_depth(&self) -> usize {
+        self.queue_depth.load(Ordering::Relaxed) as usize
+    }
+
+    pub fn workers(&self) -> Vec<WorkerSnapshot> {
+        self.worker_stats.lock().unwrap().iter()
+            .enumerate()
+            .map(|(index, worker)| worker.snapshot(index))
+            .collect()
+    }
+
+    fn register(&self) -> Arc<WorkerStats> {
+        let worker = Arc::new(WorkerStats {
+            cpu_micros: usage::thread_cpu_time().map(|_| AtomicU64::new(0)),
+            ..WorkerStats::default()
+        });
+
+        self.worker_stats.lock().unwrap().push(worker.clone());
+        worker
+    }
+
+    fn started(&self, wait: Duration) {
+        let wait = wait.as_micros() as u64;
+        self.queue_depth.fetch_sub(1, Ordering::Relaxed);
+        self.busy_workers.fetch_add(1, Ordering::Relaxed);
+        self.started_tasks.fetch_add(1, Ordering::Relaxed);
+        self.total_wait_micros.fetch_add(wait, Ordering::Relaxed);
+        self.max_wait_micros.fetch_max(wait, Ordering::Relaxed);
+    }
+
+    fn finished(&self) {
+        self.busy_workers.fetch_sub(1, Ordering::Relaxed);
+        self.completed_tasks.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+pub struct ThreadPool {
+    queue: Arc<Queue>,
+    stats: Arc<PoolStats>
+}
+
+impl ThreadPool {
+    pub fn new(workers: usize, stats: Arc<PoolStats>, name: &str) -> Self {
+        let queue = Arc::new(Queue::default());
+        stats.workers.store(workers.max(1) as u64, Ordering::Relaxed);
+
+        for index in 0..workers.max(1) {
+            let queue = queue.clone();
+            let stats = stats.clone();
+            let worker = stats.register();
+            let builder = thread::Builder::new().name(format!("{name}-worker-{index}"));
+
+            builder.spawn(move || {
+                CURRENT_WORKER.with(|current| *current.borrow_mut() = Some(worker.clone()));
+
+                loop {
+                    let next = {
+                        let mut lanes = queue.available.wait_while(queue.lanes.lock().unwrap(), |lanes| {
+                            !lanes.closed && lanes.queues.iter().all(VecDeque::is_empty)
+                        }).unwrap();
+
+                        lanes.pop()
+                    };
+
+                    let Some((queued, task)) = next else {
+                        break;
+                    };
+
+                    stats.started(queued.elapsed());
+                    worker.busy.store(true, Ordering::Relaxed);
+
+                    if panic::catch_unwind(AssertUnwindSafe(task)).is_err() {
+                        error!("Worker task panicked");
+                    }
+
+                    worker.busy.store(false, Ordering::Relaxed);
+                    worker.tasks.fetch_add(1, Ordering::Relaxed);
+                    worker.update_cpu_time();
+                    stats.finished();
+                }
+            }).expect("Failed to spawn worker thread");
+        }
+
+        Self {
+            queue,
+            stats
+        }
+    }
+
+    pub fn execute(&self, task: impl FnOnce() + Send + 'static) {
+        self.execute_with(Priority::Normal, task);
+    }
+
+    pub fn execute_with(&self, priority: Priority, task: impl FnOnce() + Send + 'static) {
+        self.stats.queue_depth.fetch_add(1, Ordering::Relaxed);
+        self.queue.lanes.lock().unwrap().queues[priority as usize].push_back((Instant::now(), Box::new(task)));
+        self.queue.available.notify_one();
+    }
+
+    pub fn is_busy(&self) -> bool {
+        self.stats.busy_workers.load(Ordering::Relaxed) >= self.stats.workers.load(Ordering::Relaxed)
+    }
+
+    pub fn queue_depth(&self) -> usize {
+        self.stats.queue_depth()
+    }
+
+    pub fn stats(&self) -> &PoolStats {
+        &self.stats
+    }
+}
+
+impl Drop for ThreadPool {
+    fn drop(&mut self) {
+        self.queue.lanes.lock().unwrap().closed = true;
+        self.queue.available.notify_all();
+    }
+}
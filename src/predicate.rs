@@ -0,0 +1,123 @@
+use std::fmt::{self, Debug, Formatter};
+use std::sync::Arc;
+use crate::message::Request;
+
+type Matcher = Arc<dyn Fn(&Request) -> bool + Sync + Send>;
+
+#[derive(Clone)]
+pub enum Predicate {
+    Header(String, String),
+    HasHeader(String),
+    Query(String, String),
+    HasQuery(String),
+    ContentType(String),
+    Accepts(String),
+    All(Vec<Predicate>),
+    Any(Vec<Predicate>),
+    Not(Box<Predicate>),
+    Custom(Matcher)
+}
+
+impl Predicate {
+    pub fn header(name: &str, value: &str) -> Self {
+        Self::Header(name.to_string(), value.to_string())
+    }
+
+    pub fn has_header(name: &str) -> Self {
+        Self::HasHeader(name.to_string())
+    }
+
+    pub fn query(name: &str, value: &str) -> Self {
+        Self::Query(name.to_string(), value.to_string())
+    }
+
+    pub fn has_query(name: &str) -> Self {
+        Self::HasQuery(name.to_string())
+    }
+
+    pub fn content_type(media_type: &str) -> Self {
+        Self::ContentType(media_type.to_ascii_lowercase())
+    }
+
+    pub fn accepts(media_type: &str) -> Self {
+        Self::Accepts(media_type.to_ascii_lowercase())
+    }
+
+    pub fn custom(matcher: impl Fn(&Request) -> bool + Sync + Send + 'static) -> Self {
+        Self::Custom(Arc::new(matcher))
+    }
+
+    pub fn and(self, other: Predicate) -> Self {
+        match self {
+            Self::All(mut predicates) => {
+                predicates.push(other);
+                Self::All(predicates)
+            },
+            predicate => Self::All(vec![predicate, other])
+        }
+    }
+
+    pub fn or(self, other: Predicate) -> Self {
+        match self {
+            Self::Any(mut predicates) => {
+                predicates.push(other);
+                Self::Any(predicates)
+            },
+            predicate => Self::Any(vec![predicate, other])
+        }
+    }
+
+    pub fn negate(self) -> Self {
+        Self::Not(Box::new(self))
+    }
+
+    pub fn matches(&self, request: &Request) -> bool {
+        match self {
+            Self::Header(name, value) => request.header_all(name).iter().any(|given| given.trim().eq_ignore_ascii_case(value)),
+            Self::HasHeader(name) => request.header(name).is_some(),
+            Self::Query(name, value) => request.query_all(name).contains(&value.as_str()),
+            Self::HasQuery(name) => request.query(name).is_some(),
+            Self::ContentType(media_type) => request.header("content-type").is_some_and(|given| media_matches(media_type, essence(given))),
+            Self::Accepts(media_type) => request.headers().get_list("accept").iter().any(|entry| {
+                let mut parts = entry.split(';');
+                let range = parts.next().unwrap_or("").trim();
+                let quality = parts.find_map(|param| param.trim().strip_prefix("q=")?.parse::<f32>().ok()).unwrap_or(1.0);
+                range != "*/*" && media_matches(&range.to_ascii_lowercase(), media_type) && quality > 0.0
+            }),
+            Self::All(predicates) => predicates.iter().all(|predicate| predicate.matches(request)),
+            Self::Any(predicates) => predicates.iter().any(|predicate| predicate.matches(request)),
+            Self::Not(predicate) => !predicate.matches(request),
+            Self::Custom(matcher) => matcher(request)
+        }
+    }
+}
+
+impl Debug for Predicate {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Header(name, value) => write!(f, "Header({name:?}, {value:?})"),
+            Self::HasHeader(name) => write!(f, "HasHeader({name:?})"),
+            Self::Query(name, value) => write!(f, "Query({name:?}, {value:?})"),
+            Self::HasQuery(name) => write!(f, "HasQuery({name:?})"),
+            Self::ContentType(media_type) => write!(f, "ContentType({media_type:?})"),
+            Self::Accepts(media_type) => write!(f, "Accepts({media_type:?})"),
+            Self::All(predicates) => f.debug_tuple("All").field(predicates).finish(),
+            Self::Any(predicates) => f.debug_tuple("Any").field(predicates).finish(),
+            Self::Not(predicate) => f.debug_tuple("Not").field(predicate).finish(),
+            Self::Custom(_) => write!(f, "Custom(..)")
+        }
+    }
+}
+
+fn essence(content_type: &str) -> String {
+    content_type.split(';').next().unwrap_or("").trim().to_ascii_lowercase()
+}
+
+fn media_matches(range: &str, media_type: impl AsRef<str>) -> bool {
+    let media_type = media_type.as_ref();
+
+    match range.strip_suffix("/*") {
+        Some(kind) => media_type.split('/').next() == Some(kind),
+        None => range == media_type
+    }
+}
@@ -0,0 +1,78 @@
+use std::error::Error;
+use std::fmt::{self, Debug, Display, Formatter};
+use std::sync::Arc;
+
+type Check = Arc<dyn Fn() -> Result<(), String> + Sync + Send>;
+
+#[derive(Debug)]
+pub struct PreflightError {
+    failures: Vec<(String, String)>
+}
+
+impl PreflightError {
+    pub fn failures(&self) -> &[(String, String)] {
+        &self.failures
+    }
+}
+
+impl Display for PreflightError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{} preflight check(s) failed", self.failures.len())?;
+
+        for (name, reason) in &self.failures {
+            write!(f, "\n  - {}: {}", name, reason)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl Error for PreflightError {}
+
+#[derive(Clone, Default)]
+pub struct Preflight {
+    enabled: bool,
+    checks: Vec<(String, Check)>
+}
+
+impl Preflight {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn enabled(mut self, enabled: bool) -> Self {
+        self.enabled = enabled;
+        self
+    }
+
+    pub fn check(mut self, name: &str, check: impl Fn() -> Result<(), String> + Sync + Send + 'static) -> Self {
+        self.checks.push((name.to_string(), Arc::new(check)));
+        self
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    pub fn run(&self) -> Result<(), PreflightError> {
+        if !self.enabled {
+            return Ok(());
+        }
+
+        let failures: Vec<(String, String)> = self.checks.iter()
+            .filter_map(|(name, check)| check().err().map(|reason| (name.clone(), reason)))
+            .collect();
+
+        match failures.is_empty() {
+            true => Ok(()),
+            false => Err(PreflightError { failures })
+        }
+    }
+}
+
+impl Debug for Preflight {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let names: Vec<&str> = self.checks.iter().map(|(name, _)| name.as_str()).collect();
+        f.debug_struct("Preflight").field("enabled", &self.enabled).field("checks", &names).finish()
+    }
+}
@@ -0,0 +1,148 @@
+use std::env;
+use std::fs;
+use std::io;
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command};
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+use log::{info, warn};
+use crate::restart;
+use crate::usage::{self, ProcessUsage};
+
+pub const WORKER_VAR: &str = "HTTP_SERVER_PREFORK_WORKER";
+pub const USAGE_VAR: &str = "HTTP_SERVER_PREFORK_USAGE";
+
+const POLL_INTERVAL: Duration = Duration::from_millis(100);
+const MIN_UPTIME: Duration = Duration::from_secs(1);
+const RESPAWN_DELAY: Duration = Duration::from_secs(1);
+const USAGE_INTERVAL: Duration = Duration::from_secs(60);
+const PUBLISH_INTERVAL: Duration = Duration::from_secs(5);
+
+static WORKERS: Mutex<Vec<ProcessUsage>> = Mutex::new(Vec::new());
+
+struct Worker {
+    index: usize,
+    child: Child,
+    started: Instant
+}
+
+pub fn is_worker() -> bool {
+    env::var_os(WORKER_VAR).is_some()
+}
+
+pub fn worker_index() -> Option<usize> {
+    env::var(WORKER_VAR).ok()?.parse().ok()
+}
+
+pub fn workers_usage() -> Vec<ProcessUsage> {
+    match env::var_os(USAGE_VAR) {
+        Some(path) => fs::read(path).ok().and_then(|bytes| serde_json::from_slice(&bytes).ok()).unwrap_or_default(),
+        None => WORKERS.lock().unwrap().clone()
+    }
+}
+
+pub fn supervise(workers: usize, listeners: &[TcpListener]) -> io::Result<()> {
+    let usage_path = env::temp_dir().join(format!("http_server-prefork-{}.json", std::process::id()));
+    let result = run(workers, listeners, &usage_path);
+    fs::remove_file(&usage_path).ok();
+    result
+}
+
+fn run(workers: usize, listeners: &[TcpListener], usage_path: &Path) -> io::Result<()> {
+    let mut running = (0..workers.max(1))
+        .map(|index| spawn(index, listeners, usage_path))
+        .collect::<io::Result<Vec<Worker>>>()?;
+
+    info!("Supervising {} worker process(es)", running.len());
+    let mut reported = Instant::now();
+    let mut published = Instant::now();
+
+    while !running.is_empty() {
+        thread::sleep(POLL_INTERVAL);
+
+        if published.elapsed() >= PUBLISH_INTERVAL {
+            let usage = publish_usage(&running, usage_path);
+            published = Instant::now();
+
+            if reported.elapsed() >= USAGE_INTERVAL {
+                report_usage(&usage);
+                reported = Instant::now();
+            }
+        }
+
+        let mut exited = Vec::new();
+
+        for (position, worker) in running.iter_mut().enumerate() {
+            let Some(status) = worker.child.try_wait()? else {
+                continue;
+            };
+
+            if status.success() {
+                info!("Worker {} (pid {}) exited", worker.index, worker.child.id());
+                exited.push(position);
+                continue;
+            }
+
+            warn!("Worker {} (pid {}) exited with {}, restarting", worker.index, worker.child.id(), status);
+
+            if worker.started.elapsed() < MIN_UPTIME {
+                thread::sleep(RESPAWN_DELAY);
+            }
+
+            *worker = spawn(worker.index, listeners, usage_path)?;
+        }
+
+        for position in exited.into_iter().rev() {
+            running.remove(position);
+        }
+    }
+
+    Ok(())
+}
+
+fn publish_usage(workers: &[Worker], path: &Path) -> Vec<ProcessUsage> {
+    let usage: Vec<ProcessUsage> = workers.iter()
+        .filter_map(|worker| usage::of_process(worker.child.id()).map(|usage| ProcessUsage { worker: Some(worker.index), ..usage }))
+        .collect();
+
+    let staging = PathBuf::from(format!("{}.tmp", path.display()));
+    let written = serde_json::to_vec(&usage)
+        .map_err(io::Error::from)
+        .and_then(|json| fs::write(&staging, json))
+        .and_then(|_| fs::rename(&staging, path));
+
+    if let Err(err) = written {
+        warn!("Failed to publish worker usage to {}: {}", path.display(), err);
+    }
+
+    *WORKERS.lock().unwrap() = usage.clone();
+    usage
+}
+
+fn report_usage(usage: &[ProcessUsage]) {
+    for usage in usage {
+        info!(
+            "Worker {} (pid {}): cpu {:?}, rss {} bytes",
+            usage.worker.unwrap_or_default(),
+            usage.pid,
+            usage.cpu_time.unwrap_or_default(),
+            usage.resident_memory.unwrap_or_default()
+        );
+    }
+}
+
+fn spawn(index: usize, listeners: &[TcpListener], usage_path: &Path) -> io::Result<Worker> {
+    let mut command = Command::new(env::current_exe()?);
+    command.env(WORKER_VAR, index.to_string());
+    command.env(USAGE_VAR, usage_path);
+    let child = restart::reexec(listeners, command)?;
+    info!("Started worker {} (pid {})", index, child.id());
+
+    Ok(Worker {
+        index,
+        child,
+        started: Instant::now()
+    })
+}
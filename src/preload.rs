@@ -0,0 +1,72 @@
+use std::collections::HashMap;
+use std::fs;
+use std::io;
+use std::path::Path;
+
+#[derive(Debug, Clone, Default)]
+pub struct PreloadManifest {
+    pages: HashMap<String, Vec<String>>,
+    early_hints: bool
+}
+
+impl PreloadManifest {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn load(path: impl AsRef<Path>) -> io::Result<Self> {
+        let pages = serde_json::from_str(&fs::read_to_string(path)?)?;
+
+        Ok(Self {
+            pages,
+            early_hints: false
+        })
+    }
+
+    pub fn page(mut self, page: &str, assets: &[&str]) -> Self {
+        self.pages.entry(page.to_string()).or_default().extend(assets.iter().map(|asset| asset.to_string()));
+        self
+    }
+
+    pub fn early_hints(mut self, enabled: bool) -> Self {
+        self.early_hints = enabled;
+        self
+    }
+
+    pub fn sends_early_hints(&self) -> bool {
+        self.early_hints
+    }
+
+    pub fn link_header(&self, page: &str) -> Option<String> {
+        let assets = self.pages.get(page).filter(|assets| !assets.is_empty())?;
+
+        let links: Vec<String> = assets.iter()
+            .map(|asset| match Self::destination(asset) {
+                Some("font") => format!("<{asset}>; rel=preload; as=font; crossorigin"),
+                Some(destination) => format!("<{asset}>; rel=preload; as={destination}"),
+                None => format!("<{asset}>; rel=preload")
+            })
+            .collect();
+
+        Some(links.join(", "))
+    }
+
+    pub fn early_hints_bytes(&self, page: &str) -> Option<Vec<u8>> {
+        let link = self.link_header(page)?;
+        Some(format!("HTTP/1.1 103 Early Hints\r\nLink: {link}\r\n\r\n").into_bytes())
+    }
+
+    fn destination(asset: &str) -> Option<&'static str> {
+        let path = asset.split(['?', '#']).next().unwrap_or(asset);
+        let extension = path.rsplit('.').next().unwrap_or("").to_ascii_lowercase();
+
+        match extension.as_str() {
+            "css" => Some("style"),
+            "js" | "mjs" => Some("script"),
+            "woff" | "woff2" | "ttf" | "otf" | "eot" => Some("font"),
+            "png" | "jpg" | "jpeg" | "gif" | "webp" | "avif" | "svg" | "ico" => Some("image"),
+            "json" => Some("fetch"),
+            _ => None
+        }
+    }
+}
@@ -0,0 +1,56 @@
+use crate::route::matches_prefix;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Priority {
+    High,
+    #[default]
+    Normal,
+    Low
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Priorities {
+    scopes: Vec<(String, Priority)>,
+    shed_threshold: Option<usize>
+}
+
+impl Priorities {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn scope(mut self, prefix: &str, priority: Priority) -> Self {
+        let prefix = format!("/{}", prefix.trim_matches('/'));
+        self.scopes.retain(|(scope, _)| *scope != prefix);
+        self.scopes.push((prefix, priority));
+        self
+    }
+
+    pub fn shed_low(mut self, queue_depth: usize) -> Self {
+        self.shed_threshold = Some(queue_depth);
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.scopes.is_empty()
+    }
+
+    pub fn sheds(&self, priority: Priority, queue_depth: usize) -> bool {
+        priority == Priority::Low && self.shed_threshold.is_some_and(|threshold| queue_depth >= threshold)
+    }
+
+    pub fn for_route(&self, route: &str) -> Priority {
+        self.scopes.iter()
+            .filter(|(prefix, _)| matches_prefix(route, prefix))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, priority)| *priority)
+            .unwrap_or_default()
+    }
+
+    pub fn for_head(&self, head: &[u8]) -> Priority {
+        let line = head.split(|byte| *byte == b'\r').next().unwrap_or_default();
+        let line = String::from_utf8_lossy(line);
+        let target = line.split(' ').nth(1).unwrap_or("/");
+        self.for_route(target.split(['?', '#']).next().unwrap_or("/"))
+    }
+}
@@ -0,0 +1,58 @@
+use std::ops::Range;
+use crate::message::{Request, Response};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RangeRequest {
+    Full,
+    Partial(Range<u64>),
+    Unsatisfiable
+}
+
+pub fn parse(header: &str, length: u64) -> RangeRequest {
+    let Some(spec) = header.trim().strip_prefix("bytes=") else {
+        return RangeRequest::Full;
+    };
+
+    if spec.contains(',') {
+        return RangeRequest::Full;
+    }
+
+    let Some((start, end)) = spec.trim().split_once('-') else {
+        return RangeRequest::Full;
+    };
+
+    let (start, end) = (start.trim(), end.trim());
+
+    let range = match (start.parse::<u64>(), end.parse::<u64>()) {
+        (Ok(start), Ok(end)) if start <= end => start..(end + 1).min(length),
+        (Ok(start), Err(_)) if end.is_empty() => start..length,
+        (Err(_), Ok(suffix)) if start.is_empty() => length.saturating_sub(suffix)..length,
+        _ => return RangeRequest::Full
+    };
+
+    match range.start < range.end {
+        true => RangeRequest::Partial(range),
+        false => RangeRequest::Unsatisfiable
+    }
+}
+
+pub fn requested(request: &Request, response: &Response, length: u64) -> RangeRequest {
+    let Some(range) = request.header("range") else {
+        return RangeRequest::Full;
+    };
+
+    let fresh = match request.header("if-range").map(str::trim) {
+        None => true,
+        Some(tag) if tag.starts_with('"') => response.get_header("ETag") == Some(tag),
+        Some(date) => response.get_header("Last-Modified") == Some(date)
+    };
+
+    match fresh {
+        true => parse(range, length),
+        false => RangeRequest::Full
+    }
+}
+
+pub fn content_range(range: &Range<u64>, length: u64) -> String {
+    format!("bytes {}-{}/{length}", range.start, range.end - 1)
+}
@@ -0,0 +1,77 @@
+use std::collections::HashMap;
+use std::fmt::{self, Debug, Formatter};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use crate::error::ServerError;
+use crate::message::{Request, Response};
+use crate::middleware::Next;
+use crate::throttle::TokenBucket;
+
+const SWEEP_THRESHOLD: usize = 10_000;
+
+type KeyExtractor = Arc<dyn Fn(&Request) -> Option<String> + Sync + Send>;
+
+#[derive(Clone)]
+pub struct RateLimiter {
+    rate: f64,
+    burst: u64,
+    key: KeyExtractor,
+    buckets: Arc<Mutex<HashMap<String, TokenBucket>>>
+}
+
+impl RateLimiter {
+    pub fn new(requests: u64, per: Duration) -> Self {
+        Self {
+            rate: requests as f64 / per.as_secs_f64().max(f64::EPSILON),
+            burst: requests.max(1),
+            key: Arc::new(|request| Some(request.socket_addr().ip().to_string())),
+            buckets: Arc::new(Mutex::new(HashMap::new()))
+        }
+    }
+
+    pub fn burst(mut self, burst: u64) -> Self {
+        self.burst = burst.max(1);
+        self
+    }
+
+    pub fn key(mut self, key: impl Fn(&Request) -> Option<String> + Sync + Send + 'static) -> Self {
+        self.key = Arc::new(key);
+        self
+    }
+
+    pub fn check(&self, request: &Request) -> Result<(), Duration> {
+        let Some(key) = (self.key)(request) else {
+            return Ok(());
+        };
+
+        let mut buckets = self.buckets.lock().unwrap();
+
+        if buckets.len() >= SWEEP_THRESHOLD && !buckets.contains_key(&key) {
+            buckets.retain(|_, bucket| !bucket.is_full());
+        }
+
+        buckets.entry(key)
+            .or_insert_with(|| TokenBucket::with_rate(self.rate, self.burst))
+            .try_take(1)
+    }
+
+    pub fn middleware<E: ServerError>(self) -> impl Fn(&mut Request, Next<'_, E>) -> Result<Response, E> + Sync + Send + 'static {
+        move |request, next| match self.check(request) {
+            Ok(()) => next.run(request),
+            Err(wait) => {
+                let mut response = Response::text("Too many requests", 429);
+                response.header("Retry-After", &wait.as_secs_f64().ceil().min(u32::MAX as f64).max(1.0).to_string());
+                Ok(response)
+            }
+        }
+    }
+}
+
+impl Debug for RateLimiter {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("RateLimiter")
+            .field("rate", &self.rate)
+            .field("burst", &self.burst)
+            .finish()
+    }
+}
@@ -0,0 +1,256 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+use url::form_urlencoded;
+use crate::error::{InvalidSignatureError, ServerError};
+use crate::message::{Request, Response};
+use crate::middleware::Next;
+
+pub const SIGNATURE_ALGORITHM: &str = "HMAC-SHA256";
+pub const DATE_HEADER: &str = "x-date";
+pub const NONCE_HEADER: &str = "x-nonce";
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignedRequest {
+    pub key_id: String
+}
+
+pub struct SignatureVerifier {
+    keys: HashMap<String, Vec<u8>>,
+    clock_skew: Duration,
+    nonces: Mutex<HashMap<String, u64>>
+}
+
+impl SignatureVerifier {
+    pub fn new() -> Self {
+        Self {
+            keys: HashMap::new(),
+            clock_skew: Duration::from_secs(300),
+            nonces: Mutex::new(HashMap::new())
+        }
+    }
+
+    pub fn key(mut self, key_id: &str, secret: impl AsRef<[u8]>) -> Self {
+        self.keys.insert(key_id.to_string(), secret.as_ref().to_vec());
+        self
+    }
+
+    pub fn clock_skew(mut self, clock_skew: Duration) -> Self {
+        self.clock_skew = clock_skew;
+        self
+    }
+
+    pub fn verify(&self, request: &Request) -> Result<String, InvalidSignatureError> {
+        let authorization = request.header("authorization").ok_or(InvalidSignatureError)?;
+        let params = authorization.strip_prefix(SIGNATURE_ALGORITHM).ok_or(InvalidSignatureError)?;
+        let mut key_id = None;
+        let mut signed_headers = None;
+        let mut signature = None;
+
+        for param in params.split(',') {
+            match param.trim().split_once('=') {
+                Some(("KeyId", value)) => key_id = Some(value),
+                Some(("SignedHeaders", value)) => signed_headers = Some(value),
+                Some(("Signature", value)) => signature = Some(value),
+                _ => ()
+            }
+        }
+
+        let key_id = key_id.ok_or(InvalidSignatureError)?;
+        let signed_headers: Vec<&str> = signed_headers.ok_or(InvalidSignatureError)?.split(';').collect();
+        let signature = decode_hex(signature.ok_or(InvalidSignatureError)?).ok_or(InvalidSignatureError)?;
+        let secret = self.keys.get(key_id).ok_or(InvalidSignatureError)?;
+
+        if !signed_headers.contains(&DATE_HEADER) || !signed_headers.contains(&NONCE_HEADER) {
+            return Err(InvalidSignatureError);
+        }
+
+        let date = request.header(DATE_HEADER).and_then(|date| date.parse::<u64>().ok()).ok_or(InvalidSignatureError)?;
+        let nonce = request.header(NONCE_HEADER).ok_or(InvalidSignatureError)?;
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+
+        if now.abs_diff(date) > self.clock_skew.as_secs() {
+            return Err(InvalidSignatureError);
+        }
+
+        let mut headers = Vec::new();
+
+        for header in &signed_headers {
+            headers.push((*header, request.header(header).ok_or(InvalidSignatureError)?));
+        }
+
+        let query: Vec<(String, String)> = request.url().query_pairs().into_owned().collect();
+        let canonical = canonical_request(request.method().as_str(), request.raw_route(), &query, &headers, request.raw());
+
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret).expect("HMAC accepts keys of any length");
+        mac.update(string_to_sign(date, nonce, &canonical).as_bytes());
+        mac.verify_slice(&signature).map_err(|_| InvalidSignatureError)?;
+
+        self.use_nonce(nonce, date, now)?;
+        Ok(key_id.to_string())
+    }
+
+    pub fn middleware<E: ServerError>(self) -> impl Fn(&mut Request, Next<'_, E>) -> Result<Response, E> + Sync + Send + 'static {
+        move |request, next| match self.verify(request) {
+            Ok(key_id) => {
+                request.insert_ext(SignedRequest { key_id });
+                next.run(request)
+            },
+            Err(_) => Ok(Response::text("Forbidden", 403))
+        }
+    }
+
+    fn use_nonce(&self, nonce: &str, date: u64, now: u64) -> Result<(), InvalidSignatureError> {
+        let mut nonces = self.nonces.lock().unwrap();
+        nonces.retain(|_, expires| *expires >= now);
+
+        if nonces.contains_key(nonce) {
+            return Err(InvalidSignatureError);
+        }
+
+        nonces.insert(nonce.to_string(), date + self.clock_skew.as_secs());
+        Ok(())
+    }
+}
+
+impl Default for SignatureVerifier {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub fn canonical_request(method: &str, path: &str, query: &[(String, String)], headers: &[(&str, &str)], body: &[u8]) -> String {
+    let mut query = query.to_vec();
+    query.sort();
+
+    let query = form_urlencoded::Serializer::new(String::new())
+        .extend_pairs(query)
+        .finish();
+
+    let mut headers: Vec<(String, &str)> = headers.iter()
+        .map(|(header, value)| (header.to_ascii_lowercase(), value.trim()))
+        .collect();
+
+    headers.sort();
+
+    let canonical_headers: String = headers.iter()
+        .map(|(header, value)| format!("{header}:{value}\n"))
+        .collect();
+
+    let signed_headers = headers.iter()
+        .map(|(header, _)| header.as_str())
+        .collect::<Vec<&str>>()
+        .join(";");
+
+    format!("{method}\n{path}\n{query}\n{canonical_headers}\n{signed_headers}\n{}", encode_hex(&Sha256::digest(body)))
+}
+
+pub fn string_to_sign(date: u64, nonce: &str, canonical_request: &str) -> String {
+    format!("{SIGNATURE_ALGORITHM}\n{date}\n{nonce}\n{}", encode_hex(&Sha256::digest(canonical_request.as_bytes())))
+}
+
+pub fn sign(secret: impl AsRef<[u8]>, string_to_sign: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_ref()).expect("HMAC accepts keys of any length");
+    mac.update(string_to_sign.as_bytes());
+    encode_hex(&mac.finalize().into_bytes())
+}
+
+fn encode_hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if !hex.len().is_multiple_of(2) {
+        return None;
+    }
+
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+    use crate::error::DefaultError;
+    use crate::method::HttpMethod;
+    use crate::middleware::Middleware;
+    use crate::route::Router;
+    use super::*;
+
+    const SECRET: &str = "secret";
+
+    fn now() -> u64 {
+        SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_secs()
+    }
+
+    fn signed(date: u64, nonce: &str) -> Request {
+        let headers = [("host", "localhost"), (DATE_HEADER, &date.to_string()), (NONCE_HEADER, nonce)];
+        let query = vec![("b".to_string(), "2".to_string()), ("a".to_string(), "1".to_string())];
+        let canonical = canonical_request("POST", "/items", &query, &headers, b"body");
+        let signature = sign(SECRET, &string_to_sign(date, nonce, &canonical));
+
+        let bytes = format!(
+            "POST /items?b=2&a=1 HTTP/1.1\r\nHost: localhost\r\nX-Date: {date}\r\nX-Nonce: {nonce}\r\nContent-Length: 4\r\nAuthorization: {SIGNATURE_ALGORITHM} KeyId=client, SignedHeaders=host;x-date;x-nonce, Signature={signature}\r\n\r\nbody"
+        );
+
+        Request::from_bytes(SocketAddr::from(([127, 0, 0, 1], 1234)), bytes.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn builds_canonical_requests() {
+        let query = vec![("b".to_string(), "2 3".to_string()), ("a".to_string(), "1".to_string())];
+        let canonical = canonical_request("GET", "/items", &query, &[("X-Nonce", " n "), ("Host", "localhost")], b"");
+
+        assert_eq!(
+            canonical,
+            "GET\n/items\na=1&b=2+3\nhost:localhost\nx-nonce:n\n\nhost;x-nonce\ne3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+
+    #[test]
+    fn accepts_valid_signatures_once() {
+        let verifier = SignatureVerifier::new().key("client", SECRET);
+        let request = signed(now(), "n1");
+
+        assert_eq!(verifier.verify(&request).unwrap(), "client");
+        assert!(verifier.verify(&request).is_err());
+    }
+
+    #[test]
+    fn rejects_dates_outside_the_clock_skew() {
+        let verifier = SignatureVerifier::new().key("client", SECRET).clock_skew(Duration::from_secs(60));
+
+        assert!(verifier.verify(&signed(now() - 120, "old")).is_err());
+        assert!(verifier.verify(&signed(now() + 120, "future")).is_err());
+        assert!(verifier.verify(&signed(now() - 30, "recent")).is_ok());
+    }
+
+    #[test]
+    fn prunes_nonces_outside_the_clock_skew() {
+        let verifier = SignatureVerifier::new().key("client", SECRET).clock_skew(Duration::from_secs(60));
+        verifier.nonces.lock().unwrap().insert("stale".to_string(), now() - 1);
+        verifier.verify(&signed(now(), "fresh")).unwrap();
+
+        let nonces = verifier.nonces.lock().unwrap();
+        assert!(!nonces.contains_key("stale"));
+        assert!(nonces.contains_key("fresh"));
+    }
+
+    #[test]
+    fn middleware_rejects_unsigned_requests() {
+        let mut router = Router::new(|_: &Request| Err(DefaultError::NotFound));
+        router.add(HttpMethod::Post, "/items", |request: &Request| Ok(Response::text(request.ext::<SignedRequest>().unwrap().key_id.clone(), 200)));
+        let middleware = [Middleware::new("/", SignatureVerifier::new().key("client", SECRET).middleware())];
+
+        let mut request = signed(now(), "n2");
+        let response = Next::new(&middleware, &router).run(&mut request).unwrap();
+        assert_eq!((response.status().as_u16(), response.body()), (200, &b"client"[..]));
+
+        let mut request = Request::from_bytes(SocketAddr::from(([127, 0, 0, 1], 1234)), b"POST /items HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+        assert_eq!(Next::new(&middleware, &router).run(&mut request).unwrap().status(), 403);
+    }
+}
@@ -0,0 +1,244 @@
+use std::collections::HashMap;
+use std::io::{self, ErrorKind};
+use std::net::{SocketAddr, ToSocketAddrs};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+pub const MAX_CACHE_ENTRIES: usize = 1024;
+
+type Lookup = Arc<dyn Fn(&str, u16) -> io::Result<Vec<Record>> + Sync + Send>;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IpPreference {
+    System,
+    Ipv4First,
+    Ipv6First,
+    Ipv4Only,
+    Ipv6Only
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Record {
+    pub address: SocketAddr,
+    pub ttl: Option<Duration>
+}
+
+impl Record {
+    pub fn new(address: SocketAddr, ttl: Option<Duration>) -> Self {
+        Self {
+            address,
+            ttl
+        }
+    }
+}
+
+struct CacheEntry {
+    expires: Instant,
+    addresses: Option<Vec<SocketAddr>>
+}
+
+pub struct Resolver {
+    ttl: Duration,
+    negative_ttl: Duration,
+    preference: IpPreference,
+    lookup: Lookup,
+    capacity: usize,
+    cache: Mutex<HashMap<(String, u16), CacheEntry>>
+}
+
+impl Resolver {
+    pub fn new() -> Self {
+        Self {
+            ttl: Duration::from_secs(60),
+            negative_ttl: Duration::from_secs(5),
+            preference: IpPreference::System,
+            lookup: Arc::new(system_lookup),
+            capacity: MAX_CACHE_ENTRIES,
+            cache: Mutex::new(HashMap::new())
+        }
+    }
+
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    pub fn negative_ttl(mut self, ttl: Duration) -> Self {
+        self.negative_ttl = ttl;
+        self
+    }
+
+    pub fn preference(mut self, preference: IpPreference) -> Self {
+        self.preference = preference;
+        self
+    }
+
+    pub fn capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity;
+        self
+    }
+
+    pub fn lookup(mut self, lookup: impl Fn(&str, u16) -> io::Result<Vec<Record>> + Sync + Send + 'static) -> Self {
+        self.lookup = Arc::new(lookup);
+        self
+    }
+
+    pub fn resolve(&self, host: &str, port: u16) -> io::Result<Vec<SocketAddr>> {
+        let key = (host.to_ascii_lowercase(), port);
+
+        if let Some(entry) = self.cache.lock().unwrap().get(&key).filter(|entry| entry.expires > Instant::now()) {
+            return entry.addresses.clone().ok_or(Self::not_found(host));
+        }
+
+        let records = (self.lookup)(host, port)?;
+        let record_ttl = records.iter().filter_map(|record| record.ttl).min();
+
+        let addresses = Some(self.order(records.iter().map(|record| record.address).collect()))
+            .filter(|addresses| !addresses.is_empty());
+
+        let ttl = match addresses {
+            Some(_) => record_ttl.map_or(self.ttl, |ttl| ttl.min(self.ttl)),
+            None => self.negative_ttl
+        };
+
+        if !ttl.is_zero() && self.capacity > 0 {
+            let mut cache = self.cache.lock().unwrap();
+
+            if cache.len() >= self.capacity && !cache.contains_key(&key) {
+                let now = Instant::now();
+                cache.retain(|_, entry| entry.expires > now);
+            }
+
+            if cache.len() >= self.capacity && !cache.contains_key(&key) {
+                let oldest = cache.iter().min_by_key(|(_, entry)| entry.expires).map(|(key, _)| key.clone());
+
+                if let Some(oldest) = oldest {
+                    cache.remove(&oldest);
+                }
+            }
+
+            cache.insert(key, CacheEntry {
+                expires: Instant::now() + ttl,
+                addresses: addresses.clone()
+            });
+        }
+
+        addresses.ok_or(Self::not_found(host))
+    }
+
+    pub fn clear(&self) {
+        self.cache.lock().unwrap().clear();
+    }
+
+    fn order(&self, mut addresses: Vec<SocketAddr>) -> Vec<SocketAddr> {
+        match self.preference {
+            IpPreference::System => (),
+            IpPreference::Ipv4First => addresses.sort_by_key(|address| address.is_ipv6()),
+            IpPreference::Ipv6First => addresses.sort_by_key(|address| address.is_ipv4()),
+            IpPreference::Ipv4Only => addresses.retain(|address| address.is_ipv4()),
+            IpPreference::Ipv6Only => addresses.retain(|address| address.is_ipv6())
+        }
+
+        addresses
+    }
+
+    fn not_found(host: &str) -> io::Error {
+        io::Error::new(ErrorKind::NotFound, format!("Could not resolve {host}"))
+    }
+}
+
+fn system_lookup(host: &str, port: u16) -> io::Result<Vec<Record>> {
+    Ok((host, port).to_socket_addrs()?.map(|address| Record::new(address, None)).collect())
+}
+
+impl Default for Resolver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::thread;
+    use super::*;
+
+    fn counting(ttl: Option<Duration>) -> (Arc<AtomicUsize>, impl Fn(&str, u16) -> io::Result<Vec<Record>> + Sync + Send + 'static) {
+        let lookups = Arc::new(AtomicUsize::new(0));
+        let counter = lookups.clone();
+
+        (lookups, move |_: &str, port: u16| {
+            counter.fetch_add(1, Ordering::Relaxed);
+            Ok(vec![Record::new(SocketAddr::from(([127, 0, 0, 1], port)), ttl)])
+        })
+    }
+
+    #[test]
+    fn expires_entries_with_their_record_ttl() {
+        let (lookups, lookup) = counting(Some(Duration::from_millis(30)));
+        let resolver = Resolver::new().ttl(Duration::from_secs(60)).lookup(lookup);
+
+        resolver.resolve("example.test", 80).unwrap();
+        resolver.resolve("example.test", 80).unwrap();
+        assert_eq!(lookups.load(Ordering::Relaxed), 1);
+
+        thread::sleep(Duration::from_millis(50));
+        resolver.resolve("example.test", 80).unwrap();
+        assert_eq!(lookups.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn caps_record_ttls_at_the_configured_maximum() {
+        let (lookups, lookup) = counting(Some(Duration::from_secs(3600)));
+        let resolver = Resolver::new().ttl(Duration::from_millis(30)).lookup(lookup);
+
+        resolver.resolve("example.test", 80).unwrap();
+        thread::sleep(Duration::from_millis(50));
+        resolver.resolve("example.test", 80).unwrap();
+        assert_eq!(lookups.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn uses_the_configured_ttl_without_record_ttls() {
+        let (lookups, lookup) = counting(None);
+        let resolver = Resolver::new().ttl(Duration::from_secs(60)).lookup(lookup);
+
+        resolver.resolve("example.test", 80).unwrap();
+        thread::sleep(Duration::from_millis(50));
+        resolver.resolve("example.test", 80).unwrap();
+        assert_eq!(lookups.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn returns_lookup_errors_without_caching_them() {
+        let lookups = Arc::new(AtomicUsize::new(0));
+        let counter = lookups.clone();
+
+        let resolver = Resolver::new().lookup(move |_: &str, _: u16| {
+            counter.fetch_add(1, Ordering::Relaxed);
+            Err(io::Error::new(ErrorKind::TimedOut, "DNS timeout"))
+        });
+
+        assert_eq!(resolver.resolve("example.test", 80).unwrap_err().kind(), ErrorKind::TimedOut);
+        assert_eq!(resolver.resolve("example.test", 80).unwrap_err().kind(), ErrorKind::TimedOut);
+        assert_eq!(lookups.load(Ordering::Relaxed), 2);
+    }
+
+    #[test]
+    fn evicts_entries_beyond_the_capacity() {
+        let (lookups, lookup) = counting(None);
+        let resolver = Resolver::new().capacity(2).lookup(lookup);
+
+        for host in ["a.test", "b.test", "c.test"] {
+            resolver.resolve(host, 80).unwrap();
+        }
+
+        assert_eq!(resolver.cache.lock().unwrap().len(), 2);
+
+        resolver.resolve("c.test", 80).unwrap();
+        assert_eq!(lookups.load(Ordering::Relaxed), 3);
+
+        resolver.resolve("a.test", 80).unwrap();
+        assert_eq!(lookups.load(Ordering::Relaxed), 4);
+    }
+}
@@ -0,0 +1,159 @@
+use std::env;
+use std::io::{self, ErrorKind};
+use std::net::{Ipv4Addr, Ipv6Addr, SocketAddr, TcpListener, TcpStream};
+use std::process::{Child, Command};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::thread;
+use std::time::Duration;
+use log::{info, warn};
+
+pub const LISTEN_FDS_VAR: &str = "HTTP_SERVER_LISTEN_FDS";
+
+const WAKE_INTERVAL: Duration = Duration::from_millis(10);
+const WAKE_ATTEMPTS: usize = 500;
+
+#[derive(Debug, Default)]
+pub struct SoftRestart {
+    listeners: Mutex<Vec<TcpListener>>,
+    accepting: AtomicUsize,
+    draining: AtomicBool,
+    drain_timeout: Option<Duration>
+}
+
+impl SoftRestart {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn drain_timeout(mut self, timeout: Duration) -> Self {
+        self.drain_timeout = Some(timeout);
+        self
+    }
+
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::Relaxed)
+    }
+
+    pub fn restart(&self) -> io::Result<u32> {
+        if self.draining.swap(true, Ordering::Relaxed) {
+            return Err(io::Error::new(ErrorKind::AlreadyExists, "A restart is already in progress"));
+        }
+
+        let child = match self.spawn() {
+            Ok(child) => child,
+            Err(err) => {
+                self.draining.store(false, Ordering::Relaxed);
+                return Err(err);
+            }
+        };
+
+        info!("Started replacement process {}, draining connections", child);
+        self.wake();
+        Ok(child)
+    }
+
+    pub(crate) fn bind(&self, listeners: &[&TcpListener]) -> io::Result<()> {
+        let listeners = listeners.iter()
+            .map(|listener| listener.try_clone())
+            .collect::<io::Result<Vec<TcpListener>>>()?;
+
+        *self.listeners.lock().unwrap() = listeners;
+        Ok(())
+    }
+
+    pub(crate) fn started_accepting(&self) {
+        self.accepting.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn stopped_accepting(&self) {
+        self.accepting.fetch_sub(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn drain_deadline(&self) -> Option<Duration> {
+        self.drain_timeout
+    }
+
+    fn spawn(&self) -> io::Result<u32> {
+        let listeners = self.listeners.lock().unwrap();
+        Ok(reexec(&listeners, Command::new(env::current_exe()?))?.id())
+    }
+
+    fn wake(&self) {
+        let addresses: Vec<SocketAddr> = self.listeners.lock().unwrap().iter()
+            .filter_map(|listener| listener.local_addr().ok())
+            .map(loopback)
+            .collect();
+
+        for _ in 0..WAKE_ATTEMPTS {
+            if self.accepting.load(Ordering::Relaxed) == 0 {
+                return;
+            }
+
+            for address in &addresses {
+                TcpStream::connect_timeout(address, WAKE_INTERVAL).ok();
+            }
+
+            thread::sleep(WAKE_INTERVAL);
+        }
+
+        warn!("Listeners did not stop accepting after the restart was requested");
+    }
+}
+
+#[cfg(unix)]
+pub(crate) fn reexec(listeners: &[TcpListener], mut command: Command) -> io::Result<Child> {
+    use std::os::fd::AsRawFd;
+    use std::os::unix::process::CommandExt;
+
+    let fds: Vec<i32> = listeners.iter().map(AsRawFd::as_raw_fd).collect();
+    let inherited = fds.iter().map(i32::to_string).collect::<Vec<String>>().join(",");
+    command.args(env::args_os().skip(1)).env(LISTEN_FDS_VAR, inherited);
+
+    unsafe {
+        command.pre_exec(move || {
+            for fd in &fds {
+                if libc::fcntl(*fd, libc::F_SETFD, 0) == -1 {
+                    return Err(io::Error::last_os_error());
+                }
+            }
+
+            Ok(())
+        });
+    }
+
+    command.spawn()
+}
+
+#[cfg(not(unix))]
+pub(crate) fn reexec(_: &[TcpListener], _: Command) -> io::Result<Child> {
+    Err(io::Error::new(ErrorKind::Unsupported, "Passing listeners to another process needs unix file descriptor inheritance"))
+}
+
+pub fn inherited_listeners() -> Option<Vec<TcpListener>> {
+    let fds = env::var(LISTEN_FDS_VAR).ok()?;
+    env::remove_var(LISTEN_FDS_VAR);
+    from_fds(&fds)
+}
+
+#[cfg(unix)]
+fn from_fds(fds: &str) -> Option<Vec<TcpListener>> {
+    use std::os::fd::FromRawFd;
+
+    fds.split(',')
+        .map(|fd| fd.trim().parse::<i32>().ok().map(|fd| unsafe { TcpListener::from_raw_fd(fd) }))
+        .collect()
+}
+
+#[cfg(not(unix))]
+fn from_fds(_: &str) -> Option<Vec<TcpListener>> {
+    None
+}
+
+fn loopback(address: SocketAddr) -> SocketAddr {
+    match address {
+        SocketAddr::V4(v4) if v4.ip().is_unspecified() => SocketAddr::from((Ipv4Addr::LOCALHOST, v4.port())),
+        SocketAddr::V6(v6) if v6.ip().is_unspecified() => SocketAddr::from((Ipv6Addr::LOCALHOST, v6.port())),
+        address => address
+    }
+}
@@ -1,101 +1,518 @@
-use std::hash::{Hash, Hasher};
 use std::collections::HashMap;
-use std::error::Error;
-use std::marker::PhantomData;
 use std::str::Split;
+use std::sync::Arc;
+use log::trace;
 use crate::error::{DefaultError, ServerError};
+use crate::feature_flags::FeatureFlags;
 use crate::message::{Request, Response};
 use crate::method::HttpMethod;
+use crate::predicate::Predicate;
+use crate::uri;
 
 pub static NOT_FOUND_ACTION: fn(&Request) -> Result<Response, DefaultError> = |_| Err(DefaultError::NotFound);
 
-pub trait RouteAction<E: ServerError> : Fn(&Request) -> Result<Response, E> + Sync + Send + Clone + 'static {}
-impl <E: ServerError, F: Fn(&Request) -> Result<Response, E> + Sync + Send + Clone + 'static> RouteAction<E> for F {}
+pub trait RouteAction<E: ServerError> : Fn(&Request) -> Result<Response, E> + Sync + Send + 'static {}
+impl <E: ServerError, F: Fn(&Request) -> Result<Response, E> + Sync + Send + 'static> RouteAction<E> for F {}
 
-pub struct Router<E: ServerError, F: RouteAction<E>> {
-    nothing: PhantomData<E>,
-    route_tree: [RoutingTreeNode<E, F>; 5],
-    not_found_action: F
+pub type Handler<E> = Arc<dyn Fn(&Request) -> Result<Response, E> + Sync + Send>;
+
+pub type Params = HashMap<String, String>;
+
+type GroupRoute<E> = (HttpMethod, String, Option<String>, Option<Predicate>, Handler<E>);
+
+pub fn matches_prefix(route: &str, prefix: &str) -> bool {
+    let prefix = prefix.trim_end_matches('/');
+    prefix.is_empty() || route == prefix || route.strip_prefix(prefix).is_some_and(|rest| rest.starts_with('/'))
+}
+
+pub type Router<E> = RouteTable<Handler<E>>;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum TrailingSlash {
+    #[default]
+    Ignore,
+    Strict,
+    Redirect
 }
 
-impl <E: ServerError, F: RouteAction<E>> Router<E, F> {
-    pub fn new(not_found_action: F) -> Self {
+pub struct RouteTable<H> {
+    route_tree: [RoutingTreeNode<H>; 9],
+    extensions: HashMap<String, RoutingTreeNode<H>>,
+    not_found_action: H,
+    feature_flags: Option<Arc<dyn FeatureFlags>>,
+    trailing_slash: TrailingSlash
+}
+
+impl <E: ServerError> Router<E> {
+    pub fn new(not_found_action: impl RouteAction<E>) -> Self {
+        Self::with_fallback(Arc::new(not_found_action))
+    }
+
+    pub fn add(&mut self, method: HttpMethod, route: &str, action: impl RouteAction<E>) {
+        self.insert(method, route, Arc::new(action), None);
+    }
+
+    pub fn add_flagged(&mut self, method: HttpMethod, route: &str, flag: &str, action: impl RouteAction<E>) {
+        self.insert(method, route, Arc::new(action), Some(flag));
+    }
+
+    pub fn add_when(&mut self, method: HttpMethod, route: &str, predicate: Predicate, action: impl RouteAction<E>) {
+        self.insert_guarded(method, route, predicate, Arc::new(action));
+    }
+
+    pub fn mount(&mut self, prefix: &str, group: &RouteGroup<E>) {
+        for (method, route, flag, predicate, action) in &group.routes {
+            let route = join_routes(prefix, route);
+
+            match predicate {
+                Some(predicate) => self.insert_guarded(method.clone(), &route, predicate.clone(), action.clone()),
+                None => self.insert(method.clone(), &route, action.clone(), flag.as_deref())
+            }
+        }
+    }
+}
+
+impl <H> RouteTable<H> {
+    pub fn with_fallback(not_found_action: H) -> Self {
         Self {
-            nothing: PhantomData,
-            route_tree: [
-                RoutingTreeNode::new(not_found_action.clone()),
-                RoutingTreeNode::new(not_found_action.clone()),
-                RoutingTreeNode::new(not_found_action.clone()),
-                RoutingTreeNode::new(not_found_action.clone()),
-                RoutingTreeNode::new(not_found_action.clone())
-            ],
-            not_found_action
+            route_tree: Default::default(),
+            extensions: HashMap::new(),
+            not_found_action,
+            feature_flags: None,
+            trailing_slash: TrailingSlash::Ignore
         }
     }
 
-    pub fn get(&self, method: HttpMethod, route: &str) -> &F {
-        println!("{route}");
-        let path = Self::split_route(route);
-        println!("{:?}", path.clone().collect::<Vec<&str>>());
+    pub fn get(&self, method: HttpMethod, route: &str) -> (&H, Params) {
+        self.find(&method, route)
+            .and_then(|(node, values)| Some((node.action.as_ref()?, params(&node.param_names, values))))
+            .unwrap_or_else(|| (&self.not_found_action, HashMap::new()))
+    }
+
+    pub fn resolve(&self, request: &Request) -> (&H, Params) {
+        let found = match request.method() {
+            HttpMethod::Head => self.select(&HttpMethod::Head, request).or_else(|| self.select(&HttpMethod::Get, request)),
+            method => self.select(&method, request)
+        };
+
+        found.unwrap_or_else(|| (&self.not_found_action, HashMap::new()))
+    }
+
+    pub fn redirect(&self, request: &Request) -> Option<Response> {
+        if self.trailing_slash != TrailingSlash::Redirect {
+            return None;
+        }
+
+        let method = match request.method() {
+            HttpMethod::Head if !self.has_route(HttpMethod::Head, request) => HttpMethod::Get,
+            method => method
+        };
+
+        self.select(&method, request)?;
+        let route = format!("/{}", request.raw_route().trim_start_matches('/'));
+        let (node, _) = self.find(&method, &route)?;
+
+        let location = match (has_trailing_slash(&route), node.trailing_slash) {
+            (true, false) => route.trim_end_matches('/').to_string(),
+            (false, true) => format!("{route}/"),
+            _ => return None
+        };
+
+        let location = match request.raw_query() {
+            Some(query) => format!("{location}?{query}"),
+            None => location
+        };
+
+        let status = match method {
+            HttpMethod::Get => 301,
+            _ => 308
+        };
+
+        Some(Response::redirect(&location, status))
+    }
+
+    pub fn has_route(&self, method: HttpMethod, request: &Request) -> bool {
+        self.select(&method, request).is_some()
+    }
+
+    pub fn allowed_methods(&self, request: &Request) -> Vec<HttpMethod> {
+        let extensions = self.extensions.keys().map(|method| HttpMethod::Extension(method.clone()));
+
+        let mut methods: Vec<HttpMethod> = HttpMethod::ALL.into_iter()
+            .chain(extensions)
+            .filter(|method| self.select(method, request).is_some())
+            .collect();
+
+        if methods.contains(&HttpMethod::Get) && !methods.contains(&HttpMethod::Head) {
+            methods.push(HttpMethod::Head);
+        }
 
-        match self.route_tree[method as usize].get(path) {
-            Some(action) => action,
-            None => &self.not_found_action
+        if !methods.is_empty() && !methods.contains(&HttpMethod::Options) {
+            methods.push(HttpMethod::Options);
         }
+
+        methods
     }
 
-    pub fn add(&mut self, method: HttpMethod, route: &str, action: F) {
+    pub fn method_not_allowed(&self, request: &Request) -> Option<Vec<HttpMethod>> {
+        let method = request.method();
+
+        if self.select(&method, request).is_some() || (method == HttpMethod::Head && self.has_route(HttpMethod::Get, request)) {
+            return None;
+        }
+
+        let methods = self.allowed_methods(request);
+        (!methods.is_empty()).then_some(methods)
+    }
+
+    pub fn insert(&mut self, method: HttpMethod, route: &str, action: H, flag: Option<&str>) {
         let path = Self::split_route(route);
-        self.route_tree[method as usize].add(path, action, &self.not_found_action);
+        self.tree_mut(method).add(path, action, Vec::new(), flag.map(String::from), has_trailing_slash(route));
+    }
+
+    pub fn insert_guarded(&mut self, method: HttpMethod, route: &str, predicate: Predicate, action: H) {
+        let path = Self::split_route(route);
+        self.tree_mut(method).add_guarded(path, predicate, action, Vec::new(), has_trailing_slash(route));
+    }
+
+    pub fn feature_flags(&mut self, provider: impl FeatureFlags + 'static) {
+        self.feature_flags = Some(Arc::new(provider));
+    }
+
+    pub fn trailing_slash(&mut self, policy: TrailingSlash) {
+        self.trailing_slash = policy;
+    }
+
+    fn tree(&self, method: &HttpMethod) -> Option<&RoutingTreeNode<H>> {
+        match method {
+            HttpMethod::Extension(method) => self.extensions.get(method),
+            method => HttpMethod::ALL.iter().position(|standard| standard == method).map(|index| &self.route_tree[index])
+        }
+    }
+
+    fn tree_mut(&mut self, method: HttpMethod) -> &mut RoutingTreeNode<H> {
+        match method {
+            HttpMethod::Extension(method) => self.extensions.entry(method).or_default(),
+            method => {
+                let index = HttpMethod::ALL.iter().position(|standard| *standard == method).expect("standard methods are listed in HttpMethod::ALL");
+                &mut self.route_tree[index]
+            }
+        }
     }
 
-    fn split_route(route: &str) -> Split<char> {
+    fn find(&self, method: &HttpMethod, route: &str) -> Option<(&RoutingTreeNode<H>, Vec<String>)> {
+        let segments: Vec<String> = Self::split_route(route).map(uri::decode).collect();
+        trace!("Finding route {route}: {:?}", segments);
+        let mut values = Vec::new();
+
+        self.tree(method)?
+            .get(segments.iter().map(String::as_str), &mut values)
+            .map(|node| (node, values.into_iter().map(String::from).collect()))
+    }
+
+    fn select(&self, method: &HttpMethod, request: &Request) -> Option<(&H, Params)> {
+        let strict = self.trailing_slash == TrailingSlash::Strict;
+
+        let (node, values) = self.find(method, request.raw_route())
+            .filter(|(node, _)| !strict || node.trailing_slash == has_trailing_slash(request.raw_route()))?;
+
+        if let Some(guarded) = node.guarded.iter().find(|guarded| guarded.predicate.matches(request)) {
+            return Some((&guarded.action, params(&guarded.param_names, values)));
+        }
+
+        let action = node.action.as_ref().filter(|_| self.flag_enabled(node.flag.as_deref(), request))?;
+        Some((action, params(&node.param_names, values)))
+    }
+
+    fn flag_enabled(&self, flag: Option<&str>, request: &Request) -> bool {
+        match (flag, &self.feature_flags) {
+            (None, _) => true,
+            (Some(flag), Some(provider)) => provider.enabled(flag, request),
+            (Some(_), None) => false
+        }
+    }
+
+    fn split_route(route: &str) -> Split<'_, char> {
         route.trim_matches('/').split('/')
     }
 }
 
-pub struct RoutingTreeNode<E: ServerError, F: RouteAction<E>> {
-    nothing: PhantomData<E>,
-    action: F,
-    children: HashMap<String, Box<RoutingTreeNode<E, F>>>
+struct Guarded<H> {
+    predicate: Predicate,
+    action: H,
+    param_names: Vec<String>
 }
 
-impl <E: ServerError, F: RouteAction<E>> RoutingTreeNode<E, F> {
-    pub fn new(action: F) -> Self {
+pub struct RoutingTreeNode<H> {
+    action: Option<H>,
+    guarded: Vec<Guarded<H>>,
+    param_names: Vec<String>,
+    flag: Option<String>,
+    trailing_slash: bool,
+    children: HashMap<String, Box<RoutingTreeNode<H>>>,
+    param_child: Option<Box<RoutingTreeNode<H>>>
+}
+
+impl <H> RoutingTreeNode<H> {
+    pub fn new() -> Self {
         Self {
-            nothing: PhantomData,
-            action,
-            children: HashMap::new()
+            action: None,
+            guarded: Vec::new(),
+            param_names: Vec::new(),
+            flag: None,
+            trailing_slash: false,
+            children: HashMap::new(),
+            param_child: None
         }
     }
 
-    pub fn get<'a, I: Iterator<Item = &'a str>>(&self, mut route: I) -> Option<&F> {
+    pub fn get<'a, I: Iterator<Item = &'a str> + Clone>(&self, mut route: I, values: &mut Vec<&'a str>) -> Option<&Self> {
         let p = route.next();
-        println!("{p:?}");
+        trace!("Matching segment {p:?}");
 
         match p {
-            Some("") | None => Some(&self.action),
-            Some(next) => match self.children.get(next) {
-                Some(child) => child.get(route),
-                _ => None
-            },
+            Some("") | None => (self.action.is_some() || !self.guarded.is_empty()).then_some(self),
+            Some(next) => {
+                if let Some(node) = self.children.get(next).and_then(|child| child.get(route.clone(), values)) {
+                    return Some(node);
+                }
+
+                let child = self.param_child.as_ref()?;
+                values.push(next);
+                let node = child.get(route, values);
+
+                if node.is_none() {
+                    values.pop();
+                }
+
+                node
+            }
         }
     }
 
-    pub fn add<'a, I: Iterator<Item = &'a str>>(&mut self, mut route: I, action: F, not_found_action: &F) {
+    pub fn add<'a, I: Iterator<Item = &'a str>>(&mut self, route: I, action: H, mut param_names: Vec<String>, flag: Option<String>, trailing_slash: bool) {
+        let node = self.descend(route, &mut param_names);
+        node.action = Some(action);
+        node.param_names = param_names;
+        node.flag = flag;
+        node.trailing_slash = trailing_slash;
+    }
+
+    pub fn add_guarded<'a, I: Iterator<Item = &'a str>>(&mut self, route: I, predicate: Predicate, action: H, mut param_names: Vec<String>, trailing_slash: bool) {
+        let node = self.descend(route, &mut param_names);
+        node.guarded.push(Guarded { predicate, action, param_names });
+        node.trailing_slash = trailing_slash;
+    }
+
+    fn descend<'a, I: Iterator<Item = &'a str>>(&mut self, mut route: I, param_names: &mut Vec<String>) -> &mut Self {
         let p = route.next();
-        println!("{p:?}");
+        trace!("Adding segment {p:?}");
 
         match p {
-            Some("") | None => self.action = action,
+            Some("") | None => self,
             Some(next) => {
-                if !self.children.contains_key(next) {
-                    self.children.insert(next.to_string(), Box::new(RoutingTreeNode::new(not_found_action.clone())));
-                }
+                let child = match next.strip_prefix(':') {
+                    Some(name) => {
+                        param_names.push(name.to_string());
+                        self.param_child.get_or_insert_with(|| Box::new(RoutingTreeNode::new()))
+                    },
+                    None => self.children.entry(next.to_string()).or_insert_with(|| Box::new(RoutingTreeNode::new()))
+                };
 
-                let child = self.children.get_mut(next).unwrap();
-                child.add(route, action, not_found_action);
+                child.descend(route, param_names)
             }
         }
     }
-}
\ No newline at end of file
+}
+
+impl <H> Default for RoutingTreeNode<H> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct RouteGroup<E: ServerError> {
+    routes: Vec<GroupRoute<E>>
+}
+
+impl <E: ServerError> RouteGroup<E> {
+    pub fn new() -> Self {
+        Self {
+            routes: Vec::new()
+        }
+    }
+
+    pub fn route(&mut self, method: HttpMethod, route: &str, action: impl RouteAction<E>) {
+        self.routes.push((method, route.to_string(), None, None, Arc::new(action)));
+    }
+
+    pub fn route_flagged(&mut self, method: HttpMethod, route: &str, flag: &str, action: impl RouteAction<E>) {
+        self.routes.push((method, route.to_string(), Some(flag.to_string()), None, Arc::new(action)));
+    }
+
+    pub fn route_when(&mut self, method: HttpMethod, route: &str, predicate: Predicate, action: impl RouteAction<E>) {
+        self.routes.push((method, route.to_string(), None, Some(predicate), Arc::new(action)));
+    }
+
+    pub fn get(&mut self, route: &str, action: impl RouteAction<E>) {
+        self.route(HttpMethod::Get, route, action);
+    }
+
+    pub fn post(&mut self, route: &str, action: impl RouteAction<E>) {
+        self.route(HttpMethod::Post, route, action);
+    }
+
+    pub fn put(&mut self, route: &str, action: impl RouteAction<E>) {
+        self.route(HttpMethod::Put, route, action);
+    }
+
+    pub fn patch(&mut self, route: &str, action: impl RouteAction<E>) {
+        self.route(HttpMethod::Patch, route, action);
+    }
+
+    pub fn delete(&mut self, route: &str, action: impl RouteAction<E>) {
+        self.route(HttpMethod::Delete, route, action);
+    }
+
+    pub fn head(&mut self, route: &str, action: impl RouteAction<E>) {
+        self.route(HttpMethod::Head, route, action);
+    }
+
+    pub fn options(&mut self, route: &str, action: impl RouteAction<E>) {
+        self.route(HttpMethod::Options, route, action);
+    }
+
+    pub fn trace(&mut self, route: &str, action: impl RouteAction<E>) {
+        self.route(HttpMethod::Trace, route, action);
+    }
+
+    pub fn connect(&mut self, route: &str, action: impl RouteAction<E>) {
+        self.route(HttpMethod::Connect, route, action);
+    }
+
+    pub fn scope(&mut self, prefix: &str, build: impl FnOnce(&mut RouteGroup<E>)) {
+        let mut group = RouteGroup::new();
+        build(&mut group);
+        self.mount(prefix, &group);
+    }
+
+    pub fn mount(&mut self, prefix: &str, group: &RouteGroup<E>) {
+        for (method, route, flag, predicate, action) in &group.routes {
+            self.routes.push((method.clone(), join_routes(prefix, route), flag.clone(), predicate.clone(), action.clone()));
+        }
+    }
+}
+
+impl <E: ServerError> Default for RouteGroup<E> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+fn params(names: &[String], values: Vec<String>) -> Params {
+    names.iter().cloned().zip(values).collect()
+}
+
+fn has_trailing_slash(route: &str) -> bool {
+    route.len() > 1 && route.ends_with('/')
+}
+
+fn join_routes(prefix: &str, route: &str) -> String {
+    let prefix = prefix.trim_matches('/');
+    let slash = if has_trailing_slash(route) { "/" } else { "" };
+    let route = route.trim_matches('/');
+
+    match (prefix.is_empty(), route.is_empty()) {
+        (true, _) => format!("/{route}{slash}"),
+        (false, true) => format!("/{prefix}"),
+        (false, false) => format!("/{prefix}/{route}{slash}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+    use super::*;
+
+    fn request(method: &str, target: &str) -> Request {
+        let bytes = format!("{method} {target} HTTP/1.1\r\nHost: localhost\r\n\r\n");
+        Request::from_bytes(SocketAddr::from(([127, 0, 0, 1], 1234)), bytes.as_bytes()).unwrap()
+    }
+
+    fn router() -> Router<DefaultError> {
+        let mut router = Router::new(NOT_FOUND_ACTION);
+        router.trailing_slash(TrailingSlash::Redirect);
+        router.add(HttpMethod::Get, "/:user", |_: &Request| Ok::<_, DefaultError>(Response::text("user", 200)));
+        router.add(HttpMethod::Get, "/dir/", |_: &Request| Ok::<_, DefaultError>(Response::text("dir", 200)));
+        router
+    }
+
+    #[test]
+    fn redirect_drops_trailing_slash() {
+        let response = router().redirect(&request("GET", "/alice/?page=2")).unwrap();
+        assert_eq!(response.status(), 301);
+        assert_eq!(response.get_header("Location"), Some("/alice?page=2"));
+    }
+
+    #[test]
+    fn redirect_adds_registered_slash() {
+        let response = router().redirect(&request("POST", "/dir"));
+        assert!(response.is_none());
+
+        let response = router().redirect(&request("GET", "/dir")).unwrap();
+        assert_eq!(response.get_header("Location"), Some("/dir/"));
+    }
+
+    #[test]
+    fn redirect_never_leaves_the_origin() {
+        for target in ["//evil.com/", "///evil.com/", "//evil.com/?a=1"] {
+            let response = router().redirect(&request("GET", target)).unwrap();
+            let location = response.get_header("Location").unwrap();
+            assert!(location.starts_with('/') && !location.starts_with("//"), "{target} redirected to {location}");
+        }
+    }
+
+    #[test]
+    fn mounted_groups_keep_route_predicates() {
+        let mut router = router();
+        let mut group = RouteGroup::new();
+        group.scope("/v1", |group| {
+            group.route_when(HttpMethod::Get, "/items", Predicate::has_header("x-beta"), |_: &Request| Ok(Response::text("beta", 200)));
+            group.get("/items", |_: &Request| Ok(Response::text("stable", 200)));
+        });
+        router.mount("/api", &group);
+
+        let body = |request: &mut Request| {
+            let response = crate::middleware::Next::new(&[], &router).run(request).unwrap();
+            String::from_utf8_lossy(response.body()).into_owned()
+        };
+
+        let mut beta = Request::from_bytes(SocketAddr::from(([127, 0, 0, 1], 1234)), b"GET /api/v1/items HTTP/1.1\r\nHost: localhost\r\nX-Beta: 1\r\n\r\n").unwrap();
+        assert_eq!(body(&mut beta), "beta");
+        assert_eq!(body(&mut request("GET", "/api/v1/items")), "stable");
+    }
+
+    #[test]
+    fn decodes_percent_encoded_routes_and_params() {
+        let mut router = Router::new(NOT_FOUND_ACTION);
+        router.add(HttpMethod::Get, "/files/:name", |request: &Request| Ok::<_, DefaultError>(Response::text(request.param("name").unwrap_or_default(), 200)));
+        router.add(HttpMethod::Get, "/caf\u{e9}", |_: &Request| Ok::<_, DefaultError>(Response::text("menu", 200)));
+
+        let body = |target: &str| {
+            let response = crate::middleware::Next::new(&[], &router).run(&mut request("GET", target)).unwrap();
+            String::from_utf8_lossy(response.body()).into_owned()
+        };
+
+        assert_eq!(body("/files/hello%20world.txt"), "hello world.txt");
+        assert_eq!(body("/files/a%2Fb"), "a/b");
+        assert_eq!(body("/caf%C3%A9"), "menu");
+    }
+
+    #[test]
+    fn keeps_the_raw_route_next_to_the_decoded_one() {
+        let request = request("GET", "/files/a%2Fb%20c");
+        assert_eq!(request.route(), "/files/a/b c");
+        assert_eq!(request.raw_route(), "/files/a%2Fb%20c");
+        assert_eq!(uri::decode("%E2%82%AC%FF"), "\u{20ac}\u{fffd}");
+    }
+}
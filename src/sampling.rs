@@ -0,0 +1,176 @@
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use serde::Serialize;
+use crate::error::ServerError;
+use crate::headers::HeaderMap;
+use crate::message::{Request, Response};
+use crate::route::RouteAction;
+
+const MAX_CAPTURED_BODY: usize = 64 * 1024;
+const REDACTED: &str = "[redacted]";
+const SENSITIVE_HEADERS: [&str; 8] = [
+    "authorization",
+    "proxy-authorization",
+    "cookie",
+    "set-cookie",
+    "x-api-key",
+    "x-auth-token",
+    "x-csrf-token",
+    "x-amz-security-token"
+];
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Capture {
+    pub timestamp: u64,
+    pub client: String,
+    pub method: String,
+    pub target: String,
+    pub request_headers: Vec<(String, String)>,
+    pub request_body: String,
+    pub status: u16,
+    pub response_headers: Vec<(String, String)>,
+    pub response_body: String,
+    pub duration: Duration
+}
+
+#[derive(Debug)]
+pub struct RequestSampler {
+    rate: u64,
+    debug_header: Option<(String, String)>,
+    redacted: Vec<String>,
+    capacity: usize,
+    seen: AtomicU64,
+    captures: Mutex<VecDeque<Capture>>
+}
+
+impl RequestSampler {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            rate: 0,
+            debug_header: None,
+            redacted: SENSITIVE_HEADERS.iter().map(|header| header.to_string()).collect(),
+            capacity: capacity.max(1),
+            seen: AtomicU64::new(0),
+            captures: Mutex::new(VecDeque::new())
+        }
+    }
+
+    pub fn one_in(mut self, rate: u64) -> Self {
+        self.rate = rate;
+        self
+    }
+
+    pub fn debug_header(mut self, header: &str, secret: &str) -> Self {
+        self.debug_header = Some((header.to_string(), secret.to_string()));
+        self
+    }
+
+    pub fn redact(mut self, header: &str) -> Self {
+        self.redacted.push(header.to_ascii_lowercase());
+        self
+    }
+
+    pub fn should_sample(&self, request: &Request) -> bool {
+        let requested = self.debug_header.as_ref().is_some_and(|(header, secret)| {
+            request.header(header).is_some_and(|value| constant_time_eq(value.as_bytes(), secret.as_bytes()))
+        });
+
+        let seen = self.seen.fetch_add(1, Ordering::Relaxed);
+        requested || (self.rate > 0 && seen.is_multiple_of(self.rate))
+    }
+
+    pub fn record(&self, request: &Request, response: &Response, duration: Duration) {
+        let redact = |headers: &HeaderMap| -> Vec<(String, String)> {
+            headers.iter().map(|(header, value)| {
+                let sensitive = self.redacted.iter().any(|redacted| redacted.eq_ignore_ascii_case(header))
+                    || self.debug_header.as_ref().is_some_and(|(debug, _)| debug.eq_ignore_ascii_case(header));
+
+                (header.to_string(), if sensitive { REDACTED.to_string() } else { value.to_string() })
+            }).collect()
+        };
+
+        let response_body = match response.is_stream() {
+            true => "[stream]".to_string(),
+            false => body_text(response.body())
+        };
+
+        let capture = Capture {
+            timestamp: SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64,
+            client: request.socket_addr().to_string(),
+            method: request.method().as_str().to_string(),
+            target: request.target().to_string(),
+            request_headers: redact(request.headers()),
+            request_body: body_text(request.raw()),
+            status: response.status().as_u16(),
+            response_headers: redact(response.headers()),
+            response_body,
+            duration
+        };
+
+        let mut captures = self.captures.lock().unwrap();
+
+        if captures.len() == self.capacity {
+            captures.pop_front();
+        }
+
+        captures.push_back(capture);
+    }
+
+    pub fn snapshot(&self) -> Vec<Capture> {
+        self.captures.lock().unwrap().iter().cloned().collect()
+    }
+
+    pub fn clear(&self) {
+        self.captures.lock().unwrap().clear();
+    }
+
+    pub fn endpoint<E: ServerError>(self: Arc<Self>) -> impl RouteAction<E> {
+        move |_| Ok(Response::json(self.snapshot(), 200).unwrap_or_else(|_| Response::text("Internal server error", 500)))
+    }
+}
+
+fn body_text(body: &[u8]) -> String {
+    let truncated = &body[..body.len().min(MAX_CAPTURED_BODY)];
+    let mut text = String::from_utf8_lossy(truncated).into_owned();
+
+    if body.len() > MAX_CAPTURED_BODY {
+        text.push_str(&format!("... [{} more bytes]", body.len() - MAX_CAPTURED_BODY));
+    }
+
+    text
+}
+
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).fold(0, |difference, (x, y)| difference | (x ^ y)) == 0
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+    use super::*;
+
+    #[test]
+    fn redacts_credentials_in_requests_and_responses() {
+        let sampler = RequestSampler::new(4).debug_header("X-Debug", "secret").redact("X-Tenant-Secret");
+        let request = Request::from_bytes(
+            SocketAddr::from(([127, 0, 0, 1], 4000)),
+            b"GET /a HTTP/1.1\r\nHost: x\r\nAuthorization: Bearer t\r\nProxy-Authorization: Basic u\r\nX-Api-Key: k\r\nX-Debug: secret\r\nX-Tenant-Secret: s\r\nAccept: */*\r\n\r\n"
+        ).unwrap();
+
+        let mut response = Response::text("ok", 200);
+        response.append_header("Set-Cookie", "session=abc");
+        sampler.record(&request, &response, Duration::ZERO);
+
+        let capture = sampler.snapshot().remove(0);
+        let value = |headers: &[(String, String)], name: &str| headers.iter().find(|(header, _)| header.eq_ignore_ascii_case(name)).map(|(_, value)| value.clone());
+
+        for header in ["Authorization", "Proxy-Authorization", "X-Api-Key", "X-Debug", "X-Tenant-Secret"] {
+            assert_eq!(value(&capture.request_headers, header).as_deref(), Some(REDACTED), "{header}");
+        }
+
+        assert_eq!(value(&capture.request_headers, "Accept").as_deref(), Some("*/*"));
+        assert_eq!(value(&capture.response_headers, "Set-Cookie").as_deref(), Some(REDACTED));
+    }
+}
@@ -0,0 +1,274 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use rand::RngCore;
+use serde::Serialize;
+use serde::de::DeserializeOwned;
+use serde_json::Value;
+use crate::cookie::{Cookie, SameSite};
+use crate::cookie_jar::CookieJar;
+use crate::error::ServerError;
+use crate::message::{Request, Response};
+use crate::middleware::Next;
+
+pub const SESSION_COOKIE: &str = "session";
+
+pub type SessionData = HashMap<String, Value>;
+
+pub trait SessionStore: Sync + Send {
+    fn load(&self, id: &str) -> Option<SessionData>;
+    fn save(&self, id: &str, data: &SessionData, ttl: Duration);
+    fn remove(&self, id: &str);
+}
+
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    sessions: Mutex<HashMap<String, (Instant, SessionData)>>
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl SessionStore for MemoryStore {
+    fn load(&self, id: &str) -> Option<SessionData> {
+        let mut sessions = self.sessions.lock().unwrap();
+        let now = Instant::now();
+        sessions.retain(|_, (expires, _)| *expires > now);
+        sessions.get(id).map(|(_, data)| data.clone())
+    }
+
+    fn save(&self, id: &str, data: &SessionData, ttl: Duration) {
+        self.sessions.lock().unwrap().insert(id.to_string(), (Instant::now() + ttl, data.clone()));
+    }
+
+    fn remove(&self, id: &str) {
+        self.sessions.lock().unwrap().remove(id);
+    }
+}
+
+#[derive(Debug, Default)]
+struct SessionState {
+    id: Option<String>,
+    retired: Option<String>,
+    data: SessionData,
+    changed: bool,
+    destroyed: bool
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct Session(Arc<Mutex<SessionState>>);
+
+impl Session {
+    fn new(id: Option<String>, data: SessionData) -> Self {
+        Self(Arc::new(Mutex::new(SessionState {
+            id,
+            data,
+            ..SessionState::default()
+        })))
+    }
+
+    pub fn id(&self) -> Option<String> {
+        self.0.lock().unwrap().id.clone()
+    }
+
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        let state = self.0.lock().unwrap();
+        serde_json::from_value(state.data.get(key)?.clone()).ok()
+    }
+
+    pub fn set(&self, key: &str, value: impl Serialize) -> serde_json::Result<()> {
+        let value = serde_json::to_value(value)?;
+        let mut state = self.0.lock().unwrap();
+        state.data.insert(key.to_string(), value);
+        state.changed = true;
+        Ok(())
+    }
+
+    pub fn remove(&self, key: &str) {
+        let mut state = self.0.lock().unwrap();
+        state.changed |= state.data.remove(key).is_some();
+    }
+
+    pub fn clear(&self) {
+        let mut state = self.0.lock().unwrap();
+        state.data.clear();
+        state.changed = true;
+    }
+
+    pub fn regenerate(&self) {
+        let mut state = self.0.lock().unwrap();
+
+        if let Some(id) = state.id.take() {
+            state.retired = Some(id);
+        }
+
+        state.changed = true;
+    }
+
+    pub fn destroy(&self) {
+        let mut state = self.0.lock().unwrap();
+        state.data.clear();
+        state.destroyed = true;
+    }
+}
+
+#[derive(Clone)]
+pub struct Sessions {
+    store: Arc<dyn SessionStore>,
+    cookie: String,
+    ttl: Duration,
+    secure: bool,
+    jar: Option<CookieJar>
+}
+
+impl Sessions {
+    pub fn new(store: impl SessionStore + 'static) -> Self {
+        Self {
+            store: Arc::new(store),
+            cookie: SESSION_COOKIE.to_string(),
+            ttl: Duration::from_secs(24 * 60 * 60),
+            secure: false,
+            jar: None
+        }
+    }
+
+    pub fn cookie(mut self, cookie: &str) -> Self {
+        self.cookie = cookie.to_string();
+        self
+    }
+
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.ttl = ttl;
+        self
+    }
+
+    pub fn secure(mut self, secure: bool) -> Self {
+        self.secure = secure;
+        self
+    }
+
+    pub fn jar(mut self, jar: CookieJar) -> Self {
+        self.jar = Some(jar);
+        self
+    }
+
+    pub fn load(&self, request: &Request) -> Session {
+        let id = match &self.jar {
+            Some(jar) => jar.signed(request, &self.cookie),
+            None => request.cookie(&self.cookie).map(|id| id.to_string())
+        };
+
+        let loaded = id.and_then(|id| {
+            let data = self.store.load(&id)?;
+            Some((id, data))
+        });
+
+        match loaded {
+            Some((id, data)) => Session::new(Some(id), data),
+            None => Session::new(None, SessionData::new())
+        }
+    }
+
+    pub fn commit(&self, session: &Session, response: &mut Response) {
+        let mut state = session.0.lock().unwrap();
+
+        if let Some(retired) = state.retired.take() {
+            self.store.remove(&retired);
+        }
+
+        if state.destroyed {
+            if let Some(id) = state.id.take() {
+                self.store.remove(&id);
+                response.set_cookie(Cookie::removal(&self.cookie));
+            }
+
+            return;
+        }
+
+        if !state.changed {
+            return;
+        }
+
+        let id = state.id.get_or_insert_with(Self::generate_id).clone();
+        self.store.save(&id, &state.data, self.ttl);
+
+        let cookie = Cookie::new(&self.cookie, &id)
+            .path("/")
+            .max_age(self.ttl)
+            .http_only(true)
+            .secure(self.secure)
+            .same_site(SameSite::Lax);
+
+        match &self.jar {
+            Some(jar) => response.set_cookie(jar.sign(cookie)),
+            None => response.set_cookie(cookie)
+        }
+    }
+
+    pub fn middleware<E: ServerError>(self) -> impl Fn(&mut Request, Next<'_, E>) -> Result<Response, E> + Sync + Send + 'static {
+        move |request, next| {
+            let session = self.load(request);
+            request.set_session(session.clone());
+            let mut response = next.run(request)?;
+            self.commit(&session, &mut response);
+            Ok(response)
+        }
+    }
+
+    fn generate_id() -> String {
+        let mut bytes = [0_u8; 32];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        URL_SAFE_NO_PAD.encode(bytes)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+    use super::*;
+
+    fn request(cookie: &str) -> Request {
+        let bytes = format!("GET / HTTP/1.1\r\nHost: localhost\r\nCookie: {SESSION_COOKIE}={cookie}\r\n\r\n");
+        Request::from_bytes(SocketAddr::from(([127, 0, 0, 1], 1234)), bytes.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn regenerate_moves_the_data_to_a_new_id() {
+        let store = Arc::new(MemoryStore::new());
+        store.save("fixed", &SessionData::from([("user".to_string(), Value::from("alice"))]), Duration::from_secs(60));
+
+        let sessions = Sessions::new(ArcStore(store.clone()));
+        let session = sessions.load(&request("fixed"));
+        session.regenerate();
+
+        let mut response = Response::new(200);
+        sessions.commit(&session, &mut response);
+
+        let id = session.id().unwrap();
+        assert_ne!(id, "fixed");
+        assert!(store.load("fixed").is_none());
+        assert_eq!(store.load(&id).unwrap().get("user"), Some(&Value::from("alice")));
+        assert!(response.cookies().iter().any(|cookie| cookie.name() == SESSION_COOKIE && cookie.value() == id));
+    }
+
+    struct ArcStore(Arc<MemoryStore>);
+
+    impl SessionStore for ArcStore {
+        fn load(&self, id: &str) -> Option<SessionData> {
+            self.0.load(id)
+        }
+
+        fn save(&self, id: &str, data: &SessionData, ttl: Duration) {
+            self.0.save(id, data, ttl);
+        }
+
+        fn remove(&self, id: &str) {
+            self.0.remove(id);
+        }
+    }
+}
@@ -0,0 +1,113 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use crate::error::InvalidSignatureError;
+use crate::keyring::{self, Keyring, KeyringError};
+use crate::message::Request;
+
+const EXPIRES_PARAM: &str = "expires";
+const SIGNATURE_PARAM: &str = "signature";
+
+pub struct UrlSigner {
+    keys: Vec<Vec<u8>>
+}
+
+impl UrlSigner {
+    pub fn new(key: impl AsRef<[u8]>) -> Self {
+        Self {
+            keys: vec![key.as_ref().to_vec()]
+        }
+    }
+
+    pub fn from_keyring(keyring: &Keyring) -> Result<Self, KeyringError> {
+        let keys: Vec<Vec<u8>> = keyring.derive(keyring::SIGNED_URLS).secrets().map(|secret| secret.to_vec()).collect();
+
+        match keys.is_empty() {
+            true => Err(KeyringError::Empty),
+            false => Ok(Self { keys })
+        }
+    }
+
+    pub fn sign(&self, path: &str, expires_in: Duration) -> String {
+        let expires = (SystemTime::now() + expires_in).duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        let signature = URL_SAFE_NO_PAD.encode(Self::mac(&self.keys[0], path, expires).finalize().into_bytes());
+        let separator = if path.contains('?') { '&' } else { '?' };
+
+        format!("{path}{separator}{EXPIRES_PARAM}={expires}&{SIGNATURE_PARAM}={signature}")
+    }
+
+    pub fn verify(&self, request: &Request) -> Result<(), InvalidSignatureError> {
+        let mut expires = None;
+        let mut signature = None;
+
+        for (key, value) in request.url().query_pairs() {
+            match key.as_ref() {
+                EXPIRES_PARAM => expires = value.parse::<u64>().ok(),
+                SIGNATURE_PARAM => signature = URL_SAFE_NO_PAD.decode(value.as_bytes()).ok(),
+                _ => ()
+            }
+        }
+
+        let (expires, signature) = expires.zip(signature).ok_or(InvalidSignatureError)?;
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+
+        if now > expires {
+            return Err(InvalidSignatureError);
+        }
+
+        self.keys.iter()
+            .any(|key| Self::mac(key, request.raw_route(), expires).verify_slice(&signature).is_ok())
+            .then_some(())
+            .ok_or(InvalidSignatureError)
+    }
+
+    fn mac(key: &[u8], path: &str, expires: u64) -> Hmac<Sha256> {
+        let path = path.split('?').next().unwrap_or(path);
+        let mut mac = Hmac::<Sha256>::new_from_slice(key).expect("HMAC accepts keys of any length");
+        mac.update(format!("{path}\n{expires}").as_bytes());
+        mac
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+    use super::*;
+
+    fn request(target: &str) -> Request {
+        let bytes = format!("GET {target} HTTP/1.1\r\nHost: localhost\r\n\r\n");
+        Request::from_bytes(SocketAddr::from(([127, 0, 0, 1], 1234)), bytes.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn verifies_signed_url() {
+        let signer = UrlSigner::new("secret");
+        let url = signer.sign("/files/report.pdf", Duration::from_secs(60));
+        assert!(signer.verify(&request(&url)).is_ok());
+    }
+
+    #[test]
+    fn verifies_percent_encoded_path() {
+        let signer = UrlSigner::new("secret");
+        let url = signer.sign("/files/annual%20report.pdf", Duration::from_secs(60));
+        assert!(signer.verify(&request(&url)).is_ok());
+    }
+
+    #[test]
+    fn rejects_tampered_path_and_wrong_key() {
+        let signer = UrlSigner::new("secret");
+        let url = signer.sign("/files/a.pdf", Duration::from_secs(60));
+        assert!(signer.verify(&request(&url.replace("a.pdf", "b.pdf"))).is_err());
+        assert!(UrlSigner::new("other").verify(&request(&url)).is_err());
+    }
+
+    #[test]
+    fn rejects_expired_url() {
+        let signer = UrlSigner::new("secret");
+        let signature = URL_SAFE_NO_PAD.encode(UrlSigner::mac(b"secret", "/files/a.pdf", 1).finalize().into_bytes());
+        let url = format!("/files/a.pdf?{EXPIRES_PARAM}=1&{SIGNATURE_PARAM}={signature}");
+        assert!(signer.verify(&request(&url)).is_err());
+    }
+}
@@ -0,0 +1,178 @@
+use std::io::ErrorKind;
+use std::path::{Component, Path, PathBuf};
+use crate::error::ServerError;
+use crate::message::{Request, Response};
+use crate::method::HttpMethod;
+use crate::middleware::Next;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StaticFile {
+    Found(PathBuf),
+    Forbidden,
+    NotFound
+}
+
+#[derive(Debug, Clone)]
+pub struct StaticFiles {
+    prefix: String,
+    root: PathBuf,
+    index: String
+}
+
+impl StaticFiles {
+    pub fn new(prefix: &str, root: impl AsRef<Path>) -> Self {
+        Self {
+            prefix: format!("/{}", prefix.trim_matches('/')),
+            root: root.as_ref().to_path_buf(),
+            index: "index.html".to_string()
+        }
+    }
+
+    pub fn index(mut self, index: &str) -> Self {
+        self.index = index.to_string();
+        self
+    }
+
+    pub fn prefix(&self) -> &str {
+        &self.prefix
+    }
+
+    pub fn check(&self) -> Result<(), String> {
+        match self.root.is_dir() {
+            true => Ok(()),
+            false => Err(format!("Static root {} is not a readable directory", self.root.display()))
+        }
+    }
+
+    pub fn resolve(&self, route: &str) -> StaticFile {
+        let Some(relative) = route.strip_prefix(self.prefix.trim_end_matches('/')) else {
+            return StaticFile::NotFound;
+        };
+
+        let relative = Path::new(relative.trim_start_matches('/'));
+
+        if relative.components().any(|component| !matches!(component, Component::Normal(_) | Component::CurDir)) {
+            return StaticFile::Forbidden;
+        }
+
+        let mut path = self.root.join(relative);
+
+        if path.is_dir() {
+            path.push(&self.index);
+
+            if !path.is_file() {
+                return StaticFile::Forbidden;
+            }
+        }
+
+        let (Ok(root), Ok(canonical)) = (self.root.canonicalize(), path.canonicalize()) else {
+            return StaticFile::NotFound;
+        };
+
+        match canonical.starts_with(root) {
+            true if canonical.is_file() => StaticFile::Found(canonical),
+            true => StaticFile::NotFound,
+            false => StaticFile::Forbidden
+        }
+    }
+
+    pub fn middleware<E: ServerError>(self) -> impl Fn(&mut Request, Next<'_, E>) -> Result<Response, E> + Sync + Send + 'static {
+        move |request, next| {
+            if !matches!(request.method(), HttpMethod::Get | HttpMethod::Head) {
+                return next.run(request);
+            }
+
+            let path = match self.resolve(request.route()) {
+                StaticFile::Found(path) => path,
+                StaticFile::Forbidden => return Ok(Response::text("Forbidden", 403)),
+                StaticFile::NotFound => return next.run(request)
+            };
+
+            match Response::precompressed_file(request, &path.to_string_lossy(), 200) {
+                Ok(response) => Ok(response),
+                Err(err) if err.kind() == ErrorKind::PermissionDenied => Ok(Response::text("Forbidden", 403)),
+                Err(_) => next.run(request)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::net::SocketAddr;
+    use crate::error::DefaultError;
+    use crate::middleware::Middleware;
+    use crate::route::Router;
+    use super::*;
+
+    fn serve(name: &str, request: &str) -> Vec<u8> {
+        let root = std::env::temp_dir().join(format!("http-server-static-{name}-{}", std::process::id()));
+        fs::create_dir_all(&root).unwrap();
+        fs::write(root.join("data.txt"), "0123456789").unwrap();
+        fs::write(root.join("logo"), b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR").unwrap();
+        fs::write(root.join("page"), "<!doctype html><script>alert(1)</script>").unwrap();
+
+        let router = Router::new(|_: &Request| Err(DefaultError::NotFound));
+        let middleware = [Middleware::new("/assets", StaticFiles::new("/assets", &root).middleware())];
+        let mut request = Request::from_bytes(SocketAddr::from(([127, 0, 0, 1], 1234)), request.as_bytes()).unwrap();
+        let mut response = Next::new(&middleware, &router).run(&mut request).unwrap();
+        response.sniff_content_type();
+        fs::remove_dir_all(&root).ok();
+
+        let mut written = Vec::new();
+        response.write_to(&mut written).unwrap();
+        written
+    }
+
+    #[test]
+    fn streams_files_with_their_length() {
+        let written = String::from_utf8(serve("full", "GET /assets/data.txt HTTP/1.1\r\nHost: localhost\r\n\r\n")).unwrap();
+        assert!(written.starts_with("HTTP/1.1 200 OK\r\n"));
+        assert!(written.contains("Accept-Ranges: bytes\r\n"));
+        assert!(written.contains("Content-Length: 10\r\n"));
+        assert!(!written.contains("Transfer-Encoding"));
+        assert!(written.ends_with("\r\n\r\n0123456789"));
+    }
+
+    #[test]
+    fn sniffs_extensionless_files_but_never_as_html() {
+        let written = serve("png", "GET /assets/logo HTTP/1.1\r\nHost: localhost\r\n\r\n");
+        assert!(String::from_utf8_lossy(&written).contains("Content-Type: image/png\r\n"));
+        assert!(written.ends_with(b"\r\n\r\n\x89PNG\r\n\x1a\n\0\0\0\rIHDR"));
+
+        let written = String::from_utf8(serve("html", "GET /assets/page HTTP/1.1\r\nHost: localhost\r\n\r\n")).unwrap();
+        assert!(written.contains("Content-Type: text/plain; charset=utf-8\r\n"));
+    }
+
+    #[test]
+    fn streams_only_the_requested_range() {
+        let written = String::from_utf8(serve("range", "GET /assets/data.txt HTTP/1.1\r\nHost: localhost\r\nRange: bytes=3-5\r\n\r\n")).unwrap();
+        assert!(written.starts_with("HTTP/1.1 206 Partial Content\r\n"));
+        assert!(written.contains("Content-Range: bytes 3-5/10\r\n"));
+        assert!(written.contains("Content-Length: 3\r\n"));
+        assert!(written.ends_with("\r\n\r\n345"));
+    }
+
+    #[test]
+    fn rejects_ranges_past_the_end() {
+        let written = String::from_utf8(serve("unsatisfiable", "GET /assets/data.txt HTTP/1.1\r\nHost: localhost\r\nRange: bytes=20-\r\n\r\n")).unwrap();
+        assert!(written.starts_with("HTTP/1.1 416 "));
+        assert!(written.contains("Content-Range: bytes */10\r\n"));
+        assert!(written.contains("Content-Length: 0\r\n"));
+    }
+
+    #[test]
+    fn file_range_ignores_stale_if_range() {
+        let path = std::env::temp_dir().join(format!("http-server-file-range-{}.txt", std::process::id()));
+        fs::write(&path, "0123456789").unwrap();
+        let request = "GET /data.txt HTTP/1.1\r\nHost: localhost\r\nRange: bytes=3-5\r\nIf-Range: \"stale\"\r\n\r\n";
+        let request = Request::from_bytes(SocketAddr::from(([127, 0, 0, 1], 1234)), request.as_bytes()).unwrap();
+        let response = Response::file_range(&request, &path.to_string_lossy()).unwrap();
+        fs::remove_file(&path).ok();
+
+        assert_eq!(response.status(), 200);
+        assert_eq!(response.get_header("Content-Length"), Some("10"));
+        assert!(response.is_stream());
+    }
+}
@@ -0,0 +1,209 @@
+use std::io::{self, Write};
+use std::sync::{Arc, OnceLock};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use serde::Serialize;
+use crate::message::Response;
+use crate::parser::MemoryBudget;
+use crate::prefork;
+use crate::pool::{self, PoolSnapshot, PoolStats, WorkerSnapshot};
+use crate::usage::{self, ProcessUsage};
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct StatsSnapshot {
+    pub open_connections: u64,
+    pub total_connections: u64,
+    pub total_requests: u64,
+    pub client_errors: u64,
+    pub server_errors: u64,
+    pub bytes_in: u64,
+    pub bytes_out: u64,
+    pub buffered_bytes: u64,
+    pub shed_requests: u64,
+    pub uptime: Duration,
+    pub process: ProcessUsage,
+    pub workers: Vec<ProcessUsage>,
+    pub pool: Option<PoolSnapshot>
+}
+
+#[derive(Debug, Default)]
+pub struct ServerStats {
+    started: OnceLock<Instant>,
+    open_connections: AtomicU64,
+    total_connections: AtomicU64,
+    total_requests: AtomicU64,
+    client_errors: AtomicU64,
+    server_errors: AtomicU64,
+    bytes_in: AtomicU64,
+    bytes_out: AtomicU64,
+    buffered_bytes: AtomicU64,
+    shed_requests: AtomicU64,
+    pool: OnceLock<Arc<PoolStats>>
+}
+
+impl ServerStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn snapshot(&self) -> StatsSnapshot {
+        StatsSnapshot {
+            open_connections: self.open_connections.load(Ordering::Relaxed),
+            total_connections: self.total_connections.load(Ordering::Relaxed),
+            total_requests: self.total_requests.load(Ordering::Relaxed),
+            client_errors: self.client_errors.load(Ordering::Relaxed),
+            server_errors: self.server_errors.load(Ordering::Relaxed),
+            bytes_in: self.bytes_in.load(Ordering::Relaxed),
+            bytes_out: self.bytes_out.load(Ordering::Relaxed),
+            buffered_bytes: self.buffered_bytes.load(Ordering::Relaxed),
+            shed_requests: self.shed_requests.load(Ordering::Relaxed),
+            uptime: self.uptime(),
+            process: usage::current_process(),
+            workers: prefork::workers_usage(),
+            pool: self.pool.get().map(|pool| pool.snapshot())
+        }
+    }
+
+    pub fn workers(&self) -> Vec<WorkerSnapshot> {
+        self.pool.get().map(|pool| pool.workers()).unwrap_or_default()
+    }
+
+    pub fn queue_depth(&self) -> usize {
+        self.pool.get().map(|pool| pool.queue_depth()).unwrap_or_default()
+    }
+
+    pub fn uptime(&self) -> Duration {
+        self.started.get().map(Instant::elapsed).unwrap_or_default()
+    }
+
+    pub(crate) fn pool(&self) -> Arc<PoolStats> {
+        self.pool.get_or_init(|| Arc::new(PoolStats::default())).clone()
+    }
+
+    pub(crate) fn start(&self) {
+        self.started.get_or_init(Instant::now);
+    }
+
+    pub(crate) fn connection(self: &Arc<Self>) -> ConnectionGuard {
+        self.open_connections.fetch_add(1, Ordering::Relaxed);
+        self.total_connections.fetch_add(1, Ordering::Relaxed);
+        ConnectionGuard(self.clone())
+    }
+
+    pub(crate) fn request(&self, bytes: usize) {
+        self.total_requests.fetch_add(1, Ordering::Relaxed);
+        self.bytes_in.fetch_add(bytes as u64, Ordering::Relaxed);
+        pool::record_request();
+    }
+
+    pub(crate) fn response(&self, response: &Response) {
+        let counter = match response.status().as_u16() {
+            400..=499 => &self.client_errors,
+            500..=599 => &self.server_errors,
+            _ => return
+        };
+
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn shed(&self) {
+        self.shed_requests.fetch_add(1, Ordering::Relaxed);
+    }
+
+    pub(crate) fn reserve(self: &Arc<Self>, bytes: usize, limit: Option<usize>) -> Option<BufferGuard> {
+        self.try_reserve(bytes as u64, limit).then(|| BufferGuard(self.clone(), bytes as u64))
+    }
+
+    pub(crate) fn reservation(self: &Arc<Self>, limit: Option<usize>) -> Reservation {
+        Reservation {
+            guard: BufferGuard(self.clone(), 0),
+            limit
+        }
+    }
+
+    fn try_reserve(&self, bytes: u64, limit: Option<usize>) -> bool {
+        let limit = limit.map_or(u64::MAX, |limit| limit as u64);
+
+        let reserved = self.buffered_bytes.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |used| {
+            used.checked_add(bytes).filter(|total| *total <= limit)
+        });
+
+        if reserved.is_err() {
+            self.shed_requests.fetch_add(1, Ordering::Relaxed);
+        }
+
+        reserved.is_ok()
+    }
+
+    pub(crate) fn writer<W: Write>(self: &Arc<Self>, inner: W) -> StatsWriter<W> {
+        StatsWriter {
+            inner,
+            stats: self.clone(),
+            written: 0
+        }
+    }
+}
+
+pub(crate) struct ConnectionGuard(Arc<ServerStats>);
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        self.0.open_connections.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+pub(crate) struct BufferGuard(Arc<ServerStats>, u64);
+
+impl Drop for BufferGuard {
+    fn drop(&mut self) {
+        self.0.buffered_bytes.fetch_sub(self.1, Ordering::Relaxed);
+    }
+}
+
+pub(crate) struct Reservation {
+    guard: BufferGuard,
+    limit: Option<usize>
+}
+
+impl MemoryBudget for Reservation {
+    fn reserve(&mut self, bytes: usize) -> bool {
+        let extra = (bytes as u64).saturating_sub(self.guard.1);
+
+        if extra == 0 {
+            return true;
+        }
+
+        let reserved = self.guard.0.try_reserve(extra, self.limit);
+
+        if reserved {
+            self.guard.1 += extra;
+        }
+
+        reserved
+    }
+}
+
+pub(crate) struct StatsWriter<W: Write> {
+    inner: W,
+    stats: Arc<ServerStats>,
+    written: u64
+}
+
+impl <W: Write> StatsWriter<W> {
+    pub(crate) fn written(&self) -> u64 {
+        self.written
+    }
+}
+
+impl <W: Write> Write for StatsWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let size = self.inner.write(buf)?;
+        self.stats.bytes_out.fetch_add(size as u64, Ordering::Relaxed);
+        self.written += size as u64;
+        Ok(size)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
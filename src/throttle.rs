@@ -0,0 +1,122 @@
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+#[derive(Debug)]
+pub struct TokenBucket {
+    rate: f64,
+    capacity: u64,
+    tokens: f64,
+    last: Instant
+}
+
+impl TokenBucket {
+    pub fn new(rate: u64) -> Self {
+        Self::with_rate(rate as f64, rate.max(1))
+    }
+
+    pub fn with_rate(rate: f64, capacity: u64) -> Self {
+        Self {
+            rate,
+            capacity,
+            tokens: capacity as f64,
+            last: Instant::now()
+        }
+    }
+
+    pub fn capacity(&self) -> u64 {
+        self.capacity
+    }
+
+    pub fn take(&mut self, amount: u64) -> Duration {
+        self.refill();
+        self.tokens -= amount as f64;
+
+        if self.tokens >= 0.0 || self.rate <= 0.0 {
+            Duration::ZERO
+        } else {
+            Duration::from_secs_f64(-self.tokens / self.rate)
+        }
+    }
+
+    pub fn try_take(&mut self, amount: u64) -> Result<(), Duration> {
+        self.refill();
+
+        if self.tokens >= amount as f64 {
+            self.tokens -= amount as f64;
+            return Ok(());
+        }
+
+        match self.rate > 0.0 {
+            true => Err(Duration::from_secs_f64((amount as f64 - self.tokens) / self.rate)),
+            false => Err(Duration::MAX)
+        }
+    }
+
+    pub fn is_full(&mut self) -> bool {
+        self.refill();
+        self.tokens >= self.capacity as f64
+    }
+
+    fn refill(&mut self) {
+        let now = Instant::now();
+        self.tokens = (self.tokens + now.duration_since(self.last).as_secs_f64() * self.rate).min(self.capacity as f64);
+        self.last = now;
+    }
+}
+
+pub struct ThrottledWriter<W: Write> {
+    inner: W,
+    connection: Option<TokenBucket>,
+    global: Option<Arc<Mutex<TokenBucket>>>
+}
+
+impl <W: Write> ThrottledWriter<W> {
+    pub fn new(inner: W, connection: Option<u64>, global: Option<Arc<Mutex<TokenBucket>>>) -> Self {
+        Self {
+            inner,
+            connection: connection.map(TokenBucket::new),
+            global
+        }
+    }
+
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    fn chunk_size(&self) -> usize {
+        let connection = self.connection.as_ref().map(TokenBucket::capacity);
+        let global = self.global.as_ref().map(|bucket| bucket.lock().unwrap().capacity());
+
+        match connection.into_iter().chain(global).min() {
+            Some(capacity) => capacity as usize,
+            None => usize::MAX
+        }
+    }
+}
+
+impl <W: Write> Write for ThrottledWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let size = buf.len().min(self.chunk_size());
+        let mut wait = Duration::ZERO;
+
+        if let Some(bucket) = self.connection.as_mut() {
+            wait = wait.max(bucket.take(size as u64));
+        }
+
+        if let Some(bucket) = &self.global {
+            wait = wait.max(bucket.lock().unwrap().take(size as u64));
+        }
+
+        if !wait.is_zero() {
+            thread::sleep(wait);
+        }
+
+        self.inner.write(&buf[..size])
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
@@ -0,0 +1,150 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use serde::Serialize;
+use crate::message::{Request, Response};
+use crate::method::HttpMethod;
+
+const MAX_PATHS: usize = 1024;
+
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct PathTransfers {
+    pub full: u64,
+    pub partial: u64,
+    pub resumed: u64,
+    pub bytes: u64
+}
+
+#[derive(Debug)]
+pub struct TransferStats {
+    paths: Mutex<HashMap<String, PathTransfers>>,
+    max_paths: usize
+}
+
+impl Default for TransferStats {
+    fn default() -> Self {
+        Self {
+            paths: Mutex::new(HashMap::new()),
+            max_paths: MAX_PATHS
+        }
+    }
+}
+
+impl TransferStats {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn max_paths(mut self, max_paths: usize) -> Self {
+        self.max_paths = max_paths;
+        self
+    }
+
+    pub fn record(&self, request: &Request, response: &Response, bytes: u64) {
+        if request.method() != HttpMethod::Get || !matches!(response.status().as_u16(), 200 | 206) {
+            return;
+        }
+
+        let Some(file) = response.served_file() else {
+            return;
+        };
+
+        let mut paths = self.paths.lock().unwrap();
+
+        if paths.len() >= self.max_paths && !paths.contains_key(file) {
+            return;
+        }
+
+        let transfers = paths.entry(file.to_string()).or_default();
+        transfers.bytes += bytes;
+
+        if response.status() == 206 {
+            transfers.partial += 1;
+
+            if Self::is_resumption(request) {
+                transfers.resumed += 1;
+            }
+        } else {
+            transfers.full += 1;
+        }
+    }
+
+    pub fn path(&self, path: &str) -> Option<PathTransfers> {
+        self.paths.lock().unwrap().get(path).copied()
+    }
+
+    pub fn snapshot(&self) -> HashMap<String, PathTransfers> {
+        self.paths.lock().unwrap().clone()
+    }
+
+    fn is_resumption(request: &Request) -> bool {
+        let starts_after_zero = request.header("range")
+            .and_then(|range| range.trim().strip_prefix("bytes="))
+            .and_then(|ranges| ranges.split(',').next())
+            .and_then(|range| range.split('-').next())
+            .and_then(|start| start.trim().parse::<u64>().ok())
+            .is_some_and(|start| start > 0);
+
+        starts_after_zero || request.header("if-range").is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::net::SocketAddr;
+    use super::*;
+
+    fn get(target: &str, range: Option<&str>) -> Request {
+        let range = range.map(|range| format!("Range: {range}\r\n")).unwrap_or_default();
+        let bytes = format!("GET {target} HTTP/1.1\r\nHost: localhost\r\n{range}\r\n");
+        Request::from_bytes(SocketAddr::from(([127, 0, 0, 1], 1234)), bytes.as_bytes()).unwrap()
+    }
+
+    fn file(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("http-server-transfers-{name}-{}.txt", std::process::id()));
+        fs::write(&path, "0123456789").unwrap();
+        path.to_string_lossy().into_owned()
+    }
+
+    #[test]
+    fn keys_transfers_by_served_file() {
+        let path = file("keyed");
+        let stats = TransferStats::new();
+
+        for target in ["/a?x=1", "/a?x=2"] {
+            let request = get(target, Some("bytes=2-"));
+            stats.record(&request, &Response::file_range(&request, &path).unwrap(), 40);
+        }
+
+        let full = get("/b", None);
+        stats.record(&full, &Response::file_range(&full, &path).unwrap(), 50);
+        fs::remove_file(&path).ok();
+
+        let transfers = stats.path(&path).unwrap();
+        assert_eq!((transfers.full, transfers.partial, transfers.resumed, transfers.bytes), (1, 2, 2, 130));
+        assert_eq!(stats.snapshot().len(), 1);
+    }
+
+    #[test]
+    fn ignores_responses_that_are_not_files() {
+        let stats = TransferStats::new();
+        stats.record(&get("/api", None), &Response::text("hello", 200), 5);
+        assert!(stats.snapshot().is_empty());
+    }
+
+    #[test]
+    fn stops_tracking_new_paths_at_the_limit() {
+        let (first, second) = (file("first"), file("second"));
+        let stats = TransferStats::new().max_paths(1);
+        let request = get("/", None);
+
+        stats.record(&request, &Response::file(&first, 200).unwrap(), 10);
+        stats.record(&request, &Response::file(&second, 200).unwrap(), 10);
+        stats.record(&request, &Response::file(&first, 200).unwrap(), 10);
+        fs::remove_file(&first).ok();
+        fs::remove_file(&second).ok();
+
+        assert_eq!(stats.snapshot().len(), 1);
+        assert_eq!(stats.path(&first).unwrap().full, 2);
+    }
+}
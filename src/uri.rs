@@ -0,0 +1,105 @@
+use percent_encoding::percent_decode_str;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum DotSegments {
+    #[default]
+    Remove,
+    Reject
+}
+
+pub fn split_target(target: &str) -> (&str, &str) {
+    match target.find(['?', '#']) {
+        Some(index) => target.split_at(index),
+        None => (target, "")
+    }
+}
+
+pub fn decode(text: &str) -> String {
+    percent_decode_str(text).decode_utf8_lossy().into_owned()
+}
+
+fn dot_segment(segment: &str) -> Option<&'static str> {
+    match decode(segment).as_str() {
+        "." => Some("."),
+        ".." => Some(".."),
+        _ => None
+    }
+}
+
+pub fn has_dot_segments(target: &str) -> bool {
+    split_target(target).0.split('/').any(|segment| dot_segment(segment).is_some())
+}
+
+pub fn remove_dot_segments(path: &str) -> String {
+    let absolute = path.starts_with('/');
+    let segments: Vec<&str> = path.split('/').skip(absolute as usize).collect();
+    let mut output: Vec<&str> = Vec::new();
+
+    for (index, segment) in segments.iter().enumerate() {
+        let last = index + 1 == segments.len();
+
+        match dot_segment(segment) {
+            Some(".") => (),
+            Some(_) => {
+                output.pop();
+            },
+            None => {
+                output.push(segment);
+                continue;
+            }
+        }
+
+        if last {
+            output.push("");
+        }
+    }
+
+    match absolute {
+        true => format!("/{}", output.join("/")),
+        false => output.join("/")
+    }
+}
+
+pub fn canonicalize(target: &str) -> String {
+    let (path, rest) = split_target(target);
+    format!("{}{rest}", remove_dot_segments(path))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::SocketAddr;
+    use crate::message::Request;
+    use super::*;
+
+    #[test]
+    fn removes_dot_segments() {
+        assert_eq!(remove_dot_segments("/a/b/c/./../../g"), "/a/g");
+        assert_eq!(remove_dot_segments("/a/./b/"), "/a/b/");
+        assert_eq!(remove_dot_segments("/a/b/.."), "/a/");
+        assert_eq!(remove_dot_segments("/a/b/."), "/a/b/");
+        assert_eq!(remove_dot_segments("/../../etc/passwd"), "/etc/passwd");
+        assert_eq!(remove_dot_segments("/a/..b/c"), "/a/..b/c");
+    }
+
+    #[test]
+    fn removes_percent_encoded_dot_segments() {
+        assert_eq!(remove_dot_segments("/static/%2e%2E/secret"), "/secret");
+        assert_eq!(remove_dot_segments("/static/%2e/file"), "/static/file");
+        assert!(has_dot_segments("/static/%2E%2e/secret"));
+    }
+
+    #[test]
+    fn canonicalizes_only_the_path() {
+        assert_eq!(canonicalize("/a/../b?next=/c/../d#/e/.."), "/b?next=/c/../d#/e/..");
+        assert!(!has_dot_segments("/search?path=../etc"));
+    }
+
+    #[test]
+    fn requests_use_the_canonical_route_and_keep_the_raw_target() {
+        let bytes = b"GET /files/../admin/./users HTTP/1.1\r\nHost: localhost\r\n\r\n";
+        let request = Request::from_bytes(SocketAddr::from(([127, 0, 0, 1], 1234)), bytes).unwrap();
+
+        assert_eq!(request.route(), "/admin/users");
+        assert_eq!(request.target(), "/files/../admin/./users");
+    }
+}
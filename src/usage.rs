@@ -0,0 +1,122 @@
+use std::time::Duration;
+use serde::{Deserialize, Serialize};
+use crate::prefork;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct ProcessUsage {
+    pub pid: u32,
+    pub worker: Option<usize>,
+    pub cpu_time: Option<Duration>,
+    pub resident_memory: Option<u64>
+}
+
+pub fn current_process() -> ProcessUsage {
+    ProcessUsage {
+        pid: std::process::id(),
+        worker: prefork::worker_index(),
+        cpu_time: process_cpu_time(),
+        resident_memory: resident_memory()
+    }
+}
+
+#[cfg(unix)]
+pub fn thread_cpu_time() -> Option<Duration> {
+    clock(libc::CLOCK_THREAD_CPUTIME_ID)
+}
+
+#[cfg(not(unix))]
+pub fn thread_cpu_time() -> Option<Duration> {
+    None
+}
+
+#[cfg(unix)]
+pub fn process_cpu_time() -> Option<Duration> {
+    clock(libc::CLOCK_PROCESS_CPUTIME_ID)
+}
+
+#[cfg(not(unix))]
+pub fn process_cpu_time() -> Option<Duration> {
+    None
+}
+
+#[cfg(target_os = "linux")]
+pub fn resident_memory() -> Option<u64> {
+    let statm = std::fs::read_to_string("/proc/self/statm").ok()?;
+    let pages: u64 = statm.split_whitespace().nth(1)?.parse().ok()?;
+    Some(pages * page_size()?)
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn resident_memory() -> Option<u64> {
+    None
+}
+
+#[cfg(target_os = "linux")]
+pub fn of_process(pid: u32) -> Option<ProcessUsage> {
+    let stat = std::fs::read_to_string(format!("/proc/{pid}/stat")).ok()?;
+    let ticks = unsafe { libc::sysconf(libc::_SC_CLK_TCK) };
+    let ticks = u64::try_from(ticks).ok().filter(|ticks| *ticks > 0)?;
+    parse_stat(pid, &stat, ticks, page_size())
+}
+
+#[cfg(target_os = "linux")]
+fn parse_stat(pid: u32, stat: &str, ticks: u64, page_size: Option<u64>) -> Option<ProcessUsage> {
+    let fields: Vec<&str> = stat.rsplit_once(')')?.1.split_whitespace().collect();
+    let cpu_ticks = fields.get(11)?.parse::<u64>().ok()? + fields.get(12)?.parse::<u64>().ok()?;
+    let pages: u64 = fields.get(21)?.parse().ok()?;
+
+    Some(ProcessUsage {
+        pid,
+        worker: None,
+        cpu_time: Some(Duration::from_secs_f64(cpu_ticks as f64 / ticks as f64)),
+        resident_memory: page_size.map(|size| pages * size)
+    })
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn of_process(_: u32) -> Option<ProcessUsage> {
+    None
+}
+
+#[cfg(unix)]
+fn clock(clock: libc::clockid_t) -> Option<Duration> {
+    let mut time = libc::timespec { tv_sec: 0, tv_nsec: 0 };
+
+    match unsafe { libc::clock_gettime(clock, &mut time) } {
+        0 => Some(Duration::new(time.tv_sec as u64, time.tv_nsec as u32)),
+        _ => None
+    }
+}
+
+#[cfg(target_os = "linux")]
+fn page_size() -> Option<u64> {
+    u64::try_from(unsafe { libc::sysconf(libc::_SC_PAGESIZE) }).ok().filter(|size| *size > 0)
+}
+
+#[cfg(all(test, target_os = "linux"))]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reads_the_current_process() {
+        let usage = of_process(std::process::id()).unwrap();
+        assert_eq!(usage.pid, std::process::id());
+        assert!(usage.cpu_time.is_some());
+        assert!(usage.resident_memory.is_some_and(|rss| rss > 0));
+    }
+
+    #[test]
+    fn reads_cpu_time_and_rss_from_their_stat_fields() {
+        let stat = "42 (a) b (c)) S 1 42 42 0 -1 4194560 100 0 0 0 250 150 0 0 20 0 1 0 1000 10485760 300 18446744073709551615";
+        let usage = parse_stat(42, stat, 100, Some(4096)).unwrap();
+
+        assert_eq!(usage.cpu_time, Some(Duration::from_secs(4)));
+        assert_eq!(usage.resident_memory, Some(300 * 4096));
+    }
+
+    #[test]
+    fn rejects_truncated_stat_lines() {
+        assert!(parse_stat(42, "42 (a) S 1 42 42 0 -1", 100, Some(4096)).is_none());
+        assert!(parse_stat(42, "garbage", 100, Some(4096)).is_none());
+    }
+}
@@ -0,0 +1,212 @@
+use std::collections::VecDeque;
+use std::io;
+use std::sync::{Arc, Mutex, OnceLock};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use hmac::{Hmac, Mac};
+use serde::Serialize;
+use sha2::Sha256;
+use crate::client::Client;
+use crate::method::HttpMethod;
+use crate::pool::{PoolStats, ThreadPool};
+
+pub const SIGNATURE_HEADER: &str = "X-Webhook-Signature";
+pub const TIMESTAMP_HEADER: &str = "X-Webhook-Timestamp";
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub enum DeliveryStatus {
+    Pending,
+    Delivered(u16),
+    Failed
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Delivery {
+    pub id: u64,
+    pub url: String,
+    pub status: DeliveryStatus,
+    pub attempts: u32,
+    pub last_error: Option<String>
+}
+
+#[derive(Clone)]
+pub struct WebhookDispatcher {
+    secret: Vec<u8>,
+    max_attempts: u32,
+    base_delay: Duration,
+    max_delay: Duration,
+    workers: usize,
+    history: usize,
+    client: Arc<Client>,
+    pool: Arc<OnceLock<ThreadPool>>,
+    next_id: Arc<AtomicU64>,
+    deliveries: Arc<Mutex<VecDeque<Delivery>>>
+}
+
+impl WebhookDispatcher {
+    pub fn new(secret: impl AsRef<[u8]>) -> Self {
+        Self {
+            secret: secret.as_ref().to_vec(),
+            max_attempts: 5,
+            base_delay: Duration::from_millis(500),
+            max_delay: Duration::from_secs(60),
+            workers: 4,
+            history: 1000,
+            client: Arc::new(Client::new().timeout(Duration::from_secs(10))),
+            pool: Arc::new(OnceLock::new()),
+            next_id: Arc::new(AtomicU64::new(1)),
+            deliveries: Arc::new(Mutex::new(VecDeque::new()))
+        }
+    }
+
+    pub fn max_attempts(mut self, attempts: u32) -> Self {
+        self.max_attempts = attempts.max(1);
+        self
+    }
+
+    pub fn base_delay(mut self, delay: Duration) -> Self {
+        self.base_delay = delay;
+        self
+    }
+
+    pub fn max_delay(mut self, delay: Duration) -> Self {
+        self.max_delay = delay;
+        self
+    }
+
+    pub fn workers(mut self, workers: usize) -> Self {
+        self.workers = workers.max(1);
+        self
+    }
+
+    pub fn history(mut self, deliveries: usize) -> Self {
+        self.history = deliveries.max(1);
+        self
+    }
+
+    pub fn client(mut self, client: Client) -> Self {
+        self.client = Arc::new(client);
+        self
+    }
+
+    pub fn dispatch(&self, url: &str, payload: &impl Serialize) -> serde_json::Result<u64> {
+        let body = serde_json::to_vec(payload)?;
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+
+        {
+            let mut deliveries = self.deliveries.lock().unwrap();
+
+            if deliveries.len() == self.history {
+                deliveries.pop_front();
+            }
+
+            deliveries.push_back(Delivery {
+                id,
+                url: url.to_string(),
+                status: DeliveryStatus::Pending,
+                attempts: 0,
+                last_error: None
+            });
+        }
+
+        let dispatcher = self.clone();
+        let url = url.to_string();
+        let pool = self.pool.get_or_init(|| ThreadPool::new(self.workers, Arc::new(PoolStats::default()), "webhook"));
+        pool.execute(move || dispatcher.deliver(id, &url, &body));
+        Ok(id)
+    }
+
+    pub fn delivery(&self, id: u64) -> Option<Delivery> {
+        self.deliveries.lock().unwrap().iter().find(|delivery| delivery.id == id).cloned()
+    }
+
+    pub fn deliveries(&self) -> Vec<Delivery> {
+        self.deliveries.lock().unwrap().iter().cloned().collect()
+    }
+
+    pub fn sign(&self, timestamp: u64, body: &[u8]) -> String {
+        let mut mac = Hmac::<Sha256>::new_from_slice(&self.secret).expect("HMAC accepts keys of any length");
+        mac.update(timestamp.to_string().as_bytes());
+        mac.update(b".");
+        mac.update(body);
+        let signature: String = mac.finalize().into_bytes().iter().map(|byte| format!("{byte:02x}")).collect();
+        format!("sha256={signature}")
+    }
+
+    fn deliver(&self, id: u64, url: &str, body: &[u8]) {
+        for attempt in 0..self.max_attempts {
+            if attempt > 0 {
+                thread::sleep(self.retry_delay(attempt));
+            }
+
+            let result = self.send(url, body);
+            let mut deliveries = self.deliveries.lock().unwrap();
+            let Some(delivery) = deliveries.iter_mut().find(|delivery| delivery.id == id) else {
+                return;
+            };
+
+            delivery.attempts = attempt + 1;
+
+            match result {
+                Ok(status) if (200..300).contains(&status) => {
+                    delivery.status = DeliveryStatus::Delivered(status);
+                    delivery.last_error = None;
+                    return;
+                },
+                Ok(status) => delivery.last_error = Some(format!("Endpoint responded with status {status}")),
+                Err(err) => delivery.last_error = Some(err.to_string())
+            }
+        }
+
+        if let Some(delivery) = self.deliveries.lock().unwrap().iter_mut().find(|delivery| delivery.id == id) {
+            delivery.status = DeliveryStatus::Failed;
+        }
+    }
+
+    fn retry_delay(&self, attempt: u32) -> Duration {
+        self.base_delay.saturating_mul(2_u32.saturating_pow(attempt - 1)).min(self.max_delay)
+    }
+
+    fn send(&self, url: &str, body: &[u8]) -> io::Result<u16> {
+        let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs();
+        let signature = self.sign(timestamp, body);
+        let timestamp = timestamp.to_string();
+
+        let headers = [
+            ("content-type", "application/json"),
+            (TIMESTAMP_HEADER, timestamp.as_str()),
+            (SIGNATURE_HEADER, signature.as_str())
+        ];
+
+        Ok(self.client.request(HttpMethod::Post, url, &headers, body.to_vec())?.status().as_u16())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn caps_the_retry_delay() {
+        let dispatcher = WebhookDispatcher::new("secret").base_delay(Duration::from_secs(1)).max_delay(Duration::from_secs(30));
+
+        assert_eq!(dispatcher.retry_delay(1), Duration::from_secs(1));
+        assert_eq!(dispatcher.retry_delay(4), Duration::from_secs(8));
+        assert_eq!(dispatcher.retry_delay(6), Duration::from_secs(30));
+        assert_eq!(dispatcher.retry_delay(40), Duration::from_secs(30));
+    }
+
+    #[test]
+    fn keeps_a_bounded_delivery_history() {
+        let dispatcher = WebhookDispatcher::new("secret").history(2).max_attempts(1).workers(1);
+
+        for _ in 0..3 {
+            dispatcher.dispatch("http://127.0.0.1:9/hook", &"event").unwrap();
+        }
+
+        let ids: Vec<u64> = dispatcher.deliveries().iter().map(|delivery| delivery.id).collect();
+        assert_eq!(ids, vec![2, 3]);
+        assert!(dispatcher.delivery(1).is_none());
+    }
+}